@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+
+/// Fades the window in when it opens, and out before it closes, for frontends that draw everything themselves
+pub struct Animation {
+    duration: Duration,
+    /// when the current animation started
+    start: Instant,
+    closing: bool
+}
+
+impl Animation {
+    /// Starts the opening animation
+    /// A duration of 0 disables animations
+    pub fn new(duration_ms: u64) -> Self {
+        Self { duration: Duration::from_millis(duration_ms), start: Instant::now(), closing: false }
+    }
+
+    /// Starts the closing animation, from where the opening one is if it is still running
+    pub fn close(&mut self) {
+        if self.closing { return }
+
+        let shown = self.linear();
+        self.closing = true;
+        // pretend the closing animation started early enough to be at the current visibility
+        let elapsed = self.duration.mul_f32(1.0 - shown);
+        self.start = Instant::now().checked_sub(elapsed).unwrap_or(self.start);
+    }
+
+    /// Fraction of the current animation that has run, from 0.0 to 1.0
+    fn progress(&self) -> f32 {
+        if self.duration.is_zero() { return 1.0 }
+        (self.start.elapsed().as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+
+    /// Visibility of the window without easing
+    fn linear(&self) -> f32 {
+        if self.closing { 1.0 - self.progress() } else { self.progress() }
+    }
+
+    /// How visible the window should be, from 0.0 (hidden) to 1.0 (fully shown)
+    pub fn visibility(&self) -> f32 {
+        // ease out, so the window appears quickly and settles slowly
+        1.0 - (1.0 - self.linear()).powi(3)
+    }
+
+    /// Wether the window should be redrawn continuously
+    pub fn running(&self) -> bool {
+        self.progress() < 1.0
+    }
+
+    /// Wether the closing animation is over, and the window can be closed
+    pub fn closed(&self) -> bool {
+        self.closing && !self.running()
+    }
+}
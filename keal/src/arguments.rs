@@ -1,9 +1,52 @@
 use std::sync::OnceLock;
 
+use crate::config::{config, Layout};
+
 pub struct Arguments {
     pub dmenu: bool,
     pub protocol: Protocol,
-    pub timings: bool
+    pub timings: bool,
+    /// frontend chosen on the command line, overrides the `frontend` config field
+    pub frontend: Option<String>,
+    /// input to print the entries of, without opening a window
+    pub headless: Option<String>,
+    /// print headless results as JSON
+    pub json: bool,
+    /// serve the plugin manager over stdio, for external UIs
+    pub rpc: bool,
+    /// write a desktop entry for keal, and exit
+    pub install_service: bool,
+    /// keep the window open after launching something
+    pub stay_open: bool,
+    /// number of entries the window should fit, instead of the default height
+    pub lines: Option<usize>,
+    pub width: Option<Width>,
+    /// label left of the input, instead of the `prompt` config field
+    pub prompt: Option<String>,
+    /// match words of the query as substrings instead of fuzzy matching them
+    pub exact: bool,
+    /// show how the score of every entry is made up in its comment
+    pub debug_scores: bool
+}
+
+/// Width of the window given on the command line
+#[derive(Clone, Copy, Debug)]
+pub enum Width {
+    Pixels(f32),
+    /// percentage of the width of the monitor
+    Percent(f32)
+}
+
+impl std::str::FromStr for Width {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |_| "expected a width in pixels like `600`, or a percentage like `50%`";
+        match s.strip_suffix('%') {
+            Some(percent) => percent.parse().map(Width::Percent).map_err(invalid),
+            None => s.trim_end_matches("px").parse().map(Width::Pixels).map_err(invalid)
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -12,52 +55,236 @@ pub enum Protocol {
     Keal
 }
 
+impl Default for Arguments {
+    fn default() -> Self {
+        Arguments {
+            dmenu: false,
+            protocol: Protocol::RofiExtended,
+            timings: false,
+            frontend: None,
+            headless: None,
+            json: false,
+            rpc: false,
+            install_service: false,
+            stay_open: false,
+            lines: None,
+            width: None,
+            prompt: None,
+            exact: false,
+            debug_scores: false
+        }
+    }
+}
+
 static ARGUMENTS: OnceLock<Arguments> = OnceLock::new();
 pub fn arguments() -> &'static Arguments {
     ARGUMENTS.get().expect("arguments should have been initialized in main")
 }
 
 pub enum Error {
+    /// the arguments asked to exit right away, like `--help`
     Exit,
-    UnknownFlag(String)
+    UnknownFlag(String),
+    /// a flag that takes a value was given none
+    MissingValue(String),
+    /// a flag that doesn't take a value was given one, like `--dmenu=yes`
+    UnexpectedValue(String),
+    /// the value given to a flag couldn't be parsed, with the reason why
+    InvalidValue(String, &'static str)
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Exit => write!(f, "exit requested"),
+            Error::UnknownFlag(flag) => write!(f, "unknown flag `{flag}`, see `keal --help`"),
+            Error::MissingValue(flag) => write!(f, "missing value for flag `{flag}`"),
+            Error::UnexpectedValue(flag) => write!(f, "flag `{flag}` doesn't take a value"),
+            Error::InvalidValue(value, reason) => write!(f, "invalid value `{value}`: {reason}")
+        }
+    }
+}
+
+/// Description of a command line flag
+/// Flags can be given as `--long`, `-s`, or grouped like `-dk`.
+/// Values can be given as `--long value`, `--long=value`, `-s value` or `-svalue`.
+/// Like with rofi, long flags can also be given with a single dash, like `-width 50%`.
+/// When a flag is repeated, the last value is kept.
+struct Flag {
+    long: &'static str,
+    short: Option<char>,
+    /// name of the value shown in the help, `None` if the flag doesn't take one
+    value: Option<&'static str>,
+    help: &'static str,
+    apply: fn(&mut Arguments, Option<String>) -> Result<(), Error>
+}
+
+impl Flag {
+    /// Gets the value of the flag named `name`, either `attached` to it or from the next argument
+    fn take_value(&self, name: String, attached: Option<String>, args: &mut impl Iterator<Item = String>) -> Result<Option<String>, Error> {
+        match (self.value, attached) {
+            (None, None) => Ok(None),
+            (None, Some(_)) => Err(Error::UnexpectedValue(name)),
+            (Some(_), Some(value)) => Ok(Some(value)),
+            (Some(_), None) => args.next().map(Some).ok_or(Error::MissingValue(name))
+        }
+    }
 }
 
+const FLAGS: &[Flag] = &[
+    Flag { long: "help", short: Some('h'), value: None, help: "Show this help and exit", apply: |_, _| {
+        Arguments::print_help();
+        Err(Error::Exit)
+    } },
+    Flag { long: "version", short: Some('v'), value: None, help: "Show the current version of keal", apply: |_, _| {
+        Arguments::print_version();
+        Err(Error::Exit)
+    } },
+    Flag { long: "dmenu", short: Some('d'), value: None, help: "Launch keal in dmenu mode (pipe choices into it)", apply: |a, _| {
+        a.dmenu = true;
+        Ok(())
+    } },
+    Flag { long: "keal", short: Some('k'), value: None, help: "In dmenu mode, use the same protocol as plugins, instead of the default rofi extended dmenu protocol", apply: |a, _| {
+        a.protocol = Protocol::Keal;
+        Ok(())
+    } },
+    Flag { long: "case-insensitive", short: Some('i'), value: None, help: "Match case-insensitively, like dmenu's -i (keal always does, this is accepted for compatibility)", apply: |_, _| Ok(()) },
+    Flag { long: "exact", short: None, value: None, help: "Match every word of the query as a substring instead of fuzzy matching, for predictable dmenu matching", apply: |a, _| {
+        a.exact = true;
+        Ok(())
+    } },
+    Flag { long: "debug-scores", short: None, value: None, help: "Show the matching score, usage and priority contributions and plugin of every entry in its comment", apply: |a, _| {
+        a.debug_scores = true;
+        Ok(())
+    } },
+    Flag { long: "timings", short: None, value: None, help: "Show how long the different keal systems take to start up", apply: |a, _| {
+        a.timings = true;
+        Ok(())
+    } },
+    Flag { long: "frontend", short: Some('f'), value: Some("name"), help: "Use the given frontend (iced, piet or raylib) if it was compiled in, instead of the one in the config", apply: |a, v| {
+        a.frontend = v;
+        Ok(())
+    } },
+    Flag { long: "headless", short: None, value: Some("input"), help: "Print the entries matching the input without opening a window, one per line with their comment after a tab", apply: |a, v| {
+        a.headless = v;
+        Ok(())
+    } },
+    Flag { long: "json", short: None, value: None, help: "With --headless, print entries as a JSON array instead", apply: |a, _| {
+        a.json = true;
+        Ok(())
+    } },
+    Flag { long: "rpc", short: None, value: None, help: "Don't open a window, and communicate with an external UI through newline-delimited JSON on stdin and stdout", apply: |a, _| {
+        a.rpc = true;
+        Ok(())
+    } },
+    Flag { long: "stay-open", short: None, value: None, help: "Keep the window open after launching something, to use keal as a persistent launch panel", apply: |a, _| {
+        a.stay_open = true;
+        Ok(())
+    } },
+    Flag { long: "lines", short: Some('l'), value: Some("count"), help: "Size the window to fit this many entries", apply: |a, v| {
+        let v = v.unwrap();
+        a.lines = Some(v.parse().map_err(|_| Error::InvalidValue(v, "expected a number of lines"))?);
+        Ok(())
+    } },
+    Flag { long: "width", short: None, value: Some("px|%"), help: "Width of the window, in pixels or as a percentage of the screen, like `600` or `50%`", apply: |a, v| {
+        let v = v.unwrap();
+        a.width = Some(v.parse().map_err(|e| Error::InvalidValue(v, e))?);
+        Ok(())
+    } },
+    Flag { long: "prompt", short: Some('p'), value: Some("text"), help: "Show a label left of the input, like `Run:`", apply: |a, v| {
+        a.prompt = v;
+        Ok(())
+    } },
+    Flag { long: "install-service", short: None, value: None, help: "Write a desktop entry for keal so that it shows up in desktop menus and other launchers, and exit", apply: |a, _| {
+        a.install_service = true;
+        Ok(())
+    } },
+];
+
 impl Arguments {
+    /// Initializes arguments with their default values, when keal is embedded in another program and its command line isn't relevant
+    /// Does nothing if arguments were already initialized
+    pub fn init_default() -> &'static Self {
+        ARGUMENTS.get_or_init(Self::default)
+    }
+
+    /// Initializes arguments like `keal --dmenu`, when an embedding program only shows choices it pipes in
+    /// Does nothing if arguments were already initialized
+    pub fn init_dmenu(protocol: Protocol) -> &'static Self {
+        ARGUMENTS.get_or_init(|| Arguments { dmenu: true, protocol, ..Self::default() })
+    }
+
     pub fn init() -> Result<&'static Self, Error> {
-        let this = Self::parse()?;
+        let this = Self::parse(std::env::args().skip(1))?; // ignore executable name
         let arguments = ARGUMENTS.get_or_init(move || this);
         Ok(arguments)
     }
 
-    fn parse() -> Result<Self, Error> {
-        let mut arguments = Arguments {
-            dmenu: false,
-            protocol: Protocol::RofiExtended,
-            timings: false
-        };
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, Error> {
+        let mut arguments = Arguments::default();
+        let mut args = args.into_iter();
 
-        let mut args = std::env::args();
-        let _ = args.next(); // ignore executable name
-        for arg in args {
-            match arg.as_str() {
-                "--dmenu" | "-d" => arguments.dmenu = true,
-                "--keal" | "-k" => arguments.protocol = Protocol::Keal,
-                "--timings" => arguments.timings = true,
-                "--help" | "-h" => {
-                    Self::print_help();
-                    Err(Error::Exit)?
-                }
-                "--version" | "-v" => {
-                    Self::print_version();
-                    Err(Error::Exit)?
+        while let Some(arg) = args.next() {
+            // rofi style long flags with a single dash, like `-width`
+            let single_dash_long = arg.strip_prefix('-')
+                .filter(|rest| !rest.starts_with('-'))
+                .filter(|rest| FLAGS.iter().any(|f| f.long.len() > 1 && rest.split('=').next() == Some(f.long)));
+
+            if let Some(long) = arg.strip_prefix("--").or(single_dash_long) {
+                let (name, attached) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_owned())),
+                    None => (long, None)
+                };
+
+                let Some(flag) = FLAGS.iter().find(|f| f.long == name) else { Err(Error::UnknownFlag(arg.clone()))? };
+                let value = flag.take_value(arg.split('=').next().unwrap().to_owned(), attached, &mut args)?;
+                (flag.apply)(&mut arguments, value)?;
+            } else if let Some(shorts) = arg.strip_prefix('-').filter(|s| !s.is_empty()) {
+                for (i, short) in shorts.char_indices() {
+                    let Some(flag) = FLAGS.iter().find(|f| f.short == Some(short)) else { Err(Error::UnknownFlag(format!("-{short}")))? };
+
+                    // a flag taking a value uses the rest of the group, like `-fpiet`
+                    let rest = &shorts[i + short.len_utf8()..];
+                    let attached = (flag.value.is_some() && !rest.is_empty()).then(|| rest.trim_start_matches('=').to_owned());
+
+                    let value = flag.take_value(format!("-{short}"), attached, &mut args)?;
+                    (flag.apply)(&mut arguments, value)?;
+                    if flag.value.is_some() { break }
                 }
-                _ => Err(Error::UnknownFlag(arg))?
+            } else {
+                Err(Error::UnknownFlag(arg))?
             }
         }
 
         Ok(arguments)
     }
 
+    /// Size of the window in logical pixels, which is `default` unless `--lines` and `--width` or the `[window]` section set it
+    /// `input_height` and `row_height` are the heights of the search bar and of an entry in the frontend
+    /// `monitor_width` is used for percentages, a 1920 pixels wide screen is assumed if it isn't known
+    /// With the horizontal layout, the window is only as high as the search bar, and bars are as wide as the monitor
+    pub fn window_size(&self, default: (f32, f32), input_height: f32, row_height: f32, monitor_width: Option<f32>) -> (f32, f32) {
+        let window = config().window;
+        let width = match self.width.or(window.width) {
+            _ if config().position.is_bar() => monitor_width.unwrap_or(1920.0),
+            Some(Width::Pixels(pixels)) => pixels,
+            Some(Width::Percent(percent)) => monitor_width.unwrap_or(1920.0) * percent / 100.0,
+            None => default.0
+        };
+        let height = match self.lines.map(|lines| lines as u64).or(window.lines) {
+            _ if config().layout == Layout::Horizontal => input_height,
+            Some(lines) => input_height + lines as f32 * row_height,
+            None => default.1
+        };
+
+        (width.max(1.0), height.max(1.0))
+    }
+
+    /// Label shown left of the input, `None` if neither `--prompt` nor the config set one
+    pub fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref().or(Some(config().prompt.as_str())).filter(|prompt| !prompt.is_empty())
+    }
+
     fn print_version() {
         println!("keal: version {}", env!("CARGO_PKG_VERSION"));
     }
@@ -66,10 +293,21 @@ impl Arguments {
         println!("usage: keal [options...]");
         println!();
         println!("options:");
-        println!("  -h, --help    Show this help and exit");
-        println!("  -v, --version Show the current version of keal");
-        println!("  -d, --dmenu   Launch keal in dmenu mode (pipe choices into it)");
-        println!("  -k, --keal    In dmenu mode, use the same protocol as plugins, instead of the default rofi extended dmenu protocol");
-        println!("      --timings Show how long the different keal systems take to start up")
+
+        for flag in FLAGS {
+            let short = flag.short.map(|s| format!("-{s},")).unwrap_or_default();
+            let long = match flag.value {
+                Some(value) => format!("--{} <{value}>", flag.long),
+                None => format!("--{}", flag.long)
+            };
+
+            // long flags with a value don't fit in the column, so their help goes on the next line
+            if long.len() > 10 {
+                println!("  {short:3} {long}");
+                println!("  {:3} {:10} {}", "", "", flag.help);
+            } else {
+                println!("  {short:3} {long:10} {}", flag.help);
+            }
+        }
     }
 }
@@ -2,7 +2,7 @@ use std::{collections::HashMap, sync::OnceLock};
 
 use indexmap::IndexMap;
 
-use crate::{xdg_utils::config_dir, ini_parser::Ini};
+use crate::{arguments::{arguments, Width}, xdg_utils::config_dir, ini_parser::Ini, keybind::{Keybind, Keybinds}};
 
 // WARN: When adding fields to the config, remember to set them in `add_from_string`!
 
@@ -11,20 +11,217 @@ use crate::{xdg_utils::config_dir, ini_parser::Ini};
 pub struct Config {
     pub font: String,
     pub font_size: f32,
+    /// size of entry icons, follows `font_size` when set to 0
+    pub icon_size: f32,
+    /// space between an icon and the name of its entry
+    pub icon_padding: f32,
     pub icon_theme: Vec<String>,
     pub usage_frequency: bool,
+    /// weights of the fuzzy matching score, usage frecency and plugin priority when sorting entries
+    pub match_weight: f32,
+    pub usage_weight: f32,
+    pub priority_weight: f32,
+    pub show_sections: bool,
+    /// search every plugin when no prefix is typed, instead of only the default ones
+    pub global_search: bool,
+    /// how many entries each plugin shows with `global_search`
+    pub global_search_limit: u64,
+    /// launch the only entry left as soon as a query narrows the list down to it
+    pub auto_accept_single: bool,
+    /// when disabled, hovering doesn't highlight entries and clicks are ignored
+    pub mouse_enabled: bool,
+    /// multiplier of the distance scrolled by the mouse wheel or touchpad
+    pub scroll_speed: f32,
+    /// how long the arrow keys are held before moving the selection repeatedly, and how often it moves then
+    pub repeat_delay_ms: u64,
+    pub repeat_interval_ms: u64,
     pub terminal_path: String,
     pub placeholder_text: String,
+    /// label left of the input, like dmenu's `-p`, `--prompt` takes precedence over it
+    pub prompt: String,
+    pub escape_action: EscapeAction,
+    pub show_on_empty: ShowOnEmpty,
+    pub density: Density,
+    /// when disabled, entries are listed without their icon or comment
+    pub show_icons: bool,
+    pub show_comments: bool,
+    pub wrap: Wrap,
+    /// with `wrap = ellipsis`, shorten paths by removing the directories in the middle
+    pub ellipsize_paths: bool,
+    /// duration of the open and close animations, 0 to disable them
+    pub animation_ms: u64,
+    /// colors loaded before the user's
+    pub theme: ColorTheme,
+    /// disables animations and cursor blinking
+    pub reduced_motion: bool,
+    /// grabs the keyboard on X11 like dmenu, so that keys can't go to another window while keal is open
+    pub grab_keyboard: bool,
+    pub window_level: WindowLevel,
+    /// shrinks the window to fit the entries when there are few of them, up to its usual height
+    pub auto_shrink: bool,
+    pub layout: Layout,
+    pub position: WindowPosition,
     pub default_plugins: Vec<String>,
+    pub fallback_plugins: Vec<String>,
+    /// plugins started with keal, so that typing their prefix doesn't wait for them to start
+    pub preload: Preload,
+    /// limits of user plugin processes, 0 to leave them unlimited
+    pub plugin_memory_limit_mb: u64,
+    pub plugin_cpu_limit_s: u64,
+    /// user plugins that take longer than this to answer an event are killed, 0 to wait forever
+    pub plugin_timeout_ms: u64,
+    /// from the `[keybinds]` section
+    pub keybinds: Keybinds,
+    /// from the `[window]` section
+    pub window: WindowSize,
     pub plugin_overrides: HashMap<String, Override>,
-    pub plugin_configs: HashMap<String, IndexMap<String, String>>
+    pub plugin_configs: HashMap<String, IndexMap<String, String>>,
+    /// set when no configuration existed, and a default one was just written
+    pub first_run: bool
+}
+
+/// What pressing escape does
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EscapeAction {
+    /// always close the window
+    #[default]
+    Close,
+    /// clear the query and exit plugin mode, only close the window if it's already empty
+    ClearThenClose
+}
+
+/// Which entries are shown when nothing is typed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ShowOnEmpty {
+    /// only entries that were launched before, by frecency
+    Recent,
+    /// every entry of the default plugins
+    #[default]
+    All,
+    /// nothing, like dmenu
+    None
+}
+
+/// How much space entries take up
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Density {
+    Compact,
+    #[default]
+    Comfortable,
+    Spacious
+}
+
+impl Density {
+    /// Space above and below the text of every entry, in pixels
+    pub fn row_padding(self) -> f32 {
+        match self {
+            Density::Compact => 4.0,
+            Density::Comfortable => 10.0,
+            Density::Spacious => 16.0
+        }
+    }
+}
+
+/// What happens to names and comments that are too long to fit on one line
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    /// cut the end and replace it with "…"
+    Ellipsis,
+    /// continue on the next lines
+    #[default]
+    Wrap
+}
+
+/// Where the window is stacked compared to other windows
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WindowLevel {
+    Normal,
+    /// above other windows, except fullscreen ones with most window managers
+    #[default]
+    OnTop,
+    /// above every window, including fullscreen ones, on X11 the window manager is bypassed and the keyboard grabbed
+    AboveFullscreen
+}
+
+/// How entries are laid out
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// one entry per line, below the search bar
+    #[default]
+    Vertical,
+    /// entries follow the search bar on a single line, like dmenu without `-l`
+    Horizontal
+}
+
+/// Where the window is on the monitor
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WindowPosition {
+    #[default]
+    Center,
+    /// a bar spanning the top of the monitor, with the horizontal layout, like dmenu
+    TopBar,
+    /// a bar spanning the bottom of the monitor, like `dmenu -b`
+    BottomBar
+}
+
+impl WindowPosition {
+    pub fn is_bar(self) -> bool {
+        self != WindowPosition::Center
+    }
+}
+
+/// Size of the window, `--width` and `--lines` take precedence over it
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WindowSize {
+    pub width: Option<Width>,
+    /// number of entries the window fits
+    pub lines: Option<u64>
+}
+
+/// Which plugins are started along with keal
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Preload {
+    /// the plugins whose prefix was typed the most, by frecency
+    Auto,
+    /// these prefixes
+    List(Vec<String>),
+    #[default]
+    Off
+}
+
+/// Bundled colors, which the `[colors]` section of the user's configuration can still override
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorTheme {
+    /// high contrast when the GTK theme is a high contrast one, like GNOME's `HighContrast`
+    #[default]
+    Auto,
+    Default,
+    HighContrast
+}
+
+impl ColorTheme {
+    fn high_contrast(self) -> bool {
+        match self {
+            ColorTheme::Auto => std::env::var("GTK_THEME").is_ok_and(|theme| theme.to_lowercase().contains("highcontrast")),
+            ColorTheme::Default => false,
+            ColorTheme::HighContrast => true
+        }
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct Override {
     pub prefix: Option<String>,
     pub icon: Option<String>,
-    pub comment: Option<String>
+    pub default_icon: Option<String>,
+    pub comment: Option<String>,
+    pub keywords: Option<Vec<String>>,
+    /// other prefixes that select the plugin
+    pub aliases: Option<Vec<String>>,
+    /// added to the score of every entry of the plugin, multiplied by `priority_weight`
+    pub priority: Option<f32>,
+    /// sort the entries of the plugin by usage frequency, overrides `usage_frequency`
+    pub usage_sort: Option<bool>
 }
 
 impl Default for Config {
@@ -32,13 +229,50 @@ impl Default for Config {
         Self {
             font: String::new(),
             font_size: 0.0,
+            icon_size: 0.0,
+            icon_padding: 0.0,
             icon_theme: vec![],
             terminal_path: String::new(),
             placeholder_text: String::new(),
+            prompt: String::new(),
+            escape_action: EscapeAction::Close,
+            show_on_empty: ShowOnEmpty::All,
+            density: Density::Comfortable,
+            show_icons: false,
+            show_comments: false,
+            wrap: Wrap::Wrap,
+            ellipsize_paths: false,
+            animation_ms: 0,
+            theme: ColorTheme::Auto,
+            reduced_motion: false,
+            grab_keyboard: false,
+            window_level: WindowLevel::OnTop,
+            auto_shrink: false,
+            layout: Layout::Vertical,
+            position: WindowPosition::Center,
             usage_frequency: false,
+            match_weight: 0.0,
+            usage_weight: 0.0,
+            priority_weight: 0.0,
+            show_sections: false,
+            global_search: false,
+            global_search_limit: 0,
+            auto_accept_single: false,
+            mouse_enabled: false,
+            scroll_speed: 0.0,
+            repeat_delay_ms: 0,
+            repeat_interval_ms: 0,
             default_plugins: Vec::new(),
+            fallback_plugins: Vec::new(),
+            preload: Preload::Off,
+            plugin_memory_limit_mb: 0,
+            plugin_cpu_limit_s: 0,
+            plugin_timeout_ms: 0,
+            keybinds: Keybinds::default(),
+            window: WindowSize::default(),
             plugin_overrides: Default::default(),
-            plugin_configs: Default::default()
+            plugin_configs: Default::default(),
+            first_run: false
         }
     }
 }
@@ -76,8 +310,17 @@ macro_rules! parse_fields {
 }
 
 impl Config {
+    /// Loads the configuration, without ever writing to the configuration directory
     pub fn init<T: FrontendConfig>(frontend: &mut T) -> &'static Self {
-        CONFIG.get_or_init(|| Self::load(frontend))
+        CONFIG.get_or_init(|| Self::load(frontend, false))
+    }
+
+    /// Loads the configuration of the launcher, writing a commented default one the first time it is opened
+    /// Pickers (`--dmenu`), `--headless`, `--rpc` and the [`Engine`](crate::engine::Engine) use [`Config::init`] instead,
+    /// so that scripts never leave a configuration behind
+    pub fn init_launcher<T: FrontendConfig>(frontend: &mut T) -> &'static Self {
+        let interactive = !arguments().dmenu && arguments().headless.is_none() && !arguments().rpc;
+        CONFIG.get_or_init(|| Self::load(frontend, interactive))
     }
 
     /// Loads the default included configuration (in public/default-config.ini)
@@ -91,9 +334,30 @@ impl Config {
     fn add_from_string<T: FrontendConfig>(&mut self, frontend: &mut T, content: String) {
         let mut file = Ini::from_string(content, &['#', ';']);
 
+        // `[section.dmenu]` replaces keys of `[section]` with `--dmenu`, so that the picker scripts open can look different from the launcher
+        let dmenu_sections: Vec<String> = file.section_names().filter(|name| name.ends_with(".dmenu")).cloned().collect();
+        for name in dmenu_sections {
+            let section = file.remove_section(&name).unwrap();
+            if arguments().dmenu {
+                file.merge_section(name.trim_end_matches(".dmenu"), section);
+            }
+        }
+
         for field in file.section("keal").into_iter().flat_map(|s| s.iter()) {
             parse_fields!(self, field, (
-                font, font_size, icon_theme, usage_frequency, terminal_path, placeholder_text, default_plugins
+                font, font_size, icon_size, icon_padding, icon_theme, usage_frequency, match_weight, usage_weight, priority_weight, show_sections, global_search, global_search_limit, auto_accept_single, mouse_enabled, scroll_speed, repeat_delay_ms, repeat_interval_ms, terminal_path, placeholder_text, prompt, escape_action, show_on_empty, density, show_icons, show_comments, wrap, ellipsize_paths, animation_ms, theme, reduced_motion, grab_keyboard, window_level, auto_shrink, layout, position, default_plugins, fallback_plugins, preload, plugin_memory_limit_mb, plugin_cpu_limit_s, plugin_timeout_ms
+            ));
+        }
+
+        for field in file.remove_section("keybinds").into_iter().flat_map(|s| s.into_iter()) {
+            parse_fields!(self.keybinds, field, (
+                select_next, select_previous, jump_mode, next_page
+            ));
+        }
+
+        for field in file.remove_section("window").into_iter().flat_map(|s| s.into_iter()) {
+            parse_fields!(self.window, field, (
+                width, lines
             ));
         }
 
@@ -111,7 +375,7 @@ impl Config {
                     let mut over = Override::default();
                     for field in section.iter() {
                         parse_fields!(over, field, (
-                            prefix, icon, comment
+                            prefix, icon, default_icon, comment, keywords, aliases, priority, usage_sort
                         ))
                     }
                     self.plugin_overrides.insert(name.to_owned(), over);
@@ -124,19 +388,81 @@ impl Config {
         }
     }
 
-    fn load<T: FrontendConfig>(frontend: &mut T) -> Self {
+    fn load<T: FrontendConfig>(frontend: &mut T, write_default: bool) -> Self {
         let mut config = Config::default_config(frontend);
 
         let Ok(mut config_path) = config_dir() else { return config };
+
+        if write_default && !config_path.exists() {
+            match crate::install::write_default_config(&config_path) {
+                Ok(()) => config.first_run = true,
+                Err(e) => eprintln!("failed to write default configuration: {e:#}")
+            }
+        }
+
         config_path.push("config.ini");
+        let content = std::fs::read_to_string(config_path).ok();
+
+        // the bundled theme goes between the default colors and the user's, so that it can still be tweaked
+        let theme = content.as_deref()
+            .and_then(|content| Ini::from_string(content.to_owned(), &['#', ';']).section_iter("keal").find(|(name, _)| *name == "theme").map(|(_, value)| value.clone()))
+            .and_then(|theme| theme.my_parse().ok())
+            .unwrap_or(config.theme);
+        if theme.high_contrast() {
+            config.add_from_string(frontend, include_str!("../../public/high-contrast.ini").to_owned());
+        }
+
+        if let Some(content) = content {
+            config.add_from_string(frontend, content);
+        }
 
-        let Ok(content) = std::fs::read_to_string(config_path) else { return config };
+        if config.reduced_motion {
+            config.animation_ms = 0;
+        }
+        if config.icon_size <= 0.0 {
+            config.icon_size = config.font_size;
+        }
+        // bars only have room for a single line
+        if config.position.is_bar() {
+            config.layout = Layout::Horizontal;
+        }
+        // the horizontal layout is always a single line high
+        if config.layout == Layout::Horizontal {
+            config.auto_shrink = false;
+        }
 
-        config.add_from_string(frontend, content);
+        // `auto` stands for the icon theme of the desktop, and every theme falls back to hicolor
+        config.icon_theme = config.icon_theme.iter()
+            .filter_map(|theme| match theme.as_str() {
+                "auto" => crate::icon::desktop_icon_theme(),
+                _ => Some(theme.clone())
+            })
+            .collect();
+        if !config.icon_theme.iter().any(|theme| theme == "hicolor") {
+            config.icon_theme.push("hicolor".to_owned());
+        }
         config
     }
 }
 
+/// Reads the `frontend` field of the configuration
+/// The frontend has to be known before the configuration is loaded, since it is loaded along with the frontend's theme
+pub fn read_frontend() -> String {
+    let user_config = config_dir().ok().and_then(|mut config_path| {
+        config_path.push("config.ini");
+        std::fs::read_to_string(config_path).ok()
+    });
+
+    // the user's config takes precedence over the default one
+    [user_config, Some(include_str!("../../public/default-config.ini").to_owned())].into_iter().flatten()
+        .find_map(|content| {
+            let file = Ini::from_string(content, &['#', ';']);
+            let frontend = file.section_iter("keal").find(|(name, _)| *name == "frontend").map(|(_, value)| value.clone());
+            frontend
+        })
+        .unwrap_or_default()
+}
+
 trait MyFromStr<T> {
     fn my_parse(&self) -> Result<T, &str>;
 }
@@ -174,3 +500,116 @@ impl MyFromStr<f32> for str {
         self.parse().map_err(|_| "couldn't parse number")
     }
 }
+
+impl MyFromStr<u64> for str {
+    fn my_parse(&self) -> Result<u64, &'static str> {
+        self.parse().map_err(|_| "couldn't parse integer")
+    }
+}
+
+impl MyFromStr<Keybind> for str {
+    fn my_parse(&self) -> Result<Keybind, &'static str> {
+        Keybind::parse(self)
+    }
+}
+
+impl MyFromStr<Width> for str {
+    fn my_parse(&self) -> Result<Width, &'static str> {
+        self.parse()
+    }
+}
+
+impl MyFromStr<EscapeAction> for str {
+    fn my_parse(&self) -> Result<EscapeAction, &'static str> {
+        match self {
+            "close" => Ok(EscapeAction::Close),
+            "clear_then_close" => Ok(EscapeAction::ClearThenClose),
+            _ => Err("unknown escape action, expected `close` or `clear_then_close`")
+        }
+    }
+}
+
+impl MyFromStr<Density> for str {
+    fn my_parse(&self) -> Result<Density, &'static str> {
+        match self {
+            "compact" => Ok(Density::Compact),
+            "comfortable" => Ok(Density::Comfortable),
+            "spacious" => Ok(Density::Spacious),
+            _ => Err("unknown density, expected `compact`, `comfortable` or `spacious`")
+        }
+    }
+}
+
+impl MyFromStr<ColorTheme> for str {
+    fn my_parse(&self) -> Result<ColorTheme, &'static str> {
+        match self {
+            "auto" => Ok(ColorTheme::Auto),
+            "default" => Ok(ColorTheme::Default),
+            "high_contrast" => Ok(ColorTheme::HighContrast),
+            _ => Err("unknown theme, expected `auto`, `default` or `high_contrast`")
+        }
+    }
+}
+
+impl MyFromStr<Wrap> for str {
+    fn my_parse(&self) -> Result<Wrap, &'static str> {
+        match self {
+            "ellipsis" => Ok(Wrap::Ellipsis),
+            "wrap" => Ok(Wrap::Wrap),
+            _ => Err("unknown wrap mode, expected `ellipsis` or `wrap`")
+        }
+    }
+}
+
+impl MyFromStr<Layout> for str {
+    fn my_parse(&self) -> Result<Layout, &'static str> {
+        match self {
+            "vertical" => Ok(Layout::Vertical),
+            "horizontal" => Ok(Layout::Horizontal),
+            _ => Err("unknown layout, expected `vertical` or `horizontal`")
+        }
+    }
+}
+
+impl MyFromStr<WindowPosition> for str {
+    fn my_parse(&self) -> Result<WindowPosition, &'static str> {
+        match self {
+            "center" => Ok(WindowPosition::Center),
+            "top_bar" => Ok(WindowPosition::TopBar),
+            "bottom_bar" => Ok(WindowPosition::BottomBar),
+            _ => Err("unknown position, expected `center`, `top_bar` or `bottom_bar`")
+        }
+    }
+}
+
+impl MyFromStr<WindowLevel> for str {
+    fn my_parse(&self) -> Result<WindowLevel, &'static str> {
+        match self {
+            "normal" => Ok(WindowLevel::Normal),
+            "on_top" => Ok(WindowLevel::OnTop),
+            "above_fullscreen" => Ok(WindowLevel::AboveFullscreen),
+            _ => Err("unknown window level, expected `normal`, `on_top` or `above_fullscreen`")
+        }
+    }
+}
+
+impl MyFromStr<Preload> for str {
+    fn my_parse(&self) -> Result<Preload, &'static str> {
+        match self {
+            "auto" => Ok(Preload::Auto),
+            "off" | "" => Ok(Preload::Off),
+            _ => Ok(Preload::List(self.split(',').map(|prefix| prefix.trim().to_owned()).filter(|prefix| !prefix.is_empty()).collect()))
+        }
+    }
+}
+
+impl MyFromStr<ShowOnEmpty> for str {
+    fn my_parse(&self) -> Result<ShowOnEmpty, &'static str> {
+        match self {
+            "recent" => Ok(ShowOnEmpty::Recent),
+            "all" => Ok(ShowOnEmpty::All),
+            "none" => Ok(ShowOnEmpty::None),
+            _ => Err("unknown value, expected `recent`, `all` or `none`")
+        }
+    }
+}
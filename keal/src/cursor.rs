@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+use crate::config::config;
+
+/// Blinking state of a text cursor, for frontends that draw their own text input
+pub struct CursorBlink {
+    /// when the cursor was last shown
+    since: Instant
+}
+
+impl Default for CursorBlink {
+    fn default() -> Self {
+        Self { since: Instant::now() }
+    }
+}
+
+impl CursorBlink {
+    /// Shows the cursor and restarts the blink cycle
+    /// Call this whenever the text is modified or the cursor moves, so that the cursor stays visible while typing
+    pub fn reset(&mut self) {
+        self.since = Instant::now();
+    }
+
+    /// Wether the cursor should be drawn, given how long it stays visible and hidden in milliseconds
+    /// A blink time of 0, or `reduced_motion`, disables blinking
+    pub fn visible(&self, blink_ms: u64) -> bool {
+        if blink_ms == 0 || config().reduced_motion { return true }
+
+        let blink_ms = blink_ms as u128;
+        self.since.elapsed().as_millis() % (2*blink_ms) < blink_ms
+    }
+
+    /// When the cursor will next appear or disappear, so that frontends can sleep until then
+    /// Returns `None` if blinking is disabled
+    pub fn next_blink(&self, blink_ms: u64) -> Option<Instant> {
+        if blink_ms == 0 || config().reduced_motion { return None }
+
+        let elapsed = self.since.elapsed().as_millis() as u64;
+        Some(Instant::now() + Duration::from_millis(blink_ms - elapsed % blink_ms))
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+
+use crate::sensitive::{is_sensitive, zeroize};
+
+/// Maximum number of undo steps kept, older ones are forgotten
+const HISTORY_SIZE: usize = 100;
+
+/// State of a text input, restored by undo and redo
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// prefix of the selected plugin, shown as a chip before the text
+    pub prefix: Option<String>,
+    pub text: String,
+    /// byte index of the cursor
+    pub cursor: usize
+}
+
+impl Snapshot {
+    fn new(prefix: Option<&str>, text: &str, cursor: usize) -> Self {
+        Snapshot { prefix: prefix.map(str::to_owned), text: text.to_owned(), cursor }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    /// typing characters
+    Insert,
+    /// removing characters one by one
+    Delete,
+    /// anything else (pasting, cutting, replacing a selection)
+    Other
+}
+
+/// Undo and redo history of a text input
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// kind of the last recorded edit, used to group consecutive insertions or deletions into a single step
+    last_kind: Option<EditKind>
+}
+
+impl EditHistory {
+    /// Saves the state of the input before an edit of the given kind
+    /// Consecutive insertions or consecutive deletions are grouped in a single undo step
+    /// Nothing is recorded in a sensitive session
+    pub fn record(&mut self, kind: EditKind, prefix: Option<&str>, text: &str, cursor: usize) {
+        self.redo.clear();
+        if is_sensitive() { return }
+        if kind != EditKind::Other && self.last_kind == Some(kind) { return }
+
+        self.last_kind = Some(kind);
+        self.push_undo(Snapshot::new(prefix, text, cursor));
+    }
+
+    /// Ends the current group of insertions or deletions, for instance when the cursor moves
+    pub fn split(&mut self) {
+        self.last_kind = None;
+    }
+
+    /// Returns the state to restore, given the current one
+    pub fn undo(&mut self, prefix: Option<&str>, text: &str, cursor: usize) -> Option<Snapshot> {
+        let snapshot = self.undo.pop_back()?;
+        self.redo.push(Snapshot::new(prefix, text, cursor));
+        self.last_kind = None;
+        Some(snapshot)
+    }
+
+    /// Returns the state to restore, given the current one
+    pub fn redo(&mut self, prefix: Option<&str>, text: &str, cursor: usize) -> Option<Snapshot> {
+        let snapshot = self.redo.pop()?;
+        self.push_undo(Snapshot::new(prefix, text, cursor));
+        self.last_kind = None;
+        Some(snapshot)
+    }
+
+    /// Forgets every step, zeroing the text they kept
+    pub fn clear(&mut self) {
+        for mut snapshot in self.undo.drain(..).chain(self.redo.drain(..)) {
+            zeroize(&mut snapshot.text);
+        }
+        self.last_kind = None;
+    }
+
+    fn push_undo(&mut self, snapshot: Snapshot) {
+        if self.undo.len() == HISTORY_SIZE {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(snapshot);
+    }
+}
+
+impl Drop for EditHistory {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
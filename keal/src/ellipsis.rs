@@ -0,0 +1,60 @@
+use std::borrow::Cow;
+
+const ELLIPSIS: &str = "…";
+
+/// Shortens `text` with an ellipsis so that it fits in `max_width`, as given by `measure`
+/// With `paths`, text that looks like a path has its middle directories removed instead, like `/home/…/project/file.rs`
+/// Only the first line is kept, and the text is returned unchanged if it already fits
+pub fn ellipsize<'a>(text: &'a str, max_width: f32, paths: bool, mut measure: impl FnMut(&str) -> f32) -> Cow<'a, str> {
+    let multiline = text.contains('\n');
+    if !multiline && measure(text) <= max_width { return Cow::Borrowed(text) }
+    let text = text.lines().next().unwrap_or_default();
+
+    if paths && !multiline && is_path(text) {
+        if let Some(shortened) = ellipsize_path(text, max_width, &mut measure) {
+            return Cow::Owned(shortened);
+        }
+    }
+
+    Cow::Owned(ellipsize_end(text, max_width, &mut measure))
+}
+
+/// Single line text without spaces, starting like an absolute or home path
+fn is_path(text: &str) -> bool {
+    (text.starts_with('/') || text.starts_with("~/")) && !text.contains(char::is_whitespace)
+}
+
+/// Keeps the first directory and as many trailing components as possible
+/// Returns `None` if even the last component doesn't fit
+fn ellipsize_path(text: &str, max_width: f32, measure: &mut impl FnMut(&str) -> f32) -> Option<String> {
+    // `/home/user/project/file.rs` is split into `/home` and `user/project/file.rs`
+    let first_end = text[1..].find('/')? + 1;
+    let (first, rest) = (&text[..first_end], &text[first_end+1..]);
+
+    // try with fewer and fewer trailing components
+    rest.match_indices('/')
+        .map(|(index, _)| &rest[index+1..])
+        .filter(|tail| !tail.is_empty())
+        .map(|tail| format!("{first}/{ELLIPSIS}/{tail}"))
+        .find(|shortened| measure(shortened) <= max_width)
+}
+
+/// Cuts the end of the text, keeping as many characters as fit before the ellipsis
+fn ellipsize_end(text: &str, max_width: f32, measure: &mut impl FnMut(&str) -> f32) -> String {
+    let boundaries: Vec<usize> = text.char_indices().map(|(index, _)| index).collect();
+    // byte index after the first `n` characters
+    let end = |n: usize| boundaries.get(n).copied().unwrap_or(text.len());
+
+    // the width only grows with the number of characters kept, so binary search the longest prefix that fits
+    let (mut low, mut high) = (0, boundaries.len());
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if measure(&format!("{}{ELLIPSIS}", &text[..end(mid)])) <= max_width {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    format!("{}{ELLIPSIS}", text[..end(low)].trim_end())
+}
@@ -0,0 +1,177 @@
+//! Embedding API: keal's plugins and fuzzy matching, without any of its frontends
+//!
+//! ```no_run
+//! use keal::{engine::Engine, headless::NoTheme};
+//!
+//! let mut engine = Engine::builder().num_entries(10).build(&mut NoTheme);
+//! engine.update_input(None, "firefox", true);
+//! for entry in engine.entries() {
+//!     println!("{}", entry.name);
+//! }
+//! ```
+
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{arguments::{Arguments, Protocol}, config::{Config, FrontendConfig}, log_time, plugin::{Action, Plugin, PluginManager, entry::{parse_pattern, OwnedEntry, Page}}};
+
+/// Configures an [`Engine`]
+pub struct Builder {
+    num_entries: usize,
+    sort_by_usage: bool,
+    dmenu: Option<Protocol>
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self { num_entries: 50, sort_by_usage: true, dmenu: None }
+    }
+}
+
+impl Builder {
+    /// Number of entries generated, and added by every [`Engine::next_page`], 50 by default
+    pub fn num_entries(self, num_entries: usize) -> Self {
+        Self { num_entries, ..self }
+    }
+
+    /// Wether frequently launched entries are shown first, true by default
+    pub fn sort_by_usage(self, sort_by_usage: bool) -> Self {
+        Self { sort_by_usage, ..self }
+    }
+
+    /// Only shows the choices piped into stdin, like `keal --dmenu`
+    /// This also applies the `.dmenu` configuration sections, and makes [`crate::exit_code`] 1 when nothing is picked
+    pub fn dmenu(self, protocol: Protocol) -> Self {
+        Self { dmenu: Some(protocol), ..self }
+    }
+
+    /// Loads the configuration, with the given frontend's theme, and the plugins
+    /// The configuration and arguments are global, and are kept as is if they were already initialized
+    pub fn build<T: FrontendConfig>(self, frontend: &mut T) -> Engine {
+        let arguments = match self.dmenu {
+            Some(protocol) => Arguments::init_dmenu(protocol),
+            None => Arguments::init_default()
+        };
+        if self.dmenu.is_some() && !arguments.dmenu {
+            eprintln!("arguments were initialized without `--dmenu` before building the engine, `.dmenu` configuration sections won't apply");
+        }
+        let _config = Config::init(frontend);
+
+        let mut manager = PluginManager::default();
+        log_time("loading plugins");
+        match self.dmenu {
+            Some(protocol) => manager.load_dmenu(protocol),
+            None => manager.load_plugins()
+        }
+
+        Engine {
+            manager,
+            matcher: Matcher::default(),
+            pattern: Pattern::default(),
+            query: String::new(),
+            page: Page::default(),
+            loading: false,
+            num_entries: self.num_entries,
+            sort_by_usage: self.sort_by_usage
+        }
+    }
+}
+
+/// Plugin manager along with the state needed to match and launch its entries
+pub struct Engine {
+    manager: PluginManager,
+    matcher: Matcher,
+    pattern: Pattern,
+    query: String,
+    page: Page,
+    loading: bool,
+
+    num_entries: usize,
+    sort_by_usage: bool
+}
+
+impl Engine {
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Prefixes of every loaded plugin
+    pub fn prefixes(&self) -> impl Iterator<Item = &str> {
+        self.manager.prefixes()
+    }
+
+    /// Splits a plugin prefix typed at the start of the input, like `app firefox` into `app` and `firefox`
+    pub fn split_prefix<'a>(&self, input: &'a str) -> Option<(&'a str, &'a str)> {
+        let (prefix, query) = input.split_once(' ')?;
+        self.prefixes().any(|p| p == prefix).then_some((prefix, query))
+    }
+
+    /// Changes the input and regenerates entries, see [`PluginManager::update_input`]
+    pub fn update_input(&mut self, prefix: Option<&str>, query: &str, from_user: bool) -> Action {
+        self.query = query.to_owned();
+        parse_pattern(&mut self.pattern, query);
+
+        let action = self.manager.update_input(prefix, query, from_user);
+        self.refresh();
+        action
+    }
+
+    /// Regenerates entries without changing the input
+    /// Returns wether a plugin is loading, in which case this should be called regularly until its entries are ready
+    pub fn refresh(&mut self) -> bool {
+        self.loading = self.manager.update_loading();
+        self.page = self.manager.get_entries(&mut self.matcher, &self.pattern, self.num_entries, self.sort_by_usage);
+        self.loading
+    }
+
+    /// Adds the next `num_entries` matches to `entries`, until the input changes
+    pub fn next_page(&mut self) {
+        self.manager.next_page();
+        self.refresh();
+    }
+
+    /// Wether a plugin was loading on the last refresh, in which case `entries` contains a placeholder for it
+    pub fn loading(&self) -> bool {
+        self.loading
+    }
+
+    pub fn entries(&self) -> &[OwnedEntry] {
+        &self.page.entries
+    }
+
+    /// How many entries matched on the last refresh, including those cut from `entries`
+    pub fn total(&self) -> usize {
+        self.page.total
+    }
+
+    /// Plugin the given entry comes from
+    pub fn plugin(&self, entry: &OwnedEntry) -> &Plugin {
+        self.manager.get_plugin(entry.label.plugin_index)
+    }
+
+    /// Launches the entry at the given index of `entries`, or the query itself if there is none
+    /// Returns `None` if the index is out of bounds
+    pub fn launch(&mut self, index: Option<usize>) -> Option<Action> {
+        let label = match index {
+            Some(index) => Some(self.page.entries.get(index)?.label),
+            None => None
+        };
+        Some(self.manager.launch(&self.query, label))
+    }
+
+    /// Launches the secondary action of the entry at the given index of `entries`, like with Shift+Enter
+    /// Returns `None` if the index is out of bounds
+    pub fn launch_secondary(&mut self, index: usize) -> Option<Action> {
+        let label = self.page.entries.get(index)?.label;
+        Some(self.manager.launch_secondary(&self.query, Some(label)))
+    }
+
+    /// Stops the selected plugin, which should be done before following a `ChangeInput` action
+    pub fn kill(&mut self) {
+        self.manager.kill();
+    }
+
+    /// Waits for the selected plugin to finish, which should be done for `Fork` and `WaitAndClose` actions
+    pub fn wait(&mut self) {
+        self.manager.wait();
+    }
+}
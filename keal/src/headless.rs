@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::{config::{config, FrontendConfig}, engine::Engine, icon::{Icon, IconPath}, plugin::entry::OwnedEntry, log_time};
+
+/// Frontends without a window have no theme
+pub struct NoTheme;
+
+impl FrontendConfig for NoTheme {
+    fn sections(&self) -> &'static [&'static str] { &[] }
+    fn add_field(&mut self, _field: (String, String)) {}
+}
+
+/// Entry as printed with `--json`, and sent by the RPC frontend
+#[derive(Serialize)]
+pub struct JsonEntry<'a> {
+    name: &'a str,
+    comment: Option<&'a str>,
+    /// icon name or path, as given by the plugin
+    icon: Option<String>,
+    plugin: &'a str,
+    /// header of the group this entry starts, when `show_sections` is enabled
+    section: Option<&'a str>
+}
+
+/// Runs the plugin manager once on the given input, and prints the resulting entries to stdout, without opening a window
+/// Like in the other frontends, the input can start with a plugin prefix followed by a space
+pub fn run(input: &str, json: bool) -> anyhow::Result<()> {
+    let mut engine = Engine::builder().build(&mut NoTheme);
+
+    let (prefix, query) = match engine.split_prefix(input) {
+        Some((prefix, query)) => (Some(prefix), query),
+        None => (None, input)
+    };
+    let _ = engine.update_input(prefix, query, true);
+
+    // there is no placeholder to show, so wait for every plugin to be ready, unless one takes longer than `plugin_timeout_ms`
+    let timeout = config().plugin_timeout_ms;
+    let start = Instant::now();
+    while engine.loading() {
+        if timeout != 0 && start.elapsed() >= Duration::from_millis(timeout) {
+            eprintln!("plugins didn't load within {timeout}ms, printing the entries loaded so far");
+            break
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        engine.refresh();
+    }
+    log_time("got entries");
+
+    if json {
+        let entries: Vec<_> = engine.entries().iter().map(|entry| json_entry(&engine, entry)).collect();
+        println!("{}", serde_json::to_string(&entries)?);
+    } else {
+        for entry in engine.entries() {
+            match &entry.comment {
+                Some(comment) => println!("{}\t{comment}", entry.name),
+                None => println!("{}", entry.name)
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn json_entry<'a>(engine: &'a Engine, entry: &'a OwnedEntry) -> JsonEntry<'a> {
+    let icon = entry.icon.as_ref().map(|icon| match icon {
+        IconPath::Name(name) => name.clone(),
+        IconPath::Path(Icon::Svg(path) | Icon::Other(path)) => path.display().to_string(),
+        IconPath::Glyph(glyph) => format!("glyph:{glyph}")
+    });
+
+    JsonEntry {
+        name: &entry.name,
+        comment: entry.comment.as_deref(),
+        icon,
+        plugin: &engine.plugin(entry).name,
+        section: entry.section.as_deref()
+    }
+}
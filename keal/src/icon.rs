@@ -1,14 +1,25 @@
-use std::{collections::HashMap, path::{PathBuf, Path}};
+//! Finding icons, and loading them at the size frontends draw them at
+//! Frontends never wait for icons: entries are shown as soon as they arrive, with a placeholder where their icon goes,
+//! while the icon cache loads and icons are rasterized in the background, and the input has the focus from the first frame
+
+use std::{collections::HashMap, path::{PathBuf, Path}, process::{Command, Stdio}, sync::{Mutex, OnceLock}, time::UNIX_EPOCH, io::Write, os::unix::fs::OpenOptionsExt};
 
 use walkdir::WalkDir;
 
-use crate::{xdg_utils::xdg_directories, log_time};
+use crate::{xdg_utils::{xdg_directories, cache_home, config_dir}, ini_parser::Ini, log_time};
+
+pub mod raster;
+
+/// Size of thumbnails in the `normal` directory of the thumbnail spec
+const THUMBNAIL_SIZE: u32 = 128;
 
 /// Distinguishes between a direct path to an icon, and an icon identifier that needs to be searched in IconCache.
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum IconPath {
     Name(String),
-    Path(Icon)
+    Path(Icon),
+    /// `glyph:<text>`, drawn with the font instead of an image, like a Nerd Font icon or an emoji
+    Glyph(String)
 }
 
 /// Links an icon name to its path
@@ -26,7 +37,9 @@ impl IconPath {
         let process_cwd = std::env::current_dir().ok();
         let cwd = cwd.or(process_cwd.as_deref());
 
-        if Path::new(&value).is_absolute() {
+        if let Some(glyph) = value.strip_prefix("glyph:") {
+            IconPath::Glyph(glyph.to_owned())
+        } else if Path::new(&value).is_absolute() {
             IconPath::Path(PathBuf::from(value).into())
         } else if Path::new(&value).starts_with("./") && cwd.is_some() {
             IconPath::Path(cwd.unwrap().join(value).into())
@@ -46,8 +59,164 @@ impl From<PathBuf> for Icon {
     }
 }
 
+/// Path of every image to the one it's loaded from, its thumbnail or itself
+static THUMBNAILS: OnceLock<Mutex<HashMap<PathBuf, PathBuf>>> = OnceLock::new();
+
+impl Icon {
+    /// Returns the path frontends should load the icon from.
+    /// Large raster images (like photos given by an image picker plugin) are replaced by a thumbnail,
+    /// shared with other applications following the [XDG thumbnail spec](https://specifications.freedesktop.org/thumbnail-spec/latest/).
+    /// This never touches the disk: until [`Icon::make_thumbnail`] was called for the image, it is returned as is.
+    pub fn load_path(&self) -> PathBuf {
+        let path = match self {
+            Icon::Svg(path) => return path.clone(),
+            Icon::Other(path) => path
+        };
+
+        let thumbnails = THUMBNAILS.get_or_init(Default::default).lock().unwrap();
+        thumbnails.get(path).unwrap_or(path).clone()
+    }
+
+    /// Finds or generates the thumbnail of the image, and returns the path to load it from like [`Icon::load_path`]
+    /// This reads and may decode the whole image, so it's only called from the threads rasterizing icons
+    pub fn make_thumbnail(&self) -> PathBuf {
+        let path = match self {
+            Icon::Svg(path) => return path.clone(),
+            Icon::Other(path) => path
+        };
+
+        if let Some(thumbnail) = THUMBNAILS.get_or_init(Default::default).lock().unwrap().get(path) {
+            return thumbnail.clone()
+        }
+
+        // generated without holding the lock, so that other threads can still look up the thumbnails that are ready
+        let thumbnail = thumbnail(path).unwrap_or_else(|| path.clone());
+        THUMBNAILS.get_or_init(Default::default).lock().unwrap().insert(path.clone(), thumbnail.clone());
+        thumbnail
+    }
+}
+
+/// Finds or generates the thumbnail of the given image, returns `None` if the image should be used as is
+fn thumbnail(path: &Path) -> Option<PathBuf> {
+    let (width, height) = image::image_dimensions(path).ok()?;
+    if width <= THUMBNAIL_SIZE && height <= THUMBNAIL_SIZE { return None }
+
+    let dir = cache_home().ok()?.join("thumbnails");
+    if path.starts_with(&dir) { return None } // never make thumbnails of thumbnails
+
+    let path = path.canonicalize().ok()?;
+    let uri = file_uri(&path)?;
+    let mtime = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?
+        .duration_since(UNIX_EPOCH).ok()?.as_secs().to_string();
+
+    let thumbnail_path = dir.join("normal").join(format!("{:x}.png", md5::compute(&uri)));
+    if thumbnail_is_valid(&thumbnail_path, &mtime) {
+        return Some(thumbnail_path)
+    }
+
+    log_time(format!("generating thumbnail for {}", path.display()));
+    match write_thumbnail(&path, &thumbnail_path, &uri, &mtime) {
+        Ok(()) => Some(thumbnail_path),
+        Err(e) => {
+            eprintln!("failed to generate thumbnail for {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// A thumbnail is only valid if it was generated from the current version of the image
+fn thumbnail_is_valid(thumbnail: &Path, mtime: &str) -> bool {
+    let Ok(file) = std::fs::File::open(thumbnail) else { return false };
+    let Ok(reader) = png::Decoder::new(std::io::BufReader::new(file)).read_info() else { return false };
+
+    reader.info().uncompressed_latin1_text.iter()
+        .any(|chunk| chunk.keyword == "Thumb::MTime" && chunk.text == mtime)
+}
+
+fn write_thumbnail(path: &Path, thumbnail: &Path, uri: &str, mtime: &str) -> anyhow::Result<()> {
+    let image = image::open(path)?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).into_rgba8();
+
+    let mut data = vec![];
+    let mut encoder = png::Encoder::new(&mut data, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_text_chunk("Thumb::URI".to_owned(), uri.to_owned())?;
+    encoder.add_text_chunk("Thumb::MTime".to_owned(), mtime.to_owned())?;
+    encoder.add_text_chunk("Software".to_owned(), "keal".to_owned())?;
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+
+    let dir = thumbnail.parent().unwrap();
+    std::fs::create_dir_all(dir)?;
+
+    // the spec requires writing to a temporary file then renaming it, so other programs never read a partial thumbnail
+    let tmp = dir.join(format!("keal-{}.png", std::process::id()));
+    std::fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600)
+        .open(&tmp)?
+        .write_all(&data)?;
+    std::fs::rename(&tmp, thumbnail)?;
+
+    Ok(())
+}
+
+/// Returns the `file://` URI of an absolute path, with every reserved character percent-encoded
+fn file_uri(path: &Path) -> Option<String> {
+    let mut uri = "file://".to_owned();
+    for &byte in path.to_str()?.as_bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+            byte => uri += &format!("%{byte:02X}")
+        }
+    }
+    Some(uri)
+}
+
+/// Finds the icon theme used by the rest of the desktop, from the GTK settings or else from the GNOME ones
+pub fn desktop_icon_theme() -> Option<String> {
+    let config_home = config_dir().ok()?.parent()?.to_owned();
+
+    let from_gtk = ["gtk-3.0", "gtk-4.0"].into_iter()
+        .filter_map(|gtk| Ini::from_file(config_home.join(gtk).join("settings.ini"), &['#', ';']).ok())
+        .find_map(|file| file.section_iter("Settings").find(|(name, _)| *name == "gtk-icon-theme-name").map(|(_, theme)| theme.clone()));
+
+    let theme = from_gtk.or_else(|| {
+        let output = Command::new("gsettings").args(["get", "org.gnome.desktop.interface", "icon-theme"])
+            .stderr(Stdio::null())
+            .output().ok()?;
+        if !output.status.success() { return None }
+        String::from_utf8(output.stdout).ok()
+    })?;
+
+    // gsettings quotes strings, and some tools quote values in settings.ini
+    let theme = theme.trim().trim_matches(['\'', '"']).to_owned();
+    (!theme.is_empty()).then_some(theme)
+}
+
+/// How badly an icon found at `path` fits the requested size, lower is better
+/// Icon themes keep each size in its own directory, like `48x48/apps/` or `scalable/apps/`
+fn size_distance(path: &Path, size: u32) -> u32 {
+    if path.extension().is_some_and(|ext| ext == "svg") { return 0 }
+
+    let found = path.components().rev()
+        .filter_map(|component| component.as_os_str().to_str())
+        .find_map(|dir| {
+            let (dir, scale) = dir.split_once('@').unwrap_or((dir, "1"));
+            let (width, height) = dir.split_once('x')?;
+            let scale: u32 = scale.trim_end_matches('x').parse().ok()?;
+            (width == height).then_some(width.parse::<u32>().ok()? * scale)
+        });
+
+    match found {
+        Some(found) if found >= size => found - size,
+        Some(found) => (size - found) * 4, // scaling up looks a lot worse than scaling down
+        None => u32::MAX
+    }
+}
+
 impl IconCache {
-    pub fn new(icon_themes: &[String]) -> Self {
+    /// `size` is the size icons are drawn at, to pick the best fitting one when a theme has several
+    pub fn new(icon_themes: &[String], size: f32) -> Self {
         log_time("loading icon cache");
 
         let icon_dirs = xdg_directories("icons");
@@ -58,19 +227,27 @@ impl IconCache {
 
         icon_dirs.push("/usr/share/pixmaps".into());
 
-        let mut cache = Self::default();
+        let size = size.round() as u32;
+        // remembers which directory each icon was found in, and how well it fits
+        let mut found: HashMap<String, (usize, u32, PathBuf)> = HashMap::new();
 
-        for dir in icon_dirs {
-            for file in WalkDir::new(&dir).follow_links(true).into_iter().flatten() {
+        for (index, dir) in icon_dirs.iter().enumerate() {
+            for file in WalkDir::new(dir).follow_links(true).into_iter().flatten() {
                 if !file.metadata().unwrap().is_file() { continue }
 
                 let Some(Some(name)) = file.path().file_stem().map(|x| x.to_str()) else { continue }; // filter non utf-8 names
-                if cache.0.contains_key(name) { continue } // filter already found icons
+                let distance = size_distance(file.path(), size);
 
-                cache.0.insert(name.to_owned(), file.into_path().into());
+                // preferred themes always win, sizes are only compared within a theme
+                match found.get(name) {
+                    Some(&(found_index, found_distance, _)) if found_index < index || found_distance <= distance => continue,
+                    _ => { found.insert(name.to_owned(), (index, distance, file.into_path())); }
+                }
             }
         }
 
+        let cache = Self(found.into_iter().map(|(name, (_, _, path))| (name, path.into())).collect());
+
         log_time("finished loading icon cache");
 
         cache
@@ -78,8 +255,10 @@ impl IconCache {
 
     pub fn get<'a>(&'a self, icon: &'a IconPath) -> Option<&'a Icon> {
         match icon {
-            IconPath::Name(icon) => self.0.get(icon),
-            IconPath::Path(icon) => Some(icon)
+            // names are file stems, but plugins may give them with an extension (`firefox.png`)
+            IconPath::Name(icon) => self.0.get(icon).or_else(|| self.0.get(Path::new(icon).file_stem()?.to_str()?)),
+            IconPath::Path(icon) => Some(icon),
+            IconPath::Glyph(_) => None
         }
     }
 }
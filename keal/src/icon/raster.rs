@@ -0,0 +1,108 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{Mutex, OnceLock}, time::UNIX_EPOCH};
+
+use anyhow::Context;
+use image::imageops::FilterType;
+use resvg::{tiny_skia, usvg};
+
+use crate::xdg_utils::cache_home;
+use super::{Icon, IconCache, IconPath};
+
+/// An icon rasterized to 8 bit RGBA, with straight (not premultiplied) alpha
+#[derive(Debug, Clone)]
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+/// Finds an icon in the cache and rasterizes it, see [`rasterize`]
+pub fn load(cache: &IconCache, icon: &IconPath, size: u32) -> Option<Raster> {
+    rasterize(cache.get(icon)?, size)
+}
+
+/// Renders an icon to fit in a square of `size` pixels, keeping its aspect ratio
+/// SVGs are rendered at that size, and other images are scaled down from their thumbnail if they are larger
+pub fn rasterize(icon: &Icon, size: u32) -> Option<Raster> {
+    let size = size.max(1);
+    let result = match icon {
+        Icon::Svg(path) => rasterize_svg(path, size),
+        Icon::Other(_) => rasterize_image(&icon.make_thumbnail(), size)
+    };
+
+    match result {
+        Ok(raster) => Some(raster),
+        Err(e) => {
+            let (Icon::Svg(path) | Icon::Other(path)) = icon;
+            eprintln!("failed to load icon {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+fn rasterize_svg(path: &Path, size: u32) -> anyhow::Result<Raster> {
+    let data = std::fs::read(path)?;
+    let options = usvg::Options { default_size: usvg::Size::from_wh(size as f32, size as f32).unwrap(), ..Default::default() };
+    let tree = usvg::Tree::from_data(&data, &options)?;
+
+    let tree_size = tree.size();
+    let factor = size as f32 / tree_size.width().max(tree_size.height());
+    let mut pixmap = tiny_skia::Pixmap::new((tree_size.width() * factor).ceil() as u32, (tree_size.height() * factor).ceil() as u32)
+        .context("icon is empty")?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(factor, factor), &mut pixmap.as_mut());
+
+    let pixels = pixmap.pixels().iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+
+    Ok(Raster { width: pixmap.width(), height: pixmap.height(), pixels })
+}
+
+fn rasterize_image(path: &Path, size: u32) -> anyhow::Result<Raster> {
+    let mut image = image::open(path)?;
+    if image.width() > size || image.height() > size {
+        image = image.resize(size, size, FilterType::Triangle);
+    }
+
+    let image = image.into_rgba8();
+    Ok(Raster { width: image.width(), height: image.height(), pixels: image.into_raw() })
+}
+
+/// Returns a PNG of the icon rasterized at `size`, for frontends that load images from files
+/// They are kept in `~/.cache/keal/icons`, so icons are only rendered again when they change
+pub fn cached_png(icon: &Icon, size: u32) -> Option<PathBuf> {
+    /// paths of the pngs written by this instance, by icon and size
+    type Cached = HashMap<(Icon, u32), Option<PathBuf>>;
+    static CACHED: OnceLock<Mutex<Cached>> = OnceLock::new();
+
+    let mut cached = CACHED.get_or_init(Default::default).lock().unwrap();
+    cached.entry((icon.clone(), size))
+        .or_insert_with(|| write_png(icon, size).inspect_err(|e| eprintln!("failed to cache icon: {e:#}")).ok().flatten())
+        .clone()
+}
+
+/// Returns `None` if the icon couldn't be rasterized
+fn write_png(icon: &Icon, size: u32) -> anyhow::Result<Option<PathBuf>> {
+    let source = icon.make_thumbnail();
+    let mtime = std::fs::metadata(&source)?.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+
+    let dir = cache_home().map_err(anyhow::Error::msg)?.join("keal/icons");
+    // the name changes with the icon, so an outdated png is never used
+    let path = dir.join(format!("{:x}.png", md5::compute(format!("{}:{mtime}:{size}", source.display()))));
+    if path.exists() {
+        return Ok(Some(path))
+    }
+
+    let Some(raster) = rasterize(icon, size) else { return Ok(None) };
+    let image = image::RgbaImage::from_raw(raster.width, raster.height, raster.pixels).context("invalid raster size")?;
+
+    std::fs::create_dir_all(&dir)?;
+    // written to a temporary file then renamed, so another instance never reads a partial png
+    let tmp = dir.join(format!("keal-{}.png", std::process::id()));
+    image.save_with_format(&tmp, image::ImageFormat::Png)?;
+    std::fs::rename(&tmp, &path)?;
+
+    Ok(Some(path))
+}
@@ -108,4 +108,62 @@ impl Ini {
     pub fn remove_section(&mut self, section: &str) -> Option<Section> {
         self.sections.remove(section)
     }
+
+    pub fn section_names(&self) -> impl Iterator<Item = &String> {
+        self.sections.keys()
+    }
+
+    /// Adds the keys of `section` to the section named `name`, replacing those it already has
+    pub fn merge_section(&mut self, name: &str, section: Section) {
+        self.sections.entry(name.to_owned()).or_default().keys.extend(section.keys);
+    }
+}
+
+/// Sets `key` of `section` in the text of an ini file, keeping everything else as it was
+/// Changes the last definition of the key in the last such section, since that's the one the parser keeps,
+/// and adds the key, or the section at the end of the file, if they are missing
+/// A comment at the end of the line defining the key is kept
+pub fn set_value(file: &str, section: &str, key: &str, value: &str, comment_chars: &[char]) -> String {
+    let mut lines: Vec<&str> = file.lines().collect();
+
+    let mut in_section = false;
+    // index of the line defining the key, and of the line after the last one of the section
+    let mut key_line = None;
+    let mut section_end = None;
+    for (index, line) in lines.iter().enumerate() {
+        let content = line.split(comment_chars).next().unwrap_or("").trim();
+
+        if content.starts_with('[') && content.ends_with(']') {
+            in_section = &content[1..content.len()-1] == section;
+            if in_section { key_line = None }
+        } else if in_section && content.split_once('=').is_some_and(|(name, _)| name.trim() == key) {
+            key_line = Some(index);
+        }
+
+        if in_section && !content.is_empty() {
+            section_end = Some(index + 1);
+        }
+    }
+
+    let definition = format!("{key} = {value}");
+    let replaced;
+    match (key_line, section_end) {
+        (Some(index), _) => {
+            let line = lines[index];
+            let comment = line.find(comment_chars).map_or("", |start| &line[line[..start].trim_end().len()..]);
+            replaced = format!("{definition}{comment}");
+            lines[index] = &replaced;
+        }
+        (None, Some(end)) => lines.insert(end, &definition),
+        (None, None) => {
+            let header = format!("[{section}]");
+            let mut file = file.trim_end().to_owned();
+            if !file.is_empty() { file.push_str("\n\n") }
+            return format!("{file}{header}\n{definition}\n")
+        }
+    }
+
+    let mut file = lines.join("\n");
+    file.push('\n');
+    file
 }
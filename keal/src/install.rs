@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::xdg_utils::data_home;
+
+/// Writes a desktop entry launching this executable in `~/.local/share/applications`, so that keal shows up in other launchers and desktop menus
+/// Returns the path of the written file
+pub fn install_desktop_entry() -> anyhow::Result<PathBuf> {
+    let exe = std::env::current_exe().context("couldn't find the path of the keal executable")?;
+    let dir = data_home().map_err(anyhow::Error::msg)?.join("applications");
+    std::fs::create_dir_all(&dir).with_context(|| format!("couldn't create `{}`", dir.display()))?;
+
+    let path = dir.join("keal.desktop");
+    let entry = format!(
+        "[Desktop Entry]\n\
+        Type=Application\n\
+        Name=Keal\n\
+        Comment=Search and launch applications\n\
+        Exec={}\n\
+        Icon=system-search\n\
+        Terminal=false\n\
+        Categories=Utility;\n",
+        desktop_quote(&exe.to_string_lossy())
+    );
+    std::fs::write(&path, entry).with_context(|| format!("couldn't write `{}`", path.display()))?;
+
+    Ok(path)
+}
+
+/// Quotes an argument of the `Exec` key of a desktop entry, following the desktop entry spec:
+/// arguments with reserved characters are put in double quotes, in which `"`, `` ` ``, `$` and `\` are escaped with a backslash,
+/// `%` is doubled so that it isn't read as a field code, and the value is then escaped like any string value, which doubles backslashes again
+fn desktop_quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    let reserved = |c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c);
+    let arg = if arg.contains(reserved) {
+        let mut quoted = "\"".to_owned();
+        for c in arg.chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') { quoted.push('\\') }
+            quoted.push(c);
+        }
+        quoted + "\""
+    } else {
+        arg
+    };
+
+    arg.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t").replace('\r', "\\r")
+}
+
+/// Writes a `config.ini` listing every option with its default value commented out, and an example plugin, to `dir`
+pub fn write_default_config(dir: &Path) -> anyhow::Result<()> {
+    let plugin_dir = dir.join("plugins/example");
+    std::fs::create_dir_all(&plugin_dir).with_context(|| format!("couldn't create `{}`", plugin_dir.display()))?;
+
+    let mut config = "# keal configuration, uncomment a line to change its value\n\
+        # see https://github.com/davawen/keal#configuration for what every option does\n\n".to_owned();
+    for line in include_str!("../../public/default-config.ini").lines().skip_while(|line| line.starts_with('#')) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            config.push_str(line);
+        } else {
+            config.push_str("# ");
+            config.push_str(line);
+        }
+        config.push('\n');
+    }
+
+    let write = |path: PathBuf, content: &str| std::fs::write(&path, content).with_context(|| format!("couldn't write `{}`", path.display()));
+    write(dir.join("config.ini"), &config)?;
+    write(plugin_dir.join("config.ini"), include_str!("../../public/example-plugin/config.ini"))?;
+    write(plugin_dir.join("exec.sh"), include_str!("../../public/example-plugin/exec.sh"))?;
+
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(plugin_dir.join("exec.sh"), std::fs::Permissions::from_mode(0o755))
+        .context("couldn't make the example plugin executable")?;
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+
+use crate::config::config;
+
+/// Repeats a held key at the rate set in the configuration, instead of relying on the repeats sent by the system
+/// `K` identifies the key being held, so that frontends know when it is released
+pub struct KeyRepeat<K> {
+    /// the held key, and when it repeats next
+    held: Option<(K, Instant)>
+}
+
+impl<K> Default for KeyRepeat<K> {
+    fn default() -> Self {
+        Self { held: None }
+    }
+}
+
+impl<K: Clone> KeyRepeat<K> {
+    /// Call this when the key is first pressed, it starts repeating after `repeat_delay_ms`
+    /// A `repeat_interval_ms` of 0 disables repeating
+    pub fn press(&mut self, key: K) {
+        let config = config();
+        self.held = match config.repeat_interval_ms {
+            0 => None,
+            _ => Some((key, Instant::now() + Duration::from_millis(config.repeat_delay_ms)))
+        };
+    }
+
+    /// Call this when the key is released, or when the modifiers change
+    pub fn stop(&mut self) {
+        self.held = None;
+    }
+
+    pub fn held(&self) -> Option<&K> {
+        self.held.as_ref().map(|(key, _)| key)
+    }
+
+    /// Returns the held key if it is time to repeat it
+    /// Repeats missed while the frontend was busy are skipped, so that the selection doesn't jump ahead
+    pub fn poll(&mut self) -> Option<K> {
+        let (key, next) = self.held.as_mut()?;
+
+        let now = Instant::now();
+        if now < *next { return None }
+
+        let interval = Duration::from_millis(config().repeat_interval_ms);
+        *next = (*next + interval).max(now);
+        Some(key.clone())
+    }
+
+    /// When the held key repeats next, so that frontends can sleep until then
+    pub fn next_repeat(&self) -> Option<Instant> {
+        self.held.as_ref().map(|&(_, next)| next)
+    }
+}
@@ -0,0 +1,93 @@
+/// Modifiers held when a key is pressed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool
+}
+
+/// Which key a keybind refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyName {
+    /// `key:j`, what the key types with the current layout, so it moves with the letter on AZERTY or Dvorak
+    /// Other keys use their W3C name, like `ArrowDown` or `Enter`, and the comparison ignores case
+    Key(String),
+    /// `code:KeyJ`, the position of the key on the keyboard, named after the US layout like W3C key codes
+    Code(String)
+}
+
+/// A key combination from the `[keybinds]` section, like `ctrl+key:j` or `ctrl+code:KeyJ`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keybind {
+    pub modifiers: Modifiers,
+    pub key: KeyName
+}
+
+impl Keybind {
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        let mut rest = s.trim();
+        let mut modifiers = Modifiers::default();
+
+        while let Some((modifier, after)) = rest.split_once('+').filter(|(modifier, _)| !modifier.contains(':')) {
+            match modifier.trim().to_ascii_lowercase().as_str() {
+                "ctrl" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                _ => return Err("unknown modifier, expected `ctrl`, `shift` or `alt`")
+            }
+            rest = after.trim_start();
+        }
+
+        let key = match rest.split_once(':') {
+            Some(("key", name)) if !name.is_empty() => KeyName::Key(name.to_lowercase()),
+            Some(("code", name)) if !name.is_empty() => KeyName::Code(name.to_owned()),
+            _ => return Err("expected a key like `key:j` or `code:KeyJ`")
+        };
+
+        Ok(Keybind { modifiers, key })
+    }
+
+    /// `key` is the logical key and `code` the physical one, named like in [`KeyName`], either can be missing if the frontend doesn't know it
+    pub fn matches(&self, modifiers: Modifiers, key: Option<&str>, code: Option<&str>) -> bool {
+        if self.modifiers != modifiers { return false }
+
+        match &self.key {
+            KeyName::Key(name) => key.is_some_and(|key| key.to_lowercase() == *name),
+            KeyName::Code(name) => code == Some(name.as_str())
+        }
+    }
+}
+
+/// Actions that can be bound in the `[keybinds]` section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    SelectNext,
+    SelectPrevious,
+    JumpMode,
+    NextPage
+}
+
+// WARN: When adding keybinds, remember to set them in `Config::add_from_string` and in `Keybinds::action`!
+#[derive(Debug, Default)]
+pub struct Keybinds {
+    pub select_next: Vec<Keybind>,
+    pub select_previous: Vec<Keybind>,
+    /// toggles jump mode, where typing a digit selects the entry with that number
+    pub jump_mode: Vec<Keybind>,
+    /// shows more entries when some were left out
+    pub next_page: Vec<Keybind>
+}
+
+impl Keybinds {
+    /// Finds the action bound to a key press, see [`Keybind::matches`]
+    pub fn action(&self, modifiers: Modifiers, key: Option<&str>, code: Option<&str>) -> Option<KeyAction> {
+        [
+            (KeyAction::SelectNext, &self.select_next),
+            (KeyAction::SelectPrevious, &self.select_previous),
+            (KeyAction::JumpMode, &self.jump_mode),
+            (KeyAction::NextPage, &self.next_page)
+        ].into_iter()
+            .find(|(_, binds)| binds.iter().any(|bind| bind.matches(modifiers, key, code)))
+            .map(|(action, _)| action)
+    }
+}
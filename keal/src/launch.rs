@@ -0,0 +1,181 @@
+//! Starting processes from the actions of plugins, so that every frontend launches and detaches them the same way
+
+use std::{io::{self, Write}, os::unix::process::CommandExt, process::Stdio};
+
+use crate::plugin::{ClonableCommand, PluginManager};
+
+/// Replaces keal with the command, for when keal closes after launching something
+/// Only returns if the command couldn't be run
+pub fn exec(mut command: ClonableCommand) {
+    let e = command.0.exec();
+    eprintln!("failed to run `{}`: {e}", command.0.get_program().to_string_lossy());
+}
+
+/// Makes the command lead a new session once it starts, see [`spawn_detached`]
+fn new_session(command: &mut ClonableCommand) {
+    // SAFETY: setsid is async-signal-safe
+    unsafe {
+        command.0.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+}
+
+/// Runs the command alongside keal instead of replacing it, for when keal stays open
+/// The command gets its own session, so that it outlives keal and the terminal keal may have been started from
+pub fn spawn_detached(mut command: ClonableCommand) {
+    new_session(&mut command);
+
+    match command.0.spawn() {
+        // wait for the child in the background so it doesn't linger as a zombie
+        Ok(mut child) => { std::thread::spawn(move || child.wait()); }
+        Err(e) => eprintln!("failed to run `{}`: {e}", command.0.get_program().to_string_lossy())
+    }
+}
+
+/// Like [`spawn_detached`], but writes `input` to the command's stdin instead of giving it keal's
+/// The input is written before returning, so keal can exit right after: the command still reads it from the pipe
+pub fn spawn_with_input(mut command: ClonableCommand, input: &[u8]) {
+    command.0.stdin(Stdio::piped()).stdout(Stdio::null());
+    new_session(&mut command);
+
+    let program = command.0.get_program().to_string_lossy().into_owned();
+    let mut child = match command.0.spawn() {
+        Ok(child) => child,
+        Err(e) => return eprintln!("failed to run `{program}`: {e}")
+    };
+
+    // dropping stdin closes it, so the command knows the input ended
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(e) = stdin.write_all(input) {
+            eprintln!("failed to write to `{program}`: {e}");
+        }
+    }
+    std::thread::spawn(move || child.wait());
+}
+
+/// Runs `f` in a copy of keal that outlives it, then exits that copy
+/// keal forks twice: the first child only starts a new session, so that the copy isn't killed along with the terminal, forks again and exits,
+/// and it is reaped here right away, so the copy running `f` is adopted and reaped by init
+/// The copy only has the thread that forked, so `f` shouldn't need the others, nor locks they may have held
+pub fn fork_detached(f: impl FnOnce()) -> io::Result<()> {
+    // SAFETY: the first child only calls async-signal-safe functions, and the second one never returns to the caller
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => unsafe {
+            libc::setsid();
+            match libc::fork() {
+                0 => {
+                    crate::supervisor::set_forked();
+                    f();
+                    libc::_exit(0)
+                }
+                _ => libc::_exit(0)
+            }
+        }
+        child => {
+            // SAFETY: waitpid doesn't touch memory besides the status
+            unsafe { libc::waitpid(child, std::ptr::null_mut(), 0) };
+            Ok(())
+        }
+    }
+}
+
+/// Keeps the current plugin running after keal closes, for [`crate::plugin::Action::Fork`]
+/// A detached copy of keal waits for the plugin (see [`fork_detached`]), and the manager lets go of it so that it isn't stopped when keal exits
+pub fn fork_plugin(manager: &mut PluginManager) {
+    if let Err(e) = fork_detached(|| manager.wait()) {
+        eprintln!("failed to fork: {e}");
+    }
+    manager.detach();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::{Path, PathBuf}, process::Command, time::{Duration, Instant}};
+
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("keal-launch-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// Waits until `f` returns something, for at most 5 seconds
+    fn poll<T>(mut f: impl FnMut() -> Option<T>) -> Option<T> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Some(value) = f() { return Some(value) }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        None
+    }
+
+    /// Reads the numbers written to `path`, once the whole line is there
+    fn read_numbers(path: &Path) -> Option<Vec<libc::pid_t>> {
+        let content = std::fs::read_to_string(path).ok()?;
+        let line = content.strip_suffix('\n')?;
+        line.split_whitespace().map(|n| n.parse().ok()).collect()
+    }
+
+    #[test]
+    fn spawn_with_input_writes_to_stdin() {
+        let path = temp_file("input");
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(r#"cat > "$1.tmp" && mv "$1.tmp" "$1""#).arg("sh").arg(&path);
+        spawn_with_input(command.into(), b"hunter2\n");
+
+        let content = poll(|| std::fs::read_to_string(&path).ok()).expect("the command didn't run");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(content, "hunter2\n");
+    }
+
+    #[test]
+    fn spawn_detached_starts_a_new_session() {
+        let path = temp_file("session");
+        let mut command = Command::new("sh");
+        // `$$` is the pid of the shell, which is the spawned process, and the 6th field of its stat is its session
+        command.arg("-c").arg(r#"echo "$$ $(cut -d ' ' -f 6 /proc/$$/stat)" > "$1""#).arg("sh").arg(&path);
+        spawn_detached(command.into());
+
+        let numbers = poll(|| read_numbers(&path)).expect("the command didn't run");
+        let _ = std::fs::remove_file(&path);
+
+        let (pid, sid) = (numbers[0], numbers[1]);
+        assert_eq!(pid, sid, "the command should lead its own session");
+        assert_ne!(sid, unsafe { libc::getsid(0) });
+    }
+
+    #[test]
+    fn spawn_detached_reaps_the_child() {
+        let path = temp_file("reap");
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(r#"echo $$ > "$1""#).arg("sh").arg(&path);
+        spawn_detached(command.into());
+
+        let pid = poll(|| read_numbers(&path)).expect("the command didn't run")[0];
+        let _ = std::fs::remove_file(&path);
+
+        // a zombie keeps its entry in `/proc` until it is reaped
+        let reaped = poll(|| (!Path::new(&format!("/proc/{pid}")).exists()).then_some(()));
+        assert!(reaped.is_some(), "the command should be reaped once it exits");
+    }
+
+    #[test]
+    fn fork_detached_is_not_our_child() {
+        let path = temp_file("fork");
+        fork_detached(|| {
+            let (ppid, sid) = unsafe { (libc::getppid(), libc::getsid(0)) };
+            let _ = std::fs::write(&path, format!("{ppid} {sid}\n"));
+        }).unwrap();
+
+        let numbers = poll(|| read_numbers(&path)).expect("the copy didn't run");
+        let _ = std::fs::remove_file(&path);
+
+        let (ppid, sid) = (numbers[0], numbers[1]);
+        assert_ne!(ppid, std::process::id() as libc::pid_t, "the copy should be adopted by init");
+        assert_ne!(sid, unsafe { libc::getsid(0) }, "the copy should be in its own session");
+    }
+}
@@ -0,0 +1,44 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Keeps the most recently used values up to a given capacity, for frontends to reuse text layouts and measurements
+/// between entry updates, since most entries stay the same from one keystroke to the next
+pub struct LayoutCache<K, V> {
+    map: HashMap<K, (V, u64)>,
+    capacity: usize,
+    /// incremented on every access, so that values remember when they were last used
+    clock: u64
+}
+
+impl<K: Hash + Eq, V> LayoutCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self { map: HashMap::new(), capacity, clock: 0 }
+    }
+
+    /// Returns the value cached for `key`, computing it with `f` if it isn't there
+    pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &V {
+        self.clock += 1;
+        if self.map.len() >= self.capacity && !self.map.contains_key(&key) {
+            self.evict();
+        }
+
+        let clock = self.clock;
+        let (value, last_used) = self.map.entry(key).or_insert_with(|| (f(), clock));
+        *last_used = clock;
+        value
+    }
+
+    /// Forgets every value, when they can't be reused anymore (like after the scale of the window changes)
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Removes the least recently used half of the values, so that it doesn't happen on every insertion
+    fn evict(&mut self) {
+        let mut uses: Vec<u64> = self.map.values().map(|&(_, last_used)| last_used).collect();
+        if uses.is_empty() { return }
+
+        let middle = uses.len() / 2;
+        let (_, &mut median, _) = uses.select_nth_unstable(middle);
+        self.map.retain(|_, &mut (_, last_used)| last_used > median);
+    }
+}
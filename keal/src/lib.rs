@@ -3,11 +3,51 @@ use std::sync::OnceLock;
 use arguments::arguments;
 
 pub mod config;
+pub mod engine;
+pub mod headless;
 pub mod arguments;
 pub mod icon;
+pub mod keybind;
+pub mod launch;
 pub mod xdg_utils;
 pub mod ini_parser;
 pub mod plugin;
+pub mod rpc;
+
+// shared by keal's own frontends and binary, not part of the embedding API
+#[doc(hidden)] pub mod animation;
+#[doc(hidden)] pub mod cursor;
+#[doc(hidden)] pub mod edit_history;
+#[doc(hidden)] pub mod ellipsis;
+#[doc(hidden)] pub mod install;
+#[doc(hidden)] pub mod key_repeat;
+#[doc(hidden)] pub mod layout_cache;
+#[doc(hidden)] pub mod match_span;
+#[doc(hidden)] pub mod selection;
+#[doc(hidden)] pub mod sensitive;
+#[doc(hidden)] pub mod supervisor;
+
+/// Code keal exits with, set when something is picked
+static EXIT_CODE: OnceLock<i32> = OnceLock::new();
+
+/// Sets the code keal exits with, only the first one set is kept
+pub fn set_exit_code(code: i32) {
+    let _ = EXIT_CODE.set(code);
+}
+
+/// Like with rofi, closing keal in dmenu mode without picking anything is a cancellation, and exits with 1
+pub fn exit_code() -> i32 {
+    EXIT_CODE.get().copied().unwrap_or(if arguments().dmenu { 1 } else { 0 })
+}
+
+/// Exits the process if the exit code isn't 0, call this once the frontend is done
+pub fn exit_on_error_code() {
+    let code = exit_code();
+    if code != 0 {
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        std::process::exit(code);
+    }
+}
 
 static START: OnceLock<std::time::Instant> = OnceLock::new();
 pub fn start_log_time() {
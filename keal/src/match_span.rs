@@ -0,0 +1,132 @@
+use std::ops::Range;
+
+use nucleo_matcher::{Matcher, pattern::Pattern, Utf32Str};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits an item into alternating spans of text that did or didn't match the pattern, for highlighting
+/// Yields byte ranges into the item, along with wether the span matched
+/// Spans never cut through a grapheme cluster, so emojis and combining characters are highlighted as a whole
+pub struct MatchSpan<'a> {
+    item: &'a str,
+    /// sorted byte offsets of the matched characters
+    matched: Vec<usize>,
+    matched_index: usize,
+    /// byte offset of every grapheme in the item
+    graphemes: Vec<usize>,
+    /// grapheme the next span starts at
+    index: usize
+}
+
+impl<'a> MatchSpan<'a> {
+    pub fn new(item: &'a str, matcher: &mut Matcher, pattern: &Pattern, charbuf: &mut Vec<char>) -> Self {
+        let graphemes: Vec<usize> = item.grapheme_indices(true).map(|(offset, _)| offset).collect();
+
+        let haystack = Utf32Str::new(item, charbuf);
+        // the matcher indexes bytes when the whole item is ascii, and graphemes otherwise
+        let indexes_bytes = matches!(haystack, Utf32Str::Ascii(_));
+
+        let mut indices = vec![];
+        pattern.indices(haystack, matcher, &mut indices);
+
+        let mut matched: Vec<usize> = indices.into_iter()
+            .filter_map(|index| match indexes_bytes {
+                true => Some(index as usize),
+                false => graphemes.get(index as usize).copied()
+            })
+            .collect();
+        matched.sort_unstable();
+        matched.dedup();
+
+        MatchSpan {
+            item,
+            matched,
+            matched_index: 0,
+            graphemes,
+            index: 0
+        }
+    }
+
+    /// Wether the grapheme at `self.index` contains a matched character
+    /// Graphemes have to be checked in order
+    fn is_matched(&mut self) -> bool {
+        let start = self.graphemes[self.index];
+        let end = self.graphemes.get(self.index + 1).copied().unwrap_or(self.item.len());
+
+        while self.matched.get(self.matched_index).is_some_and(|&offset| offset < start) {
+            self.matched_index += 1;
+        }
+        self.matched.get(self.matched_index).is_some_and(|&offset| offset < end)
+    }
+}
+
+impl Iterator for MatchSpan<'_> {
+    type Item = (Range<usize>, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = *self.graphemes.get(self.index)?;
+
+        // wether or not we start in a matching span
+        let match_state = self.is_matched();
+
+        // advance while we are in the same state we were at the beginning
+        self.index += 1;
+        while self.index < self.graphemes.len() && self.is_matched() == match_state {
+            self.index += 1;
+        }
+
+        let end = self.graphemes.get(self.index).copied().unwrap_or(self.item.len());
+        Some((start..end, match_state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nucleo_matcher::{Config, Matcher, pattern::{CaseMatching, Pattern}};
+
+    use super::MatchSpan;
+
+    fn spans<'a>(item: &'a str, query: &str) -> Vec<(&'a str, bool)> {
+        let mut matcher = Matcher::new(Config::DEFAULT);
+        let pattern = Pattern::parse(query, CaseMatching::Ignore);
+        MatchSpan::new(item, &mut matcher, &pattern, &mut vec![])
+            .map(|(range, matched)| (&item[range], matched))
+            .collect()
+    }
+
+    #[test]
+    fn zwj_emoji() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let item = format!("a {family} b");
+        assert_eq!(spans(&item, "\u{1F468}"), [("a ", false), (family, true), (" b", false)]);
+    }
+
+    #[test]
+    fn combining_accent() {
+        assert_eq!(spans("cafe\u{301} bar", "cafe"), [("cafe\u{301}", true), (" bar", false)]);
+    }
+
+    #[test]
+    fn cjk() {
+        assert_eq!(spans("東京タワー", "京タ"), [("東", false), ("京タ", true), ("ワー", false)]);
+    }
+
+    #[test]
+    fn ascii_crlf() {
+        assert_eq!(spans("a\r\nb", "ab"), [("a", true), ("\r\n", false), ("b", true)]);
+    }
+
+    #[test]
+    fn empty_item() {
+        assert_eq!(spans("", "a"), []);
+    }
+
+    #[test]
+    fn full_match() {
+        assert_eq!(spans("Firefox", "firefox"), [("Firefox", true)]);
+    }
+
+    #[test]
+    fn no_match() {
+        assert_eq!(spans("Firefox", "xyz"), [("Firefox", false)]);
+    }
+}
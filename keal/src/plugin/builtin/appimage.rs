@@ -0,0 +1,153 @@
+use std::{io::Read, os::unix::fs::PermissionsExt, path::{Path, PathBuf}, process::{Command, Stdio}, time::UNIX_EPOCH};
+
+use anyhow::Context;
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{icon::IconPath, ini_parser::Ini, plugin::{Plugin, PluginExecution, Action, entry::Entry}, xdg_utils::{cache_home, expand_home}, config::Config};
+
+struct AppImageEntry {
+    name: String,
+    comment: Option<String>,
+    icon: Option<IconPath>,
+    path: PathBuf,
+    /// AppImages are often downloaded without the executable bit, in which case they can't be launched
+    executable: bool
+}
+
+pub struct AppImagePlugin(Vec<AppImageEntry>);
+
+impl AppImagePlugin {
+    pub fn create() -> Plugin {
+        let config = indexmap::IndexMap::from([
+            ("directories".to_owned(), "~/Applications".to_owned()), // separated by commas
+        ]);
+
+        Plugin {
+            name: "AppImages".to_owned(),
+            prefix: "appimage".to_owned(),
+            icon: None,
+            comment: Some("Launch AppImages from ~/Applications".to_owned()),
+            config,
+            default_icon: Some(IconPath::Name("application-x-executable".to_owned())),
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|plugin, _| {
+                let entries = plugin.config["directories"].split(',')
+                    .map(|dir| expand_home(dir.trim()))
+                    .flat_map(|dir| std::fs::read_dir(dir).into_iter().flatten().flatten())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("appimage")))
+                    .map(AppImageEntry::load)
+                    .collect();
+
+                Box::new(AppImagePlugin(entries))
+            })
+        }
+    }
+}
+
+impl AppImageEntry {
+    /// Reads the name, comment and icon of the desktop entry embedded in the AppImage, the name defaults to the file's
+    fn load(path: PathBuf) -> Self {
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let executable = std::fs::metadata(&path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0);
+        let mut entry = AppImageEntry { name, comment: None, icon: None, path, executable };
+
+        // extracting runs the AppImage itself, so it has to be executable
+        if !executable { return entry }
+
+        match extract(&entry.path) {
+            Ok(Some(root)) => entry.read_desktop_entry(&root),
+            Ok(None) => (),
+            Err(e) => eprintln!("failed to extract {}: {e:#}", entry.path.display())
+        }
+        entry
+    }
+
+    fn read_desktop_entry(&mut self, root: &Path) {
+        let Some(desktop) = std::fs::read_dir(root).into_iter().flatten().flatten()
+            .map(|entry| entry.path())
+            .find(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+            else { return };
+        let Some(mut ini) = Ini::from_file(&desktop, &['#']).ok().and_then(|mut ini| ini.remove_section("Desktop Entry")).map(|s| s.into_map())
+            else { return };
+
+        if let Some(name) = ini.swap_remove("Name") { self.name = name }
+        self.comment = ini.swap_remove("Comment");
+
+        // the icon named in the desktop entry is at the root, and `.DirIcon` usually links to it
+        let icon = ini.get("Icon")
+            .and_then(|icon| ["svg", "png"].iter().map(|ext| root.join(format!("{icon}.{ext}"))).find(|path| path.is_file()))
+            // a `.DirIcon` that isn't a link has no extension to tell its format from
+            .or_else(|| std::fs::canonicalize(root.join(".DirIcon")).ok().filter(|path| path.is_file() && path.extension().is_some()));
+        self.icon = icon.map(|icon| IconPath::Path(icon.into()));
+    }
+}
+
+/// Extracts the desktop entry and icons at the root of a type 2 AppImage to `~/.cache/keal/appimages`, with its `--appimage-extract` option
+/// They are only extracted again when the AppImage changes
+/// Returns the directory they are in, `None` for other types of AppImages, which would be launched instead of extracted
+fn extract(path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    // the type is written after the ELF header's identification bytes
+    let mut magic = [0; 11];
+    std::fs::File::open(path)?.read_exact(&mut magic)?;
+    if &magic[8..] != b"AI\x02" { return Ok(None) }
+
+    let mtime = std::fs::metadata(path)?.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+    let dir = cache_home().map_err(anyhow::Error::msg)?
+        .join("keal/appimages")
+        .join(format!("{:x}", md5::compute(format!("{}:{mtime}", path.display()))));
+    if dir.exists() {
+        return Ok(Some(dir.join("squashfs-root")))
+    }
+
+    // extracted to a temporary directory then renamed, so that an interrupted extraction is done again
+    let tmp = dir.with_extension("tmp");
+    let _ = std::fs::remove_dir_all(&tmp);
+    std::fs::create_dir_all(tmp.join("squashfs-root"))?;
+    for pattern in ["*.desktop", "*.svg", "*.png", ".DirIcon"] {
+        let status = Command::new(path)
+            .arg("--appimage-extract").arg(pattern)
+            .current_dir(&tmp)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .context("failed to run AppImage")?;
+        if !status.success() { anyhow::bail!("`--appimage-extract` exited with {status}") }
+    }
+    std::fs::rename(&tmp, &dir)?;
+
+    Ok(Some(dir.join("squashfs-root")))
+}
+
+impl PluginExecution for AppImagePlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn send_query(&mut self, _: &Config, _: &str) -> Action { Action::None }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        let Some(idx) = idx else { return Action::None };
+        let entry = &self.0[idx];
+
+        if !entry.executable {
+            return Action::Invalid(format!("{} isn't executable, run `chmod +x` on it", entry.path.display()))
+        }
+        Action::Exec(Command::new(&entry.path).into())
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        let mut charbuf = vec![];
+        for (index, entry) in self.0.iter().enumerate() {
+            let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, &entry.name, entry.icon.as_ref(), entry.comment.as_deref(), index)
+                else { continue };
+
+            out.push(entry);
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        &self.0[index].name
+    }
+}
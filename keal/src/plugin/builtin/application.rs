@@ -1,10 +1,35 @@
-use std::{path::Path, process};
+use std::{collections::HashSet, ffi::OsStr, path::Path, process};
 
 use nucleo_matcher::{Matcher, pattern::Pattern, Utf32Str, Utf32String};
 use walkdir::WalkDir;
 
 use crate::{icon::{IconPath, Icon}, ini_parser::Ini, plugin::{Plugin, PluginExecution, Entry, Action, entry::Label}, xdg_utils::xdg_directories, config::Config};
 
+/// How an application was installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Packaging {
+    Native,
+    Flatpak,
+    Snap
+}
+
+impl Packaging {
+    /// Flatpak and snap add their own key to the desktop files they export, which also live in their own directories
+    fn detect(ini: &indexmap::IndexMap<String, String>, location: &Path) -> Self {
+        if ini.contains_key("X-Flatpak") || location.to_string_lossy().contains("/flatpak/exports/") {
+            Packaging::Flatpak
+        } else if ini.contains_key("X-SnapInstanceName") || location.starts_with("/var/lib/snapd") {
+            Packaging::Snap
+        } else {
+            Packaging::Native
+        }
+    }
+
+    fn containerized(self) -> bool {
+        self != Packaging::Native
+    }
+}
+
 #[derive(Debug)]
 struct DesktopEntry {
     name: String,
@@ -16,7 +41,25 @@ struct DesktopEntry {
     to_match: Utf32String,
     exec: String,
     path: Option<String>,
-    terminal: bool
+    terminal: bool,
+    /// `PrefersNonDefaultGPU`, the application should run on the discrete GPU of laptops with two
+    prefers_non_default_gpu: bool,
+    /// `StartupWMClass`, or the name of the desktop file, which windows of the application are expected to have
+    wm_class: String,
+    /// `SingleMainWindow`, launching the application again would only bring back its window
+    single_main_window: bool,
+    packaging: Packaging,
+    /// made by wine for a windows program, see [`is_wine`]
+    wine: bool
+}
+
+/// How the windows of an application can be recognized, so that an existing one can be focused instead of launching a duplicate
+#[derive(Debug, Clone, Copy)]
+pub struct WindowHints<'a> {
+    /// class of its windows on X11, or their app id on wayland
+    pub wm_class: &'a str,
+    /// the application only has one main window
+    pub single_main_window: bool
 }
 
 impl DesktopEntry {
@@ -49,7 +92,15 @@ impl DesktopEntry {
         }
 
         let name = ini.swap_remove("Name")?;
-        let comment = ini.swap_remove("Comment");
+        let packaging = Packaging::detect(&ini, location);
+        // shown like a badge, so that duplicates of an application can be told apart
+        let comment = match (ini.swap_remove("Comment"), packaging) {
+            (comment, Packaging::Native) => comment,
+            (Some(comment), Packaging::Flatpak) => Some(format!("{comment} (Flatpak)")),
+            (Some(comment), Packaging::Snap) => Some(format!("{comment} (Snap)")),
+            (None, Packaging::Flatpak) => Some("Flatpak".to_owned()),
+            (None, Packaging::Snap) => Some("Snap".to_owned())
+        };
         let icon = ini.swap_remove("Icon").map(|i| IconPath::new(i, None));
         let to_match = format!("{name}{}{}{}{}",
             ini.get("GenericName").map(String::as_ref).unwrap_or(""),
@@ -57,15 +108,44 @@ impl DesktopEntry {
             ini.get("Keywords").map(String::as_ref).unwrap_or(""),
             comment.as_deref().unwrap_or(""),
         ).into();
-        let exec = parse_exec_key(ini.swap_remove("Exec")?, &name, location, icon.as_ref());
+        let exec = ini.swap_remove("Exec")?;
+        let wine = is_wine(&exec, location);
+        let exec = parse_exec_key(exec, &name, location, icon.as_ref());
         let path = ini.swap_remove("Path");
         let terminal = ini.get("Terminal").map(|v| v == "true").unwrap_or(false);
+        let prefers_non_default_gpu = ini.get("PrefersNonDefaultGPU").map(|v| v == "true").unwrap_or(false);
+        let single_main_window = ini.get("SingleMainWindow").map(|v| v == "true").unwrap_or(false);
+        // the desktop file's name is what applications following the spec use as their app id
+        let wm_class = ini.swap_remove("StartupWMClass")
+            .or_else(|| location.file_stem().and_then(OsStr::to_str).map(str::to_owned))
+            .unwrap_or_default();
 
         Some(DesktopEntry {
             name, comment, icon, to_match,
-            exec, path, terminal
+            exec, path, terminal,
+            prefers_non_default_gpu, wm_class, single_main_window, packaging, wine
         })
     }
+
+    /// Wine makes desktop files for every shortcut of an installer, which are mostly uninstallers and documentation
+    fn is_wine_clutter(&self) -> bool {
+        const CLUTTER: [&str; 8] = ["uninstall", "readme", "read me", "manual", "documentation", "license", "website", "help"];
+
+        let name = self.name.to_lowercase();
+        CLUTTER.iter().any(|word| name.contains(word))
+    }
+}
+
+/// Wether a desktop file was made by wine, from where it was put or from the program it runs
+/// Wine puts them in `applications/wine/`, and they run `wine` (or `wine64`, `wine-stable`...), sometimes through `env WINEPREFIX=...`
+fn is_wine(exec: &str, location: &Path) -> bool {
+    if location.components().any(|c| c.as_os_str() == "wine") { return true }
+
+    let program = exec.split_whitespace()
+        .find(|word| *word != "env" && !word.contains('='))
+        .unwrap_or("");
+    let program = program.rsplit('/').next().unwrap_or(program).trim_matches('"');
+    program.starts_with("wine") || program.starts_with("proton")
 }
 
 /// https://specifications.freedesktop.org/desktop-entry-spec/desktop-entry-spec-latest.html#exec-variables
@@ -104,6 +184,11 @@ fn parse_exec_key(exec: String, name: &str, location: &Path, icon: Option<&IconP
     out
 }
 
+/// Wether `program` is an executable in `$PATH`
+fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
 pub struct ApplicationPlugin(Vec<DesktopEntry>);
 
 impl ApplicationPlugin {
@@ -115,8 +200,17 @@ impl ApplicationPlugin {
             prefix: "app".to_owned(),
             icon: None,
             comment: Some("Launch applications on the system".to_owned()),
-            config: Default::default(),
-            generator: Box::new(move |_, _| {
+            config: indexmap::IndexMap::from([
+                // `native` or `containerized` only keep that copy of applications installed both natively and through flatpak or snap
+                ("duplicates".to_owned(), "both".to_owned()),
+                // programs installed with wine: `hide`, `filtered` without their uninstallers and documentation, or `all`
+                ("wine".to_owned(), "hide".to_owned()),
+            ]),
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(move |plugin, _| {
                 let current_desktop: Vec<&str> = current_desktop.split(':').collect();
                 let app_dirs = xdg_directories("applications");
 
@@ -138,12 +232,42 @@ impl ApplicationPlugin {
                     entries
                 });
 
-                Box::new(ApplicationPlugin(entries.collect()))
+                let mut entries: Vec<_> = entries.collect();
+                match plugin.config["duplicates"].as_str() {
+                    "both" => (),
+                    keep @ ("native" | "containerized") => {
+                        // applications are told to be the same by their name, since flatpak and snap rename desktop files
+                        let containerized = keep == "containerized";
+                        let kept: HashSet<String> = entries.iter()
+                            .filter(|entry| entry.packaging.containerized() == containerized)
+                            .map(|entry| entry.name.to_lowercase())
+                            .collect();
+                        entries.retain(|entry| entry.packaging.containerized() == containerized || !kept.contains(&entry.name.to_lowercase()));
+                    }
+                    other => eprintln!("unknown value `{other}` for `duplicates`, expected `both`, `native` or `containerized`")
+                }
+
+                match plugin.config["wine"].as_str() {
+                    "all" => (),
+                    "filtered" => entries.retain(|entry| !entry.wine || !entry.is_wine_clutter()),
+                    "hide" => entries.retain(|entry| !entry.wine),
+                    other => eprintln!("unknown value `{other}` for `wine`, expected `hide`, `filtered` or `all`")
+                }
+
+                Box::new(ApplicationPlugin(entries))
             })
         }
     }
 }
 
+impl ApplicationPlugin {
+    /// How to recognize the windows of the application at `index`, for a window switcher
+    pub fn window_hints(&self, index: usize) -> WindowHints<'_> {
+        let app = &self.0[index];
+        WindowHints { wm_class: &app.wm_class, single_main_window: app.single_main_window }
+    }
+}
+
 impl PluginExecution for ApplicationPlugin {
     fn finished(&mut self) -> bool { false }
     fn wait(&mut self) {}
@@ -153,15 +277,29 @@ impl PluginExecution for ApplicationPlugin {
         let Some(idx) = idx else { return Action::None };
         let app = &self.0[idx];
 
-        let mut command = if app.terminal {
-            let mut command = process::Command::new(&config.terminal_path);
-            command.arg("-e");
-            command.arg("sh");
-            command
-        } else {
-            process::Command::new("sh")
-        };
-        command.arg("-c").arg(&app.exec);
+        // switcheroo-control knows what every GPU needs, otherwise the variables of mesa's and nvidia's PRIME offloading are set
+        let switcheroo = app.prefers_non_default_gpu && in_path("switcherooctl");
+
+        let mut args: Vec<&OsStr> = vec![];
+        if switcheroo {
+            args.extend(["switcherooctl", "launch"].map(OsStr::new));
+        }
+        if app.terminal {
+            args.extend([config.terminal_path.as_str(), "-e"].map(OsStr::new));
+        }
+        args.extend(["sh", "-c", app.exec.as_str()].map(OsStr::new));
+
+        let mut command = process::Command::new(args[0]);
+        command.args(&args[1..]);
+        if app.prefers_non_default_gpu && !switcheroo {
+            command.env("DRI_PRIME", "1");
+            // those break OpenGL and Vulkan without the nvidia driver
+            if Path::new("/proc/driver/nvidia").exists() {
+                command.env("__NV_PRIME_RENDER_OFFLOAD", "1")
+                    .env("__GLX_VENDOR_LIBRARY_NAME", "nvidia")
+                    .env("__VK_LAYER_NV_optimus", "NVIDIA_only");
+            }
+        }
         if let Some(path) = &app.path {
             command.current_dir(path);
         }
@@ -0,0 +1,141 @@
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{plugin::{Plugin, PluginExecution, Action, entry::{Entry, Label}}, config::Config, ini_parser, xdg_utils::config_dir};
+
+/// characters that start a comment in `config.ini`, so values can't contain them
+const COMMENT_CHARS: [char; 2] = ['#', ';'];
+
+struct ConfEntry {
+    plugin: String,
+    key: String,
+    /// `Plugin.key`, the name shown and typed to edit the value
+    name: String,
+    /// the current value
+    comment: String
+}
+
+pub struct ConfPlugin {
+    entries: Vec<ConfEntry>,
+    /// index into `entries` when the query is `Plugin.key = value`, with the action saving the value and its comment
+    editing: Option<(usize, String, String)>
+}
+
+impl ConfPlugin {
+    pub fn create() -> Plugin {
+        Plugin {
+            name: "Config".to_owned(),
+            prefix: "conf".to_owned(),
+            icon: None,
+            comment: Some("Edit the configuration of plugins".to_owned()),
+            config: Default::default(),
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|_, manager| {
+                let entries = manager.list_plugins()
+                    .flat_map(|(_, plugin)| plugin.config.iter().map(|(key, value)| ConfEntry {
+                        plugin: plugin.name.clone(),
+                        key: key.clone(),
+                        name: format!("{}.{key}", plugin.name),
+                        comment: value.clone()
+                    }))
+                    .collect();
+
+                Box::new(ConfPlugin { entries, editing: None })
+            })
+        }
+    }
+
+    /// Starts editing a value when the query is `Plugin.key = value`, and stops otherwise
+    fn edit(&mut self, query: &str) {
+        self.editing = query.split_once(" = ").and_then(|(name, value)| {
+            let index = self.entries.iter().position(|entry| entry.name == name)?;
+            let value = value.trim();
+
+            let comment = if value.contains(COMMENT_CHARS) {
+                "Values can't contain `#` or `;`".to_owned()
+            } else {
+                format!("Save to config.ini, currently `{}`, takes effect the next time keal starts", self.entries[index].comment)
+            };
+            Some((index, format!("{name} = {value}"), comment))
+        });
+    }
+}
+
+/// Writes the value to the `[Plugin.config]` section of the user's `config.ini`
+fn save(entry: &ConfEntry, value: &str) -> anyhow::Result<()> {
+    let path = config_dir().map_err(anyhow::Error::msg)?.join("config.ini");
+    // a symlinked config (like one kept in a dotfiles repository) is written through, instead of being replaced by the new file
+    let path = std::fs::canonicalize(&path).unwrap_or(path);
+    let file = match std::fs::read_to_string(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into())
+    };
+
+    let file = ini_parser::set_value(&file, &format!("{}.config", entry.plugin), &entry.key, value, &COMMENT_CHARS);
+
+    // written to a temporary file then renamed, so that keal never reads a partial config
+    let tmp = path.with_extension("ini.tmp");
+    std::fs::write(&tmp, file)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+impl PluginExecution for ConfPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn send_query(&mut self, _: &Config, query: &str) -> Action {
+        // the user chose a key and is typing its new value
+        self.edit(query);
+        Action::None
+    }
+
+    fn send_enter(&mut self, _: &Config, query: &str, idx: Option<usize>) -> Action {
+        let Some((index, _, _)) = self.editing else {
+            let Some(idx) = idx else { return Action::None };
+            let entry = &self.entries[idx];
+            let query = format!("{} = {}", entry.name, entry.comment);
+            self.edit(&query);
+            return Action::ChangeQuery(query);
+        };
+
+        let value = query.split_once(" = ").map(|(_, value)| value.trim()).unwrap_or_default();
+        if value.contains(COMMENT_CHARS) { return Action::None }
+
+        let entry = &mut self.entries[index];
+        if let Err(e) = save(entry, value) {
+            eprintln!("failed to save `{}`: {e:#}", entry.name);
+            return Action::None
+        }
+        entry.comment = value.to_owned();
+        self.editing = None;
+
+        Action::ChangeQuery(String::new())
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        // the query is the new value, so it isn't matched against anything
+        if let Some((_, name, comment)) = &self.editing {
+            out.push(Entry { name, icon: None, comment: Some(comment), score: 0, label: Label::index(0) });
+            return
+        }
+
+        let mut charbuf = vec![];
+        for (index, entry) in self.entries.iter().enumerate() {
+            let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, &entry.name, None, Some(entry.comment.as_str()), index)
+                else { continue };
+
+            out.push(entry);
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        match &self.editing {
+            Some((_, name, _)) => name,
+            None => &self.entries[index].name
+        }
+    }
+}
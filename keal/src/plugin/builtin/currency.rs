@@ -0,0 +1,287 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, process::Command, thread::JoinHandle, time::{Duration, SystemTime}};
+
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{plugin::{Plugin, PluginExecution, Action, copy_to_clipboard, entry::{Entry, Label}}, config::Config, ini_parser::Ini, xdg_utils::state_dir};
+
+struct CurrencyEntry {
+    name: String,
+    comment: String,
+    /// what gets copied to the clipboard
+    value: String
+}
+
+pub struct CurrencyPlugin {
+    /// currency code (lowercase) to its value relative to an arbitrary base currency
+    rates: HashMap<String, f64>,
+    /// how long ago the rates were fetched
+    age: Option<Duration>,
+    /// currencies shown when the query doesn't specify a target
+    targets: Vec<String>,
+    entries: Vec<CurrencyEntry>,
+    path: Option<PathBuf>,
+    /// `refresh_command` running in the background
+    refresh: Option<JoinHandle<()>>
+}
+
+/// The rates file is a list of `code = rate` lines, where every rate is relative to the same base currency
+/// ```ini
+/// usd = 1.0
+/// eur = 0.92
+/// btc = 0.0000145
+/// ```
+fn rates_path(plugin: &Plugin) -> Option<PathBuf> {
+    match plugin.config["rates_file"].as_str() {
+        "" => state_dir().ok().map(|dir| dir.join("rates.ini")),
+        path => Some(PathBuf::from(path))
+    }
+}
+
+/// Reads the rates file, and how long ago it was written
+fn read_rates(path: &Path) -> (HashMap<String, f64>, Option<Duration>) {
+    let mut rates = HashMap::new();
+    if let Ok(ini) = Ini::from_file(path, &['#', ';']) {
+        rates.extend(ini.globals().flat_map(|(code, rate)| Some((code.to_lowercase(), rate.parse::<f64>().ok()?))));
+    }
+
+    let age = std::fs::metadata(path).and_then(|m| m.modified()).ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    (rates, age)
+}
+
+impl CurrencyPlugin {
+    pub fn create() -> Plugin {
+        let config = indexmap::IndexMap::from([
+            ("rates_file".to_owned(),      String::new()), // defaults to `~/.local/state/keal/rates.ini`
+            ("refresh_command".to_owned(), String::new()), // prints the content of the rates file to stdout
+            ("max_age_hours".to_owned(),   "24".to_owned()),
+            ("targets".to_owned(),         "usd,eur,gbp,jpy,btc".to_owned()),
+        ]);
+
+        Plugin {
+            name: "Currency".to_owned(),
+            prefix: "cur".to_owned(),
+            icon: None,
+            comment: Some("Convert currencies (`10 usd to eur`)".to_owned()),
+            config,
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|plugin, _| {
+                let path = rates_path(plugin);
+
+                let (rates, age) = path.as_deref().map(read_rates).unwrap_or_default();
+
+                let max_age: u64 = plugin.config["max_age_hours"].parse().unwrap_or(24);
+                let stale = age.map(|age| age > Duration::from_secs(max_age*3600)).unwrap_or(true);
+                let refresh = plugin.config["refresh_command"].clone();
+
+                // refresh in the background, the current rates are still used until the next time the plugin is opened
+                let refresh = match &path {
+                    Some(path) if stale && !refresh.is_empty() => {
+                        let path = path.clone();
+                        Some(std::thread::spawn(move || refresh_rates(&refresh, path)))
+                    }
+                    _ => None
+                };
+
+                let targets = plugin.config["targets"].split(',').map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()).collect();
+
+                let mut this = CurrencyPlugin { rates, age, targets, entries: vec![], path, refresh };
+                this.compute("");
+                Box::new(this)
+            })
+        }
+    }
+
+    fn compute(&mut self, query: &str) {
+        self.entries.clear();
+
+        // without any rates, the ones being fetched are used as soon as they're written
+        if self.rates.is_empty() && self.refresh.as_ref().is_some_and(JoinHandle::is_finished) {
+            self.refresh = None;
+            if let Some(path) = &self.path {
+                (self.rates, self.age) = read_rates(path);
+            }
+        }
+
+        if self.rates.is_empty() {
+            let (name, comment) = match self.refresh {
+                Some(_) => ("Refreshing exchange rates…", "running `refresh_command`"),
+                None => ("No exchange rates cached", "set `refresh_command` in [Currency.config]")
+            };
+            self.entries.push(CurrencyEntry { name: name.to_owned(), comment: comment.to_owned(), value: String::new() });
+            return
+        }
+
+        let Some((amount, from, to)) = parse_query(query) else { return };
+
+        let Some(&from_rate) = self.rates.get(&from) else {
+            self.entries.push(CurrencyEntry { name: format!("Unknown currency `{from}`"), comment: String::new(), value: String::new() });
+            return
+        };
+
+        let age = match self.age {
+            Some(age) if age.as_secs() < 3600 => "rates from less than an hour ago".to_owned(),
+            Some(age) if age.as_secs() < 2*86400 => format!("rates from {} hours ago", age.as_secs() / 3600),
+            Some(age) => format!("rates from {} days ago", age.as_secs() / 86400),
+            None => "rates of unknown age".to_owned()
+        };
+
+        let targets = match to {
+            Some(to) => vec![to],
+            None => self.targets.iter().filter(|&t| *t != from).cloned().collect()
+        };
+
+        for to in targets {
+            let Some(&to_rate) = self.rates.get(&to) else {
+                self.entries.push(CurrencyEntry { name: format!("Unknown currency `{to}`"), comment: String::new(), value: String::new() });
+                continue
+            };
+
+            let value = format_amount(amount / from_rate * to_rate);
+            self.entries.push(CurrencyEntry {
+                name: format!("{value} {}", to.to_uppercase()),
+                comment: format!("{} {}, {age}", format_amount(amount), from.to_uppercase()),
+                value
+            });
+        }
+    }
+}
+
+/// Runs the user's refresh command, and only replaces the rates file if it succeeded
+fn refresh_rates(command: &str, path: PathBuf) {
+    let output = match Command::new("sh").arg("-c").arg(command).output() {
+        Ok(output) if output.status.success() && !output.stdout.is_empty() => output,
+        Ok(output) => return eprintln!("currency: refresh command failed: {}", String::from_utf8_lossy(&output.stderr).trim()),
+        Err(e) => return eprintln!("currency: failed to run refresh command: {e}")
+    };
+
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+
+    // write to a temporary file first so a concurrent read never sees a partial file
+    let tmp = path.with_extension("tmp");
+    if std::fs::write(&tmp, &output.stdout).and_then(|_| std::fs::rename(&tmp, &path)).is_err() {
+        eprintln!("currency: failed to write rates to {}", path.display());
+    }
+}
+
+/// Parses queries like `10 usd to eur`, `10usd eur`, `usd in eur` or `10 usd`
+/// Returns the amount, the source currency, and the optional target currency
+fn parse_query(query: &str) -> Option<(f64, String, Option<String>)> {
+    let mut tokens = query.split_whitespace()
+        .filter(|t| !matches!(*t, "to" | "in" | "=" | "->"));
+
+    let first = tokens.next()?;
+    let split = first.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ',')).unwrap_or(first.len());
+    let (amount, rest) = first.split_at(split);
+
+    let amount = match amount {
+        "" => 1.0,
+        amount => parse_amount(amount)?
+    };
+
+    let from = match rest {
+        "" => tokens.next()?,
+        rest => rest
+    };
+
+    Some((amount, from.to_lowercase(), tokens.next().map(str::to_lowercase)))
+}
+
+/// Parses `1,000.50`, `1,000` and `1,5`: a comma is a decimal separator only when it is the only separator,
+/// and isn't followed by exactly three digits, otherwise it separates thousands
+fn parse_amount(amount: &str) -> Option<f64> {
+    let decimal_comma = match amount.split_once(',') {
+        Some((_, decimals)) => !amount.contains('.') && !decimals.contains(',') && decimals.len() != 3,
+        None => false
+    };
+
+    if decimal_comma {
+        amount.replace(',', ".").parse().ok()
+    } else {
+        amount.replace(',', "").parse().ok()
+    }
+}
+
+fn format_amount(value: f64) -> String {
+    if value.abs() >= 1.0 || value == 0.0 {
+        format!("{value:.2}")
+    } else {
+        // keep significant digits for small values (crypto currencies)
+        format!("{value:.8}").trim_end_matches('0').to_owned()
+    }
+}
+
+impl PluginExecution for CurrencyPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+    // results are computed from the query, in a meaningful order
+    fn sorted(&self) -> bool { false }
+    fn record_usage(&self) -> bool { false }
+
+    fn send_query(&mut self, _: &Config, query: &str) -> Action {
+        self.compute(query);
+        Action::None
+    }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        let Some(idx) = idx else { return Action::None };
+
+        match self.entries[idx].value.as_str() {
+            "" => Action::None,
+            value => copy_to_clipboard(value)
+        }
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, _: &mut Matcher, _: &Pattern, out: &mut Vec<Entry<'a>>) {
+        // results are computed from the query, so they are never filtered
+        for (index, entry) in self.entries.iter().enumerate() {
+            out.push(Entry {
+                name: &entry.name,
+                icon: None,
+                comment: Some(entry.comment.as_str()).filter(|c| !c.is_empty()),
+                score: 0,
+                label: Label::index(index)
+            });
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        &self.entries[index].name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_amounts_with_separators() {
+        assert_eq!(parse_amount("10"), Some(10.0));
+        assert_eq!(parse_amount("10.5"), Some(10.5));
+        assert_eq!(parse_amount("1,5"), Some(1.5));
+        assert_eq!(parse_amount("1,50"), Some(1.5));
+        assert_eq!(parse_amount("1,000"), Some(1000.0));
+        assert_eq!(parse_amount("1,000,000"), Some(1_000_000.0));
+        assert_eq!(parse_amount("1,000.50"), Some(1000.5));
+        assert_eq!(parse_amount("1,2,3"), Some(123.0));
+        assert_eq!(parse_amount("1.2.3"), None);
+    }
+
+    #[test]
+    fn parses_queries() {
+        let query = |amount, from: &str, to: Option<&str>| Some((amount, from.to_owned(), to.map(str::to_owned)));
+
+        assert_eq!(parse_query("1,000 usd"), query(1000.0, "usd", None));
+        assert_eq!(parse_query("1,5 usd to EUR"), query(1.5, "usd", Some("eur")));
+        assert_eq!(parse_query("10usd eur"), query(10.0, "usd", Some("eur")));
+        assert_eq!(parse_query("usd in eur"), query(1.0, "usd", Some("eur")));
+        assert_eq!(parse_query("2,500.75 gbp -> jpy"), query(2500.75, "gbp", Some("jpy")));
+        assert_eq!(parse_query("10"), None);
+        assert_eq!(parse_query(""), None);
+    }
+}
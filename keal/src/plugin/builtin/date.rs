@@ -0,0 +1,343 @@
+use std::{process::Command, time::{SystemTime, UNIX_EPOCH}};
+
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{plugin::{Plugin, PluginExecution, Action, copy_to_clipboard, entry::{Entry, Label}}, config::Config};
+
+const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+const MONTHS: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+
+/// Dates are kept within a million years of the epoch, where converting them can't overflow
+const MAX_YEARS: i64 = 1_000_000;
+
+struct DateEntry {
+    name: String,
+    comment: String
+}
+
+pub struct DatePlugin {
+    /// offset of the local timezone from UTC, in seconds
+    utc_offset: i64,
+    entries: Vec<DateEntry>
+}
+
+impl DatePlugin {
+    pub fn create() -> Plugin {
+        Plugin {
+            name: "Date".to_owned(),
+            prefix: "date".to_owned(),
+            icon: None,
+            comment: Some("Date math (`next friday`, `2025-03-01 + 45d`, `unix 1712345678`)".to_owned()),
+            config: Default::default(),
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|_, _| {
+                let mut this = DatePlugin { utc_offset: local_utc_offset(), entries: vec![] };
+                this.compute("");
+                Box::new(this)
+            })
+        }
+    }
+
+    /// Returns the number of seconds since the epoch, in local time
+    fn now(&self) -> i64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        now + self.utc_offset
+    }
+
+    fn compute(&mut self, query: &str) {
+        self.entries.clear();
+
+        let today = self.now().div_euclid(86400);
+        let Some((day, time)) = parse_query(query, today, self.utc_offset) else { return };
+
+        let (y, m, d) = civil_from_days(day);
+        let mut add = |name: String, comment: &str| self.entries.push(DateEntry { name, comment: comment.to_owned() });
+
+        match time {
+            Some(time) => add(
+                format!("{y:04}-{m:02}-{d:02} {:02}:{:02}:{:02}", time / 3600, time / 60 % 60, time % 60),
+                "local time"
+            ),
+            None => add(format!("{y:04}-{m:02}-{d:02}"), "ISO 8601")
+        }
+
+        add(format!("{} {d} {} {y}", WEEKDAYS[weekday(day)], MONTHS[m as usize - 1]), "long date");
+
+        let timestamp = day.checked_mul(86400).and_then(|t| t.checked_add(time.unwrap_or(0) - self.utc_offset));
+        if let Some(timestamp) = timestamp {
+            add(timestamp.to_string(), if time.is_some() { "unix timestamp" } else { "unix timestamp (midnight)" });
+        }
+
+        let relative = match day - today {
+            0 => "today".to_owned(),
+            1 => "tomorrow".to_owned(),
+            -1 => "yesterday".to_owned(),
+            n if n > 0 => format!("in {n} days"),
+            n => format!("{} days ago", -n)
+        };
+        add(relative, "relative to today");
+    }
+}
+
+/// Returns the day (since the epoch) described by the query, and the time of day in seconds if one was given
+/// `utc_offset` is the local offset from UTC in seconds, for unix timestamps
+/// Returns `None` if the query is invalid, or if the date is too far away (see [`MAX_YEARS`])
+fn parse_query(query: &str, today: i64, utc_offset: i64) -> Option<(i64, Option<i64>)> {
+    let mut tokens = query.split_whitespace();
+
+    let mut time = None;
+    let mut day = match tokens.next().map(str::to_lowercase).as_deref() {
+        None | Some("now" | "today") => today,
+        Some("tomorrow") => today + 1,
+        Some("yesterday") => today - 1,
+        Some("next") => {
+            let target = parse_weekday(tokens.next()?)?;
+            today + 1 + (target as i64 - weekday(today + 1) as i64).rem_euclid(7)
+        }
+        Some("last") => {
+            let target = parse_weekday(tokens.next()?)?;
+            today - 1 - (weekday(today - 1) as i64 - target as i64).rem_euclid(7)
+        }
+        Some("unix") => {
+            let timestamp: i64 = tokens.next()?.parse().ok()?;
+            let local = timestamp.checked_add(utc_offset)?;
+            time = Some(local.rem_euclid(86400));
+            local.div_euclid(86400)
+        }
+        Some(token) => if let Some(target) = parse_weekday(token) {
+            today + (target as i64 - weekday(today) as i64).rem_euclid(7)
+        } else {
+            parse_iso(token)?
+        }
+    };
+
+    // apply offsets, like `+ 45d` or `-2w`
+    while let Some(token) = tokens.next() {
+        let (sign, offset) = match token {
+            "+" => (1, tokens.next()?),
+            "-" => (-1, tokens.next()?),
+            token => if let Some(offset) = token.strip_prefix('+') {
+                (1, offset)
+            } else if let Some(offset) = token.strip_prefix('-') {
+                (-1, offset)
+            } else {
+                return None
+            }
+        };
+
+        let split = offset.find(|c: char| !c.is_ascii_digit()).unwrap_or(offset.len());
+        let (amount, unit) = offset.split_at(split);
+        let amount: i64 = sign * amount.parse::<i64>().ok()?;
+
+        day = match unit {
+            "" | "d" | "day" | "days" => day.checked_add(amount)?,
+            "w" | "week" | "weeks" => day.checked_add(amount.checked_mul(7)?)?,
+            "m" | "month" | "months" => add_months(day, amount)?,
+            "y" | "year" | "years" => add_months(day, amount.checked_mul(12)?)?,
+            _ => return None
+        };
+        if day.abs() > MAX_YEARS*366 { return None }
+    }
+
+    (day.abs() <= MAX_YEARS*366).then_some((day, time))
+}
+
+/// Accepts the full name of the day, or at least its first three letters
+fn parse_weekday(token: &str) -> Option<usize> {
+    let token = token.to_lowercase();
+    if token.len() < 3 { return None }
+
+    WEEKDAYS.iter().position(|day| day.to_lowercase().starts_with(&token))
+}
+
+/// Parses a `YYYY-MM-DD` date
+fn parse_iso(token: &str) -> Option<i64> {
+    let mut parts = token.splitn(3, '-');
+    let y: i64 = parts.next()?.parse().ok()?;
+    let m: i64 = parts.next()?.parse().ok()?;
+    let d: i64 = parts.next()?.parse().ok()?;
+
+    if y.abs() > MAX_YEARS || !(1..=12).contains(&m) || d < 1 || d > days_in_month(y, m) { return None }
+
+    Some(days_from_civil(y, m, d))
+}
+
+/// Runs `date` once to get the offset of the local timezone from UTC, in seconds
+fn local_utc_offset() -> i64 {
+    let Ok(output) = Command::new("date").arg("+%z").output() else { return 0 };
+    let offset = String::from_utf8_lossy(&output.stdout);
+    let offset = offset.trim();
+
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let hours: i64 = offset.get(1..3).and_then(|h| h.parse().ok()).unwrap_or(0);
+    let minutes: i64 = offset.get(3..5).and_then(|m| m.parse().ok()).unwrap_or(0);
+
+    sign * (hours*3600 + minutes*60)
+}
+
+/// Index of the day of the week in `WEEKDAYS` (the 1st of January 1970 was a thursday)
+fn weekday(day: i64) -> usize {
+    (day + 3).rem_euclid(7) as usize
+}
+
+fn days_in_month(y: i64, m: i64) -> i64 {
+    let (next_y, next_m) = if m == 12 { (y + 1, 1) } else { (y, m + 1) };
+    days_from_civil(next_y, next_m, 1) - days_from_civil(y, m, 1)
+}
+
+/// Adds months to the given day, clamping the day of the month (January 31st + 1 month is February 28th/29th)
+/// Returns `None` if the result is too far away (see [`MAX_YEARS`])
+fn add_months(day: i64, months: i64) -> Option<i64> {
+    let (y, m, d) = civil_from_days(day);
+    let total = y.checked_mul(12)?.checked_add(m - 1)?.checked_add(months)?;
+    let (y, m) = (total.div_euclid(12), total.rem_euclid(12) + 1);
+    if y.abs() > MAX_YEARS { return None }
+
+    Some(days_from_civil(y, m, d.min(days_in_month(y, m))))
+}
+
+// Conversions between days since the epoch and the proleptic gregorian calendar
+// See http://howardhinnant.github.io/date_algorithms.html
+
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era*400;
+    let doy = (153*((m + 9) % 12) + 2)/5 + d - 1;
+    let doe = yoe*365 + yoe/4 - yoe/100 + doy;
+    era*146097 + doe - 719468
+}
+
+fn civil_from_days(day: i64) -> (i64, i64, i64) {
+    let day = day + 719468;
+    let era = day.div_euclid(146097);
+    let doe = day - era*146097;
+    let yoe = (doe - doe/1460 + doe/36524 - doe/146096)/365;
+    let doy = doe - (365*yoe + yoe/4 - yoe/100);
+    let mp = (5*doy + 2)/153;
+    let d = doy - (153*mp + 2)/5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era*400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+impl PluginExecution for DatePlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+    // results are computed from the query, in a meaningful order
+    fn sorted(&self) -> bool { false }
+    fn record_usage(&self) -> bool { false }
+
+    fn send_query(&mut self, _: &Config, query: &str) -> Action {
+        self.compute(query);
+        Action::None
+    }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        let Some(idx) = idx else { return Action::None };
+        copy_to_clipboard(&self.entries[idx].name)
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, _: &mut Matcher, _: &Pattern, out: &mut Vec<Entry<'a>>) {
+        // results are computed from the query, so they are never filtered
+        for (index, entry) in self.entries.iter().enumerate() {
+            out.push(Entry {
+                name: &entry.name,
+                icon: None,
+                comment: Some(entry.comment.as_str()),
+                score: 0,
+                label: Label::index(index)
+            });
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        &self.entries[index].name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_from_civil_counts_from_the_epoch() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1970, 1, 2), 1);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(2024, 2, 29), 19782);
+        assert_eq!(days_from_civil(1600, 1, 1), -135140);
+    }
+
+    #[test]
+    fn civil_from_days_is_the_inverse() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+
+        for day in (-800_000..800_000).step_by(97) {
+            let (y, m, d) = civil_from_days(day);
+            assert!((1..=12).contains(&m) && (1..=days_in_month(y, m)).contains(&d), "{day} gave {y}-{m}-{d}");
+            assert_eq!(days_from_civil(y, m, d), day);
+        }
+    }
+
+    #[test]
+    fn add_months_clamps_the_end_of_the_month() {
+        let date = |y, m, d| days_from_civil(y, m, d);
+        assert_eq!(add_months(date(2024, 1, 31), 1), Some(date(2024, 2, 29)));
+        assert_eq!(add_months(date(2023, 1, 31), 1), Some(date(2023, 2, 28)));
+        assert_eq!(add_months(date(2024, 3, 31), -1), Some(date(2024, 2, 29)));
+        assert_eq!(add_months(date(2024, 2, 29), 12), Some(date(2025, 2, 28)));
+        assert_eq!(add_months(date(2024, 10, 31), 3), Some(date(2025, 1, 31)));
+        assert_eq!(add_months(date(2024, 1, 15), -13), Some(date(2022, 12, 15)));
+        assert_eq!(add_months(0, i64::MAX), None);
+    }
+
+    #[test]
+    fn parses_the_query_grammar() {
+        // a wednesday
+        let today = days_from_civil(2024, 5, 15);
+        let date = |y, m, d| Some((days_from_civil(y, m, d), None));
+        let parse = |query| parse_query(query, today, 0);
+
+        assert_eq!(parse(""), date(2024, 5, 15));
+        assert_eq!(parse("tomorrow"), date(2024, 5, 16));
+        assert_eq!(parse("Yesterday"), date(2024, 5, 14));
+        assert_eq!(parse("friday"), date(2024, 5, 17));
+        assert_eq!(parse("wed"), date(2024, 5, 15));
+        assert_eq!(parse("next wednesday"), date(2024, 5, 22));
+        assert_eq!(parse("last wednesday"), date(2024, 5, 8));
+        assert_eq!(parse("last thursday"), date(2024, 5, 9));
+        assert_eq!(parse("2025-03-01 + 45d"), date(2025, 4, 15));
+        assert_eq!(parse("2024-01-31 +1m"), date(2024, 2, 29));
+        assert_eq!(parse("today -2w + 1y"), date(2025, 5, 1));
+        assert_eq!(parse("unix 90000"), Some((1, Some(3600))));
+        assert_eq!(parse_query("unix 0", today, -3600), Some((-1, Some(82800))));
+
+        assert_eq!(parse("2024-02-30"), None);
+        assert_eq!(parse("someday"), None);
+        assert_eq!(parse("today + 3 weeks"), None);
+        assert_eq!(parse("today 5d"), None);
+    }
+
+    #[test]
+    fn rejects_dates_that_overflow() {
+        let today = days_from_civil(2024, 5, 15);
+        let parse = |query| parse_query(query, today, 0);
+
+        assert_eq!(parse("today + 9223372036854775807d"), None);
+        assert_eq!(parse("today - 9223372036854775807d"), None);
+        assert_eq!(parse("today + 2000000000000000000w"), None);
+        assert_eq!(parse("today + 800000000000000000y"), None);
+        assert_eq!(parse("today + 9223372036854775807m"), None);
+        assert_eq!(parse("9223372036854775807-01-01"), None);
+        assert_eq!(parse("unix 9223372036854775807"), None);
+        assert_eq!(parse_query("unix 9223372036854775807", today, 3600), None);
+        assert_eq!(parse("today + 400000000d"), None);
+    }
+}
@@ -1,37 +1,35 @@
 use std::{iter::Peekable, io::Lines};
-use crate::{icon::IconPath, arguments::Protocol, plugin::{Plugin, PluginExecution, Action, Entry}, config::Config};
+use crate::{icon::IconPath, arguments::Protocol, plugin::{Plugin, PluginExecution, Action, Entry, entry::MatchText}, config::Config};
 use super::user::read_entry_from_stream;
 
 struct DmenuEntry {
     name: String,
     icon: Option<IconPath>,
-    comment: Option<String>
+    comment: Option<String>,
+    to_match: MatchText
 }
 
 impl DmenuEntry {
+    fn new(name: String, icon: Option<IconPath>, comment: Option<String>) -> Self {
+        let to_match = MatchText::new(&name, comment.as_deref());
+        Self { name, icon, comment, to_match }
+    }
+
     /// Creates a new dmenu entry from a line using rofi's extended dmenu protocol
     /// To set an icon for an entry, append \0icon\x1f<icon-name> to the name of the entry
     fn new_from_rofi_extended(line: &str) -> Option<Self> {
         if let Some((name, icon)) = line.split_once('\0') {
             let Some(("icon", icon)) = icon.split_once('\x1f') else { return None };
 
-            Some(Self {
-                name: name.to_owned(),
-                icon: Some(IconPath::new(icon.to_owned(), None)),
-                comment: None
-            })
+            Some(Self::new(name.to_owned(), Some(IconPath::new(icon.to_owned(), None)), None))
         } else {
-            Some(Self {
-                name: line.to_owned(),
-                icon: None,
-                comment: None
-            })
+            Some(Self::new(line.to_owned(), None, None))
         }
     }
 
     fn new_from_keal(lines: &mut Peekable<Lines<std::io::StdinLock>>) -> Self {
         let (name, icon, comment) = read_entry_from_stream(lines, None);
-        Self { name, icon, comment }
+        Self::new(name, icon, comment)
     }
 }
 
@@ -46,6 +44,10 @@ impl DmenuPlugin {
             icon: None,
             comment: None,
             config: Default::default(),
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
             generator: Box::new(move |_, _| {
                 // reads entries from stdin
                 let mut entries = vec![];
@@ -74,6 +76,7 @@ impl PluginExecution for DmenuPlugin {
     fn send_query(&mut self, _: &crate::config::Config, _: &str) -> Action { Action::None }
 
     fn send_enter(&mut self, _: &crate::config::Config, query: &str, idx: Option<usize>) -> Action {
+        crate::set_exit_code(0);
         if let Some(idx) = idx {
             let entry = &self.0[idx];
             Action::PrintAndClose(entry.name.clone())
@@ -83,9 +86,8 @@ impl PluginExecution for DmenuPlugin {
     }
 
     fn get_entries<'a>(&'a self, _: &Config, matcher: &mut nucleo_matcher::Matcher, pattern: &nucleo_matcher::pattern::Pattern, out: &mut Vec<Entry<'a>>) {
-        let mut charbuf = vec![];
         for (index, entry) in self.0.iter().enumerate() {
-            let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, &entry.name, entry.icon.as_ref(), entry.comment.as_deref(), index)
+            let Some(entry) = Entry::from_match_text(matcher, pattern, &entry.to_match, &entry.name, entry.icon.as_ref(), entry.comment.as_deref(), index)
                 else { continue };
 
             out.push(entry)
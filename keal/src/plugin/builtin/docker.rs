@@ -0,0 +1,192 @@
+use std::{process::Command, thread::JoinHandle};
+
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{plugin::{Plugin, PluginExecution, Action, entry::Entry}, config::Config};
+
+enum Target {
+    Container { name: String, running: bool },
+    Project { name: String }
+}
+
+/// A container or compose project, along with its status
+struct TargetEntry {
+    target: Target,
+    /// status, and what enter and shift+enter do
+    comment: String
+}
+
+impl Target {
+    fn name(&self) -> &str {
+        match self {
+            Target::Container { name, .. } | Target::Project { name } => name
+        }
+    }
+
+    /// Running containers get a shell on enter, everything else is started
+    /// Shift+enter stops running containers and projects
+    fn action(&self, secondary: bool) -> (&'static str, Vec<&str>, bool) {
+        match (self, secondary) {
+            (Target::Container { name, running: true }, false) => ("open a shell", vec!["exec", "-it", name], true),
+            (Target::Container { name, running: true }, true) => ("stop", vec!["stop", name], false),
+            (Target::Container { name, running: false }, _) => ("start", vec!["start", name], false),
+            (Target::Project { name }, false) => ("start", vec!["compose", "-p", name, "start"], false),
+            (Target::Project { name }, true) => ("stop", vec!["compose", "-p", name, "stop"], false)
+        }
+    }
+}
+
+pub struct DockerPlugin {
+    command: String,
+    shell: String,
+    targets: Vec<TargetEntry>,
+    /// lists containers in the background, since the engine can take a while to answer (or to start, like podman)
+    loader: Option<JoinHandle<Vec<TargetEntry>>>
+}
+
+impl DockerPlugin {
+    pub fn create() -> Plugin {
+        let config = indexmap::IndexMap::from([
+            ("command".to_owned(), "docker".to_owned()), // can be replaced by `podman`
+            ("shell".to_owned(),   "sh".to_owned()),
+        ]);
+
+        Plugin {
+            name: "Docker".to_owned(),
+            prefix: "docker".to_owned(),
+            icon: None,
+            comment: Some("Manage containers and compose projects".to_owned()),
+            config,
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|plugin, _| {
+                let command = plugin.config["command"].clone();
+                let shell = plugin.config["shell"].clone();
+
+                let loader = {
+                    let command = command.clone();
+                    std::thread::spawn(move || list_targets(&command))
+                };
+                Box::new(DockerPlugin { command, shell, targets: vec![], loader: Some(loader) })
+            })
+        }
+    }
+
+    fn finish_loading(&mut self) {
+        let Some(loader) = self.loader.take() else { return };
+        self.targets = loader.join().unwrap_or_default();
+    }
+
+    fn run(&mut self, config: &Config, idx: Option<usize>, secondary: bool) -> Action {
+        self.finish_loading();
+        let Some(target) = idx.and_then(|idx| self.targets.get(idx)) else { return Action::None };
+
+        let (_, mut args, terminal) = target.target.action(secondary);
+        if terminal { args.push(&self.shell) }
+
+        let mut command = if terminal {
+            let mut command = Command::new(&config.terminal_path);
+            command.arg("-e").arg(&self.command);
+            command
+        } else {
+            Command::new(&self.command)
+        };
+        command.args(args);
+        Action::Exec(command.into())
+    }
+}
+
+/// Lists every container known to the engine, and the compose projects they belong to
+fn list_targets(command: &str) -> Vec<TargetEntry> {
+    let output = Command::new(command)
+        .args(["ps", "--all", "--format", "{{.Names}}\t{{.State}}\t{{.Status}}\t{{.Label \"com.docker.compose.project\"}}"])
+        .output();
+
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            eprintln!("docker: `{command} ps` failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+            return vec![]
+        }
+        Err(e) => {
+            eprintln!("docker: failed to run `{command}`: {e}");
+            return vec![]
+        }
+    };
+
+    let mut containers = vec![];
+    // (name, running containers, total containers)
+    let mut projects: Vec<(String, usize, usize)> = vec![];
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut fields = line.split('\t');
+        let (Some(name), Some(state), Some(status)) = (fields.next(), fields.next(), fields.next()) else { continue };
+        let running = state == "running";
+
+        if let Some(project) = fields.next().filter(|p| !p.is_empty() && *p != "<no value>") {
+            if let Some(entry) = projects.iter_mut().find(|(p, _, _)| *p == project) {
+                entry.1 += running as usize;
+                entry.2 += 1;
+            } else {
+                projects.push((project.to_owned(), running as usize, 1));
+            }
+        }
+
+        containers.push((Target::Container { name: name.to_owned(), running }, status.to_owned()));
+    }
+
+    containers.extend(projects.into_iter().map(|(name, running, total)| (
+        Target::Project { name },
+        format!("compose project, {running}/{total} running")
+    )));
+
+    containers.into_iter().map(|(target, status)| {
+        let comment = match (target.action(false).0, target.action(true).0) {
+            (enter, shift) if enter == shift => format!("{status} (enter: {enter})"),
+            (enter, shift) => format!("{status} (enter: {enter}, shift+enter: {shift})")
+        };
+        TargetEntry { target, comment }
+    }).collect()
+}
+
+impl PluginExecution for DockerPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn loading(&mut self) -> bool {
+        if self.loader.as_ref().is_some_and(|loader| loader.is_finished()) {
+            self.finish_loading();
+        }
+
+        self.loader.is_some()
+    }
+
+    fn send_query(&mut self, _: &Config, _: &str) -> Action {
+        Action::None
+    }
+
+    fn send_enter(&mut self, config: &Config, _: &str, idx: Option<usize>) -> Action {
+        self.run(config, idx, false)
+    }
+
+    fn send_shift_enter(&mut self, config: &Config, _: &str, idx: Option<usize>) -> Action {
+        self.run(config, idx, true)
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        let mut charbuf = vec![];
+
+        for (index, entry) in self.targets.iter().enumerate() {
+            let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, entry.target.name(), None, Some(entry.comment.as_str()), index)
+                else { continue };
+
+            out.push(entry);
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        self.targets[index].target.name()
+    }
+}
@@ -0,0 +1,193 @@
+use std::{path::PathBuf, process::Command};
+
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{plugin::{Plugin, PluginExecution, Action, ClonableCommand, entry::Entry}, config::Config, xdg_utils::expand_home};
+
+struct GitEntry {
+    name: String,
+    comment: Option<String>,
+    command: ClonableCommand
+}
+
+pub struct GitPlugin {
+    editor: String,
+    /// terminal editors like vim need a terminal to run in
+    editor_in_terminal: bool,
+    opener: String,
+    /// the repository the entries were generated for
+    repo: Option<PathBuf>,
+    entries: Vec<GitEntry>
+}
+
+impl GitPlugin {
+    pub fn create() -> Plugin {
+        // `$VISUAL` and `$EDITOR` are usually terminal editors
+        let editor = std::env::var("VISUAL").or(std::env::var("EDITOR")).ok();
+        let config = indexmap::IndexMap::from([
+            ("editor_in_terminal".to_owned(), editor.is_some().to_string()),
+            ("editor".to_owned(), editor.unwrap_or("xdg-open".to_owned())),
+            ("opener".to_owned(), "xdg-open".to_owned()),
+        ]);
+
+        Plugin {
+            name: "Git".to_owned(),
+            prefix: "git".to_owned(),
+            icon: None,
+            comment: Some("Actions on a git repository (type its path)".to_owned()),
+            config,
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|plugin, _| {
+                Box::new(GitPlugin {
+                    editor: plugin.config["editor"].clone(),
+                    editor_in_terminal: plugin.config["editor_in_terminal"] == "true",
+                    opener: plugin.config["opener"].clone(),
+                    repo: None,
+                    entries: vec![]
+                })
+            })
+        }
+    }
+
+    /// Regenerates entries for the repository at `path`
+    /// `typed` is the path as it was typed by the user, so that entries still match the query
+    fn load_repo(&mut self, config: &Config, path: PathBuf, typed: &str) {
+        self.entries.clear();
+
+        let git = |args: &[&str]| {
+            let mut command = Command::new("git");
+            command.arg("-C").arg(&path).args(args);
+            command
+        };
+
+        let in_terminal = |args: &[&str]| {
+            let mut command = Command::new(&config.terminal_path);
+            command.arg("-e").arg("git").arg("-C").arg(&path).args(args);
+            command
+        };
+
+        self.entries.push(GitEntry {
+            name: format!("{typed}: pull"),
+            comment: Some("Pull changes from the remote".to_owned()),
+            command: in_terminal(&["pull"]).into()
+        });
+
+        let editor = if self.editor_in_terminal {
+            let mut command = Command::new(&config.terminal_path);
+            command.arg("-e").arg(&self.editor).arg(&path);
+            command
+        } else {
+            let mut command = Command::new(&self.editor);
+            command.arg(&path);
+            command
+        };
+        self.entries.push(GitEntry {
+            name: format!("{typed}: open in editor"),
+            comment: Some(self.editor.clone()),
+            command: editor.into()
+        });
+
+        if let Some(url) = output_of(git(&["remote", "get-url", "origin"])).and_then(|url| remote_to_https(url.trim())) {
+            let mut opener = Command::new(&self.opener);
+            opener.arg(&url);
+            self.entries.push(GitEntry {
+                name: format!("{typed}: open remote"),
+                comment: Some(url),
+                command: opener.into()
+            });
+        }
+
+        let branches = output_of(git(&["branch", "--format=%(HEAD)%(refname:short)"])).unwrap_or_default();
+        for branch in branches.lines() {
+            let (current, branch) = match branch.strip_prefix('*') {
+                Some(branch) => (true, branch),
+                None => (false, branch.trim_start())
+            };
+
+            self.entries.push(GitEntry {
+                name: format!("{typed}: checkout {branch}"),
+                comment: current.then(|| "current branch".to_owned()),
+                command: git(&["checkout", branch]).into()
+            });
+        }
+
+        self.repo = Some(path);
+    }
+}
+
+fn output_of(mut command: Command) -> Option<String> {
+    let output = command.output().ok()?;
+    if !output.status.success() { return None }
+
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Converts a remote url to one that can be opened in a browser
+/// `git@github.com:user/repo.git` becomes `https://github.com/user/repo`
+/// The port of `ssh://` urls is the ssh server's, so it is left out
+fn remote_to_https(url: &str) -> Option<String> {
+    let url = url.strip_suffix(".git").unwrap_or(url);
+
+    if url.starts_with("https://") || url.starts_with("http://") {
+        Some(url.to_owned())
+    } else if let Some(rest) = url.strip_prefix("ssh://") {
+        let rest = rest.split_once('@').map(|(_, r)| r).unwrap_or(rest);
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host = host.split_once(':').map(|(host, _)| host).unwrap_or(host);
+        Some(format!("https://{host}/{path}"))
+    } else if let Some((host, path)) = url.split_once('@').and_then(|(_, r)| r.split_once(':')) {
+        Some(format!("https://{host}/{path}"))
+    } else { None }
+}
+
+impl PluginExecution for GitPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn send_query(&mut self, config: &Config, query: &str) -> Action {
+        // anything after the path is used to filter the actions
+        // the path can contain spaces, so the longest start of the query that is a repository is taken
+        let query = query.trim_start();
+        let ends = query.char_indices().filter(|(_, c)| c.is_whitespace()).map(|(i, _)| i).chain([query.len()]);
+        let repo = ends.rev()
+            .map(|end| query[..end].trim_end())
+            .filter(|typed| !typed.is_empty())
+            .map(|typed| (typed, expand_home(typed)))
+            .find(|(_, path)| path.join(".git").exists());
+
+        match repo {
+            None => {
+                self.repo = None;
+                self.entries.clear();
+            }
+            Some((typed, path)) => if self.repo.as_ref() != Some(&path) {
+                self.load_repo(config, path, typed);
+            }
+        }
+
+        Action::None
+    }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        let Some(idx) = idx else { return Action::None };
+
+        Action::Exec(self.entries[idx].command.clone())
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        let mut charbuf = vec![];
+        for (index, entry) in self.entries.iter().enumerate() {
+            let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, &entry.name, None, entry.comment.as_deref(), index)
+                else { continue };
+
+            out.push(entry);
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        &self.entries[index].name
+    }
+}
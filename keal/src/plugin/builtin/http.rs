@@ -0,0 +1,155 @@
+use std::{io::Write, path::{Path, PathBuf}, process::{Command, Stdio}, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::Duration};
+
+use indexmap::IndexMap;
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{config::Config, plugin::{PluginExecution, PluginGenerator, Action, entry::{Entry, Label}}};
+
+use super::list_file::{ListFileEntry, RawEntry};
+
+/// How to reach the endpoint of a plugin
+struct Endpoint {
+    url: String,
+    /// sent along with the request, like `Authorization: Bearer <token>`
+    header: Option<String>,
+    /// how long to wait after the last keystroke before sending the query
+    debounce: Duration,
+    /// how long the request can take before it is abandoned
+    timeout: Duration
+}
+
+/// A response, with the generation of the query it answers
+type Response = (u64, anyhow::Result<Vec<ListFileEntry>>);
+
+/// A plugin declared with `type = http`, which POSTs the query to a URL and shows the entries in the JSON array it answers with
+/// The endpoint filters and orders the entries itself, so they are shown as they are
+pub struct HttpPlugin {
+    endpoint: Arc<Endpoint>,
+    entries: Vec<ListFileEntry>,
+    /// incremented with every query, so that the answer to an older one is dropped
+    generation: Arc<AtomicU64>,
+    response: Arc<Mutex<Option<Response>>>,
+    /// wether a query was sent and its answer hasn't arrived yet
+    pending: bool,
+    /// the plugin's directory, which commands run in and icons are relative to
+    cwd: PathBuf
+}
+
+impl HttpPlugin {
+    /// Reads `url`, `header`, `debounce_ms` and `timeout_ms` from the `[plugin]` section
+    pub fn generator(plugin_path: &Path, plugin: &mut IndexMap<String, String>) -> Option<PluginGenerator> {
+        let Some(url) = plugin.swap_remove("url") else {
+            eprintln!("plugin in {} has `type = http` but no `url`", plugin_path.display());
+            return None
+        };
+        let mut millis = |key: &str, default: u64| match plugin.swap_remove(key).map(|value| value.parse()) {
+            None => Some(Duration::from_millis(default)),
+            Some(Ok(value)) => Some(Duration::from_millis(value)),
+            Some(Err(_)) => {
+                eprintln!("invalid `{key}` for plugin in {}, expected milliseconds", plugin_path.display());
+                None
+            }
+        };
+        let debounce = millis("debounce_ms", 200)?;
+        let timeout = millis("timeout_ms", 2000)?;
+        let endpoint = Arc::new(Endpoint { url, header: plugin.swap_remove("header"), debounce, timeout });
+        let cwd = plugin_path.to_owned();
+
+        Some(Box::new(move |_, _| {
+            let mut this = HttpPlugin {
+                endpoint: endpoint.clone(), entries: vec![],
+                generation: Default::default(), response: Default::default(), pending: false, cwd: cwd.clone()
+            };
+            // the entries for an empty query are shown right away
+            this.send(String::new(), Duration::ZERO);
+            Box::new(this)
+        }))
+    }
+
+    /// Sends the query after `delay`, unless another one was sent in the meantime
+    fn send(&mut self, query: String, delay: Duration) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.pending = true;
+
+        let (endpoint, current, response, cwd) = (self.endpoint.clone(), self.generation.clone(), self.response.clone(), self.cwd.clone());
+        std::thread::spawn(move || {
+            std::thread::sleep(delay);
+            if current.load(Ordering::SeqCst) != generation { return }
+
+            let entries = request(&endpoint, &query)
+                .map(|raw| raw.into_iter().map(|entry| ListFileEntry::new(entry, Some(&cwd))).collect());
+            if current.load(Ordering::SeqCst) == generation {
+                *response.lock().unwrap() = Some((generation, entries));
+            }
+        });
+    }
+}
+
+/// POSTs `{"query": "<query>"}` to the endpoint with `curl`, and parses the JSON array of entries it answers with
+fn request(endpoint: &Endpoint, query: &str) -> anyhow::Result<Vec<RawEntry>> {
+    let mut command = Command::new("curl");
+    command.args(["--silent", "--show-error", "--fail", "--request", "POST", "--header", "Content-Type: application/json", "--data-binary", "@-"])
+        .arg("--max-time").arg(format!("{:.3}", endpoint.timeout.as_secs_f64()));
+    if let Some(header) = &endpoint.header {
+        command.arg("--header").arg(header);
+    }
+    let mut child = command.arg("--").arg(&endpoint.url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let body = serde_json::json!({ "query": query }).to_string();
+    // dropped right after, so that curl sees the end of the body
+    child.stdin.take().unwrap().write_all(body.as_bytes())?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim())
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+impl PluginExecution for HttpPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn loading(&mut self) -> bool {
+        if let Some((generation, entries)) = self.response.lock().unwrap().take() {
+            // an older answer that arrived just before the query changed
+            if generation == self.generation.load(Ordering::SeqCst) {
+                self.pending = false;
+                match entries {
+                    Ok(entries) => self.entries = entries,
+                    Err(e) => eprintln!("request to {} failed: {e:#}", self.endpoint.url)
+                }
+            }
+        }
+
+        self.pending
+    }
+
+    fn sorted(&self) -> bool { false }
+
+    fn send_query(&mut self, _: &Config, query: &str) -> Action {
+        self.send(query.to_owned(), self.endpoint.debounce);
+        Action::None
+    }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        match idx.and_then(|idx| self.entries.get(idx)) {
+            Some(entry) => entry.action(&self.cwd),
+            None => Action::None
+        }
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, _: &mut Matcher, _: &Pattern, out: &mut Vec<Entry<'a>>) {
+        out.extend(self.entries.iter().enumerate().map(|(index, entry)| Entry {
+            name: &entry.name, icon: entry.icon.as_ref(), comment: entry.comment.as_deref(), score: 0, label: Label::index(index)
+        }));
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        &self.entries[index].name
+    }
+}
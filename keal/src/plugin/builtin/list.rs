@@ -2,6 +2,7 @@ use crate::{icon::IconPath, plugin::{Plugin, PluginExecution, Action, entry::Ent
 
 struct ListEntry {
     name: String,
+    prefix: String,
     icon: Option<IconPath>,
     comment: Option<String>
 }
@@ -16,14 +17,57 @@ impl ListPlugin {
             icon: None,
             comment: Some("List loaded keal plugins".to_owned()),
             config: Default::default(),
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
             generator: Box::new(|_, manager| {
                 let entries = manager.list_plugins()
                     .map(|(prefix, plug)| ListEntry {
                         name: prefix.clone(),
+                        prefix: prefix.clone(),
                         icon: plug.icon.clone(),
                         comment: Some(plug.comment.as_ref()
                             .map(|c| format!("{} ({c})", plug.name))
                             .unwrap_or(plug.name.clone()))
+                            // aliases are matched too, so that searching for a synonym finds the prefix
+                            .map(|comment| match plug.aliases.is_empty() {
+                                true => comment,
+                                false => format!("{comment}, also {}", plug.aliases.join(", "))
+                            })
+                    })
+                    .collect();
+
+                Box::new(ListPlugin(entries))
+            })
+        }
+    }
+
+    /// Same as the list plugin, but shows more details about every plugin, and where it comes from
+    pub fn create_browser() -> Plugin {
+        Plugin {
+            name: "Plugins".to_owned(),
+            prefix: "plugins".to_owned(),
+            icon: None,
+            comment: Some("Browse loaded keal plugins".to_owned()),
+            config: Default::default(),
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|_, manager| {
+                let entries = manager.list_plugins()
+                    .map(|(prefix, plug)| {
+                        let source = plug.source.as_ref().map(|s| s.display().to_string()).unwrap_or("builtin".to_owned());
+                        ListEntry {
+                            name: plug.name.clone(),
+                            prefix: prefix.clone(),
+                            icon: plug.icon.clone(),
+                            comment: Some(match &plug.comment {
+                                Some(comment) => format!("`{prefix}`: {comment} ({source})"),
+                                None => format!("`{prefix}` ({source})")
+                            })
+                        }
                     })
                     .collect();
 
@@ -40,7 +84,7 @@ impl PluginExecution for ListPlugin {
 
     fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
         if let Some(idx) = idx {
-            let prefix = self.0[idx].name.clone();
+            let prefix = self.0[idx].prefix.clone();
             Action::ChangeInput(format!("{prefix} "))
         } else {
             Action::None
@@ -0,0 +1,145 @@
+use std::{path::{Path, PathBuf}, process::Command, time::SystemTime};
+
+use indexmap::IndexMap;
+use nucleo_matcher::{Matcher, pattern::Pattern};
+use serde::Deserialize;
+
+use crate::{icon::IconPath, xdg_utils::expand_home, config::Config, plugin::{PluginExecution, PluginGenerator, Action, entry::{Entry, MatchText}}};
+
+/// An entry as written in the list file, every field but the name is optional
+#[derive(Deserialize)]
+pub struct RawEntry {
+    name: String,
+    comment: Option<String>,
+    icon: Option<String>,
+    /// shell command run when the entry is chosen
+    exec: Option<String>,
+    /// printed to stdout when the entry is chosen, before keal closes
+    print: Option<String>
+}
+
+pub struct ListFileEntry {
+    pub name: String,
+    pub comment: Option<String>,
+    pub icon: Option<IconPath>,
+    exec: Option<String>,
+    print: Option<String>,
+    to_match: MatchText
+}
+
+impl ListFileEntry {
+    /// `icon_dir` is the directory relative icons are in
+    pub fn new(raw: RawEntry, icon_dir: Option<&Path>) -> Self {
+        // empty CSV cells are read as empty strings
+        let field = |value: Option<String>| value.filter(|value| !value.is_empty());
+
+        let comment = field(raw.comment);
+        ListFileEntry {
+            to_match: MatchText::new(&raw.name, comment.as_deref()),
+            name: raw.name,
+            comment,
+            icon: field(raw.icon).map(|icon| IconPath::new(icon, icon_dir)),
+            exec: field(raw.exec),
+            print: field(raw.print)
+        }
+    }
+
+    /// Runs `exec` in `cwd`, or prints `print`
+    pub fn action(&self, cwd: &Path) -> Action {
+        if let Some(exec) = &self.exec {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(exec).current_dir(cwd);
+            Action::Exec(command.into())
+        } else if let Some(print) = &self.print {
+            Action::PrintAndClose(print.clone())
+        } else {
+            Action::None
+        }
+    }
+}
+
+/// A plugin declared with `type = list-file`, showing the entries of a JSON or CSV file, which is read again when it changes
+/// Other programs can make menus by writing that file
+pub struct ListFilePlugin {
+    file: PathBuf,
+    /// when `file` was last modified as of the last read, `None` if it couldn't be read
+    modified: Option<SystemTime>,
+    entries: Vec<ListFileEntry>,
+    /// the plugin's directory, which commands run in
+    cwd: PathBuf
+}
+
+impl ListFilePlugin {
+    /// Reads `file` from the `[plugin]` section, relative to the plugin's directory
+    pub fn generator(plugin_path: &Path, plugin: &mut IndexMap<String, String>) -> Option<PluginGenerator> {
+        let Some(file) = plugin.swap_remove("file") else {
+            eprintln!("plugin in {} has `type = list-file` but no `file`", plugin_path.display());
+            return None
+        };
+        let file = plugin_path.join(expand_home(&file));
+        let cwd = plugin_path.to_owned();
+
+        Some(Box::new(move |_, _| {
+            let mut this = ListFilePlugin { file: file.clone(), modified: None, entries: vec![], cwd: cwd.clone() };
+            this.reload();
+            Box::new(this)
+        }))
+    }
+
+    /// Reads the file again if it was modified since it was last read
+    fn reload(&mut self) {
+        let modified = std::fs::metadata(&self.file).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified == self.modified { return }
+        self.modified = modified;
+
+        self.entries = match read_entries(&self.file) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("failed to read list file {}: {e:#}", self.file.display());
+                vec![]
+            }
+        };
+    }
+}
+
+/// Reads a JSON array of entries, or a CSV file with a header naming the columns, depending on the file's extension
+/// Icons are relative to the file's directory
+fn read_entries(path: &Path) -> anyhow::Result<Vec<ListFileEntry>> {
+    let raw: Vec<RawEntry> = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_reader(std::io::BufReader::new(std::fs::File::open(path)?))?,
+        Some("csv") => csv::Reader::from_path(path)?.deserialize().collect::<Result<_, _>>()?,
+        _ => anyhow::bail!("expected a `.json` or `.csv` file")
+    };
+
+    Ok(raw.into_iter().map(|entry| ListFileEntry::new(entry, path.parent())).collect())
+}
+
+impl PluginExecution for ListFilePlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn send_query(&mut self, _: &Config, _: &str) -> Action {
+        self.reload();
+        Action::None
+    }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        match idx.and_then(|idx| self.entries.get(idx)) {
+            Some(entry) => entry.action(&self.cwd),
+            None => Action::None
+        }
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        for (index, entry) in self.entries.iter().enumerate() {
+            let Some(entry) = Entry::from_match_text(matcher, pattern, &entry.to_match, &entry.name, entry.icon.as_ref(), entry.comment.as_deref(), index)
+                else { continue };
+
+            out.push(entry);
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        &self.entries[index].name
+    }
+}
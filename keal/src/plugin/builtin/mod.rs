@@ -1,5 +1,20 @@
 pub mod application;
+pub mod appimage;
 pub mod dmenu;
 pub mod user;
 pub mod list;
 pub mod session_manager;
+pub mod docker;
+pub mod git;
+pub mod date;
+pub mod currency;
+pub mod web;
+pub mod run;
+pub mod stats;
+pub mod recent;
+pub mod template;
+pub mod list_file;
+pub mod http;
+pub mod settings;
+pub mod conf;
+pub mod welcome;
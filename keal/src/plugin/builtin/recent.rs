@@ -0,0 +1,187 @@
+use std::{os::unix::ffi::OsStringExt, ffi::OsString, path::{Path, PathBuf}, process::Command};
+
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{icon::IconPath, plugin::{Plugin, PluginExecution, Action, entry::Entry}, xdg_utils::data_home, config::Config};
+
+struct RecentEntry {
+    name: String,
+    comment: String,
+    icon: Option<IconPath>,
+    path: PathBuf,
+    uri: String,
+    /// command line of the application that last opened the file, with `%u` or `%f` in place of the file
+    exec: Option<String>
+}
+
+pub struct RecentPlugin {
+    entries: Vec<RecentEntry>,
+    /// opens files with the application that last opened them instead of `xdg-open`
+    recorded: bool
+}
+
+impl RecentPlugin {
+    pub fn create() -> Plugin {
+        let config = indexmap::IndexMap::from([
+            ("open_with".to_owned(), "xdg-open".to_owned()), // or `recorded`, for the application that last opened the file
+        ]);
+
+        Plugin {
+            name: "Recent files".to_owned(),
+            prefix: "recent".to_owned(),
+            icon: None,
+            comment: Some("Open recently used documents".to_owned()),
+            config,
+            default_icon: Some(IconPath::Name("document-open-recent".to_owned())),
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|plugin, _| {
+                let entries = match read_recent() {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        eprintln!("failed to read recently used files: {e:#}");
+                        vec![]
+                    }
+                };
+
+                let recorded = match plugin.config["open_with"].as_str() {
+                    "recorded" => true,
+                    "xdg-open" => false,
+                    other => {
+                        eprintln!("unknown value `{other}` for `open_with`, expected `xdg-open` or `recorded`");
+                        false
+                    }
+                };
+
+                Box::new(RecentPlugin { entries, recorded })
+            })
+        }
+    }
+}
+
+/// Reads the files in `~/.local/share/recently-used.xbel`, which GTK and Qt applications write to, by most recently used
+/// Files that don't exist anymore are skipped
+fn read_recent() -> anyhow::Result<Vec<RecentEntry>> {
+    let path = data_home().map_err(anyhow::Error::msg)?.join("recently-used.xbel");
+    let file = match std::fs::read_to_string(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into())
+    };
+    let document = roxmltree::Document::parse(&file)?;
+
+    let mut bookmarks: Vec<_> = document.root_element().children()
+        .filter(|node| node.has_tag_name("bookmark"))
+        .filter_map(|bookmark| {
+            let uri = bookmark.attribute("href")?;
+            let path = file_path(uri)?;
+            if !path.exists() { return None }
+
+            // the applications are in namespaced elements, found by their local name
+            let metadata = bookmark.descendants();
+            let mime = metadata.clone().find(|node| node.tag_name().name() == "mime-type").and_then(|node| node.attribute("type"));
+            // the one that opened the file last is the most relevant
+            let application = metadata
+                .filter(|node| node.tag_name().name() == "application")
+                .max_by_key(|node| node.attribute("modified").unwrap_or(""));
+
+            Some((bookmark.attribute("modified").unwrap_or("").to_owned(), uri, path, mime, application))
+        })
+        .collect();
+    // timestamps are in ISO 8601, which sort like strings
+    bookmarks.sort_by(|(a, ..), (b, ..)| b.cmp(a));
+
+    Ok(bookmarks.into_iter().map(|(_, uri, path, mime, application)| {
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let dir = path.parent().map(home_relative).unwrap_or_default();
+
+        // GLib quotes the command line of the application
+        let exec = application.and_then(|a| a.attribute("exec")).map(|exec| exec.trim_matches('\'').to_owned());
+        let comment = match application.and_then(|a| a.attribute("name")) {
+            Some(app) => format!("{dir}, opened with {app}"),
+            None => dir
+        };
+
+        // applications' icons are usually named after their executable, and files' after their mime type
+        let program = exec.as_deref().and_then(|exec| exec.split_whitespace().next()).map(|p| p.rsplit('/').next().unwrap_or(p));
+        let icon = program.map(str::to_owned)
+            .or_else(|| mime.map(|mime| mime.replace('/', "-")))
+            .map(IconPath::Name);
+
+        RecentEntry { name, comment, icon, path, uri: uri.to_owned(), exec }
+    }).collect())
+}
+
+/// Returns the path of a `file://` URI, decoding its percent-encoded bytes
+fn file_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        match encoded[i] {
+            b'%' => {
+                let hex = std::str::from_utf8(encoded.get(i+1..i+3)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            byte => {
+                bytes.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Some(OsString::from_vec(bytes).into())
+}
+
+/// Shortens a path in the home directory to start with `~`
+fn home_relative(path: &Path) -> String {
+    match std::env::var_os("HOME").and_then(|home| path.strip_prefix(home).ok().map(Path::to_owned)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_owned(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string()
+    }
+}
+
+impl PluginExecution for RecentPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn send_query(&mut self, _: &Config, _: &str) -> Action { Action::None }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        let Some(idx) = idx else { return Action::None };
+        let entry = &self.entries[idx];
+
+        let command = match &entry.exec {
+            Some(exec) if self.recorded => {
+                // the file is passed as an argument instead of being pasted in the command line, so that it doesn't need quoting
+                let exec = exec.replace("%u", "\"$1\"").replace("%U", "\"$1\"").replace("%f", "\"$2\"").replace("%F", "\"$2\"");
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(exec).arg("sh").arg(&entry.uri).arg(&entry.path);
+                command
+            }
+            _ => {
+                let mut command = Command::new("xdg-open");
+                command.arg(&entry.path);
+                command
+            }
+        };
+        Action::Exec(command.into())
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        let mut charbuf = vec![];
+        for (index, entry) in self.entries.iter().enumerate() {
+            let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, &entry.name, entry.icon.as_ref(), Some(entry.comment.as_str()), index)
+                else { continue };
+
+            out.push(entry);
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        &self.entries[index].name
+    }
+}
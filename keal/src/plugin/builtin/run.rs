@@ -0,0 +1,74 @@
+use std::process::Command;
+
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{plugin::{Plugin, PluginExecution, Action, entry::{Entry, Label}}, config::Config};
+
+pub struct RunPlugin {
+    in_terminal: bool,
+    /// the entry shown for the current query, if it isn't empty
+    entry: Option<String>,
+    query: String
+}
+
+impl RunPlugin {
+    pub fn create() -> Plugin {
+        let config = indexmap::IndexMap::from([
+            ("in_terminal".to_owned(), "false".to_owned()),
+        ]);
+
+        Plugin {
+            name: "Run".to_owned(),
+            prefix: "run".to_owned(),
+            icon: None,
+            comment: Some("Run a shell command".to_owned()),
+            config,
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|plugin, _| {
+                Box::new(RunPlugin {
+                    in_terminal: plugin.config["in_terminal"] == "true",
+                    entry: None,
+                    query: String::new()
+                })
+            })
+        }
+    }
+}
+
+impl PluginExecution for RunPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn send_query(&mut self, _: &Config, query: &str) -> Action {
+        self.query = query.trim().to_owned();
+        self.entry = (!self.query.is_empty()).then(|| format!("Run '{}' in shell", self.query));
+        Action::None
+    }
+
+    fn send_enter(&mut self, config: &Config, _: &str, idx: Option<usize>) -> Action {
+        if idx.is_none() || self.query.is_empty() { return Action::None }
+
+        let mut command = if self.in_terminal {
+            let mut command = Command::new(&config.terminal_path);
+            command.arg("-e").arg("sh");
+            command
+        } else {
+            Command::new("sh")
+        };
+        command.arg("-c").arg(&self.query);
+        Action::Exec(command.into())
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, _: &mut Matcher, _: &Pattern, out: &mut Vec<Entry<'a>>) {
+        if let Some(entry) = &self.entry {
+            out.push(Entry { name: entry, icon: None, comment: None, score: 0, label: Label::index(0) });
+        }
+    }
+
+    fn get_name(&self, _: usize) -> &str {
+        self.entry.as_deref().unwrap_or("")
+    }
+}
@@ -43,6 +43,10 @@ impl SessionPlugin {
             prefix: "sm".to_owned(),
             icon: None,
             config,
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
             comment: Some("Manage current session".to_owned()),
             generator: Box::new(move |plugin, _| {
                 let mut entries = Vec::new();
@@ -0,0 +1,143 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, process::Command};
+
+use nucleo_matcher::{Matcher, pattern::Pattern, Utf32Str, Utf32String};
+use walkdir::WalkDir;
+
+use crate::{icon::IconPath, ini_parser::Ini, plugin::{Plugin, PluginExecution, Action, entry::{Entry, Label}}, xdg_utils::xdg_directories, config::Config};
+
+struct SettingsPanel {
+    name: String,
+    comment: Option<String>,
+    icon: Option<IconPath>,
+    /// concatenation of the keywords and comment, which say what can be changed in the panel
+    to_match: Utf32String,
+    exec: String
+}
+
+pub struct SettingsPlugin(Vec<SettingsPanel>);
+
+impl SettingsPlugin {
+    /// `current_desktop` is the `$XDG_CURRENT_DESKTOP` environment variable
+    pub fn create(current_desktop: String) -> Plugin {
+        Plugin {
+            name: "Settings".to_owned(),
+            prefix: "settings".to_owned(),
+            icon: None,
+            comment: Some("Open system settings panels".to_owned()),
+            config: Default::default(),
+            default_icon: Some(IconPath::Name("preferences-system".to_owned())),
+            // so that "bluetooth settings" finds the bluetooth panel without a prefix
+            keywords: vec!["settings".to_owned()],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(move |_, _| {
+                let current_desktop: Vec<&str> = current_desktop.split(':').collect();
+
+                // panels are found by their desktop file's name, so that those in the user's directory override the system's
+                let mut panels: HashMap<PathBuf, SettingsPanel> = HashMap::new();
+                for (dir, kservices) in xdg_directories("applications").into_iter().map(|dir| (dir, false))
+                    .chain(xdg_directories("kservices5").into_iter().map(|dir| (dir, true)))
+                {
+                    let files = WalkDir::new(dir).follow_links(true).into_iter().flatten()
+                        .map(|entry| entry.into_path())
+                        .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"));
+
+                    for path in files {
+                        let Some(name) = path.file_name() else { continue };
+                        let Some(ini) = Ini::from_file(&path, &['#']).ok() else { continue };
+                        if let Some(panel) = SettingsPanel::new(ini, &path, kservices, &current_desktop) {
+                            panels.insert(name.into(), panel);
+                        }
+                    }
+                }
+
+                let mut panels: Vec<_> = panels.into_values().collect();
+                panels.sort_by(|a, b| a.name.cmp(&b.name));
+
+                Box::new(SettingsPlugin(panels))
+            })
+        }
+    }
+}
+
+impl SettingsPanel {
+    /// Reads a settings panel from a desktop file, `None` if it isn't one
+    /// GNOME marks its panels with `X-GNOME-Settings-Panel`, and KDE names its modules `kcm_*`, or gives them the `KCModule` service type in Plasma 5's `kservices5`
+    /// Panels have `NoDisplay` set, so that they aren't listed as applications, which isn't checked here
+    fn new(mut ini: Ini, location: &Path, kservices: bool, current_desktop: &[&str]) -> Option<Self> {
+        let mut ini = ini.remove_section("Desktop Entry")?.into_map();
+        let stem = location.file_stem()?.to_str()?;
+
+        let gnome = ini.contains_key("X-GNOME-Settings-Panel");
+        let kcm = stem.starts_with("kcm_")
+            || ini.get("X-KDE-ServiceTypes").is_some_and(|types| types.split([',', ';']).any(|t| t.trim() == "KCModule"));
+        if !gnome && !kcm { return None }
+
+        if ini.get("Hidden").is_some_and(|hidden| hidden == "true") { return None }
+
+        let shown_in = |key: &str| ini.get(key).map(|desktops| desktops.split(';').filter(|s| !s.is_empty()).any(|x| current_desktop.contains(&x)));
+        if shown_in("OnlyShowIn") == Some(false) || shown_in("NotShowIn") == Some(true) { return None }
+
+        let name = ini.swap_remove("Name")?;
+        let comment = ini.swap_remove("Comment");
+        let icon = ini.swap_remove("Icon").map(|i| IconPath::new(i, None));
+        let to_match = format!("{name}{}{}",
+            ini.get("Keywords").map(String::as_ref).unwrap_or(""),
+            comment.as_deref().unwrap_or("")
+        ).into();
+
+        // modules in `kservices5` don't have an `Exec` key, they are opened by name
+        let exec = match ini.swap_remove("Exec") {
+            Some(exec) => strip_field_codes(&exec),
+            None if kservices => format!("kcmshell5 {stem}"),
+            None => return None
+        };
+
+        Some(SettingsPanel { name, comment, icon, to_match, exec })
+    }
+}
+
+/// Removes the `%f`, `%u`... codes of an `Exec` key, since panels are never opened with files
+fn strip_field_codes(exec: &str) -> String {
+    exec.split_whitespace()
+        .filter(|word| !(word.len() == 2 && word.starts_with('%')))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl PluginExecution for SettingsPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn send_query(&mut self, _: &Config, _: &str) -> Action { Action::None }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        let Some(idx) = idx else { return Action::None };
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&self.0[idx].exec);
+        Action::Exec(command.into())
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        let mut charbuf = vec![];
+
+        for (index, panel) in self.0.iter().enumerate() {
+            let score = pattern.score(Utf32Str::new(&panel.name, &mut charbuf), matcher)
+                .or_else(|| pattern.score(panel.to_match.slice(..), matcher));
+            let Some(score) = score else { continue };
+
+            out.push(Entry {
+                name: &panel.name,
+                icon: panel.icon.as_ref(),
+                comment: panel.comment.as_deref(),
+                score,
+                label: Label::index(index)
+            })
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        &self.0[index].name
+    }
+}
@@ -0,0 +1,139 @@
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{plugin::{Plugin, PluginExecution, Action, entry::Entry, usage::{self, Usage}}, config::Config};
+
+struct StatsEntry {
+    plugin: String,
+    name: String,
+    comment: String
+}
+
+pub struct StatsPlugin {
+    entries: Vec<StatsEntry>,
+    /// index into `entries` when the user chose a record
+    selected: Option<usize>,
+    /// actions on the selected record
+    actions: Vec<(String, String)>
+}
+
+/// name under which the plugin is registered, whose own records are hidden
+const NAME: &str = "Stats";
+
+impl StatsPlugin {
+    pub fn create() -> Plugin {
+        Plugin {
+            name: NAME.to_owned(),
+            prefix: "stats".to_owned(),
+            icon: None,
+            comment: Some("Inspect and forget launch statistics".to_owned()),
+            config: Default::default(),
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|_, _| {
+                Box::new(StatsPlugin { entries: list_records(), selected: None, actions: vec![] })
+            })
+        }
+    }
+
+    fn select(&mut self, index: Option<usize>) {
+        self.selected = index;
+        self.actions.clear();
+
+        let Some(index) = index else { return };
+        let entry = &self.entries[index];
+        self.actions.push((format!("{}: forget", entry.name), format!("Remove this record of {}", entry.plugin)));
+    }
+}
+
+/// Lists every usage record, by descending frecency
+fn list_records() -> Vec<StatsEntry> {
+    let now = usage::now();
+    let usage = Usage::load();
+
+    let mut records: Vec<_> = usage.records()
+        .filter(|((plugin, _), _)| *plugin != NAME)
+        .collect();
+    records.sort_by(|(_, a), (_, b)| b.frecency(now).total_cmp(&a.frecency(now)));
+
+    records.into_iter().map(|((plugin, name), record)| {
+        let times = if record.count == 1 { "once".to_owned() } else { format!("{} times", record.count) };
+        StatsEntry {
+            plugin: plugin.to_owned(),
+            name: name.to_owned(),
+            comment: format!("{plugin}, used {times}, {}", last_used(now, record.last_used))
+        }
+    }).collect()
+}
+
+fn last_used(now: u64, last_used: u64) -> String {
+    let age = now.saturating_sub(last_used);
+    match age {
+        _ if last_used == 0 => "last use unknown".to_owned(),
+        a if a < 3600 => "last used less than an hour ago".to_owned(),
+        a if a < 2*86400 => format!("last used {} hours ago", a / 3600),
+        a => format!("last used {} days ago", a / 86400)
+    }
+}
+
+impl PluginExecution for StatsPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn send_query(&mut self, _: &Config, query: &str) -> Action {
+        // the user went back to the list by editing the name of the selected record
+        if let Some(selected) = self.selected {
+            let kept = query.strip_prefix(self.entries[selected].name.as_str()).is_some_and(|r| r.starts_with(' '));
+            if !kept {
+                self.select(None);
+            }
+        }
+
+        Action::None
+    }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        let Some(idx) = idx else { return Action::None };
+
+        let Some(selected) = self.selected else {
+            self.select(Some(idx));
+            return Action::ChangeQuery(format!("{} ", self.entries[idx].name));
+        };
+
+        // only the forget action exists
+        let entry = self.entries.remove(selected);
+        Usage::load().forget((&entry.plugin, &entry.name));
+        self.select(None);
+
+        Action::ChangeQuery(String::new())
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        let mut charbuf = vec![];
+
+        if self.selected.is_some() {
+            for (index, (name, comment)) in self.actions.iter().enumerate() {
+                let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, name, None, Some(comment.as_str()), index)
+                    else { continue };
+
+                out.push(entry);
+            }
+        } else {
+            for (index, record) in self.entries.iter().enumerate() {
+                let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, &record.name, None, Some(record.comment.as_str()), index)
+                    else { continue };
+
+                out.push(entry);
+            }
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        if self.selected.is_some() {
+            &self.actions[index].0
+        } else {
+            &self.entries[index].name
+        }
+    }
+}
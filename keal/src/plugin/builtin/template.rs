@@ -0,0 +1,203 @@
+use std::{path::{Path, PathBuf}, process::{Command, Stdio}, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration, os::unix::process::CommandExt};
+
+use indexmap::IndexMap;
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{config::{config, Config}, ini_parser::Ini, plugin::{PluginExecution, PluginGenerator, Action, copy_to_clipboard, entry::{Entry, Label}}};
+
+/// What to do with what the command prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// the command is launched like an application, and keal closes
+    None,
+    /// copied to the clipboard, and keal closes
+    Copy,
+    /// shown as entries, which are copied when chosen
+    Show
+}
+
+struct TemplateEntry {
+    name: String,
+    /// replaces `%s` in the command
+    value: String
+}
+
+/// A plugin declared with `type = command-template`, running a single command with `%s` replaced by the query or by the value of a static entry
+pub struct TemplatePlugin {
+    name: String,
+    command: String,
+    output: Output,
+    /// the plugin's directory, which the command runs in
+    cwd: PathBuf,
+    entries: Vec<TemplateEntry>,
+    /// entry running the command with the query, at the index after the static entries
+    query: Option<String>,
+    /// lines printed by the command with `output = show`, shown until the query changes
+    shown: Option<Vec<String>>
+}
+
+impl TemplatePlugin {
+    /// Reads `command` and `output` from the `[plugin]` section, and static entries from the `[entries]` section, as `name = value`
+    pub fn generator(plugin_path: &Path, plugin: &mut IndexMap<String, String>, ini: &mut Ini) -> Option<PluginGenerator> {
+        let Some(command) = plugin.swap_remove("command") else {
+            eprintln!("plugin in {} has `type = command-template` but no `command`", plugin_path.display());
+            return None
+        };
+        let output = match plugin.swap_remove("output").as_deref() {
+            None | Some("none") => Output::None,
+            Some("copy") => Output::Copy,
+            Some("show") => Output::Show,
+            Some(other) => {
+                eprintln!("unknown output `{other}` for plugin in {}, expected `none`, `copy` or `show`", plugin_path.display());
+                return None
+            }
+        };
+        let entries: Vec<(String, String)> = ini.remove_section("entries").map(|s| s.into_iter().collect()).unwrap_or_default();
+        let cwd = plugin_path.to_owned();
+
+        Some(Box::new(move |plugin, _| {
+            let entries = entries.iter().map(|(name, value)| TemplateEntry {
+                name: name.clone(),
+                // `name =` uses the name itself
+                value: if value.is_empty() { name.clone() } else { value.clone() }
+            }).collect();
+
+            Box::new(TemplatePlugin {
+                name: plugin.name.clone(), command: command.clone(), output, cwd: cwd.clone(),
+                entries, query: None, shown: None
+            })
+        }))
+    }
+
+    /// The command with `%s` replaced by `value`
+    /// The value is given to `sh` as an argument instead of being pasted in the command, so that it never needs quoting
+    fn command(&self, value: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(self.command.replace("%s", "\"$1\"")).arg("sh").arg(value)
+            .current_dir(&self.cwd);
+        command
+    }
+}
+
+/// Runs the command and returns what it printed, killing it with every process it started if it takes longer than `plugin_timeout_ms`
+fn run(mut command: Command, name: &str) -> anyhow::Result<String> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let timeout = config().plugin_timeout_ms;
+    if timeout != 0 {
+        let (pid, done, name) = (child.id(), done.clone(), name.to_owned());
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(timeout));
+            if !done.load(Ordering::SeqCst) {
+                eprintln!("command of plugin `{name}` didn't finish within {timeout}ms, killing it");
+                // SAFETY: kill doesn't touch memory, and the group is the command's own since it was spawned with `process_group(0)`
+                unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+            }
+        });
+    }
+
+    let output = child.wait_with_output()?;
+    done.store(true, Ordering::SeqCst);
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => anyhow::bail!("{}", line.trim()),
+            None => anyhow::bail!("command exited with {}", output.status)
+        }
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl PluginExecution for TemplatePlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn sorted(&self) -> bool {
+        // output lines stay in the order they were printed
+        self.shown.is_none()
+    }
+
+    fn send_query(&mut self, _: &Config, query: &str) -> Action {
+        let query = query.trim();
+        self.query = (!query.is_empty()).then(|| query.to_owned());
+        self.shown = None;
+        Action::None
+    }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        if let Some(shown) = &self.shown {
+            return match idx.and_then(|idx| shown.get(idx)) {
+                Some(line) => copy_to_clipboard(line),
+                None => Action::None
+            }
+        }
+
+        // without a selected entry, the query is run
+        let value = match idx.and_then(|idx| self.entries.get(idx)) {
+            Some(entry) => &entry.value,
+            None => match &self.query {
+                Some(query) => query,
+                None => return Action::None
+            }
+        };
+        let command = self.command(value);
+
+        match self.output {
+            Output::None => Action::Exec(command.into()),
+            Output::Copy | Output::Show => {
+                let output = match run(command, &self.name) {
+                    Ok(output) => output,
+                    Err(e) => return Action::Invalid(format!("{e:#}"))
+                };
+
+                if self.output == Output::Copy {
+                    copy_to_clipboard(output.trim_end())
+                } else {
+                    self.shown = Some(output.lines().filter(|line| !line.trim().is_empty()).map(str::to_owned).collect());
+                    Action::None
+                }
+            }
+        }
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        // the query was given to the command, so its output isn't matched against it
+        if let Some(shown) = &self.shown {
+            out.extend(shown.iter().enumerate().map(|(index, line)| Entry {
+                name: line, icon: None, comment: None, score: 0, label: Label::index(index)
+            }));
+            return
+        }
+
+        let mut charbuf = vec![];
+        for (index, entry) in self.entries.iter().enumerate() {
+            let comment = (entry.value != entry.name).then_some(entry.value.as_str());
+            let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, &entry.name, None, comment, index)
+                else { continue };
+
+            out.push(entry);
+        }
+
+        if let Some(query) = &self.query {
+            out.push(Entry { name: query, icon: None, comment: None, score: 0, label: Label::index(self.entries.len()) });
+        }
+    }
+
+    fn get_name(&self, index: usize) -> &str {
+        if let Some(shown) = &self.shown {
+            return &shown[index]
+        }
+
+        match self.entries.get(index) {
+            Some(entry) => &entry.name,
+            None => self.query.as_deref().unwrap_or("")
+        }
+    }
+}
@@ -1,9 +1,12 @@
-use std::{iter::Peekable, process::{ChildStdin, ChildStdout}, io::{BufReader, Lines, BufRead, Write}, path::{Path, PathBuf}, fs};
+use std::{iter::Peekable, process::{ChildStdin, ChildStdout, Command}, io::{BufReader, Lines, BufRead, Write}, path::{Path, PathBuf}, fs, thread::JoinHandle, sync::{Arc, Condvar, Mutex}, time::{Duration, Instant}, os::unix::process::CommandExt};
 
 use bitflags::bitflags;
+use indexmap::IndexMap;
 use nucleo_matcher::{Matcher, pattern::Pattern};
 
-use crate::{ini_parser::Ini, icon::IconPath, config::Config, xdg_utils::config_dir, plugin::{PluginExecution, Plugin, Entry, Action}};
+use crate::{ini_parser::Ini, icon::IconPath, config::{config, Config}, xdg_utils::config_dir, plugin::{PluginExecution, PluginGenerator, Plugin, Entry, Action, entry::{Label, MatchText}}};
+
+use super::{template::TemplatePlugin, list_file::ListFilePlugin, http::HttpPlugin};
 
 /// returns `None` if the plugin directory does not exist
 pub fn get_user_plugins() -> Option<impl Iterator<Item = (String, Plugin)>> {
@@ -32,94 +35,342 @@ bitflags! {
     }
 }
 
+/// Options a plugin can give after its subscribed events
+struct PluginOptions {
+    /// `sort:none` keeps entries in the order they are given
+    sort: bool,
+    /// `filter:plugin` shows every entry as is, for plugins that filter them with the query themselves
+    filter: bool
+}
+
+impl Default for PluginOptions {
+    fn default() -> Self {
+        Self { sort: true, filter: true }
+    }
+}
+
 struct PluginEntry {
     name: String,
     comment: Option<String>,
-    icon: Option<IconPath>
+    icon: Option<IconPath>,
+    to_match: MatchText
+}
+
+impl PluginEntry {
+    fn new(name: String, icon: Option<IconPath>, comment: Option<String>) -> Self {
+        let to_match = MatchText::new(&name, comment.as_deref());
+        Self { name, comment, icon, to_match }
+    }
+}
+
+
+type PluginStdout = Peekable<Lines<BufReader<ChildStdout>>>;
+
+/// How the executable of a plugin is isolated, from the `sandbox` field of its config
+#[derive(Clone, Copy)]
+enum Sandbox {
+    None,
+    /// runs the plugin in bubblewrap, with a read-only filesystem, and without network unless `sandbox_network` is set
+    Bubblewrap { network: bool }
 }
 
+impl Sandbox {
+    fn command(self, exec: &Path, cwd: &Path) -> Command {
+        let mut command = match self {
+            Sandbox::None => Command::new(exec),
+            Sandbox::Bubblewrap { network } => {
+                let mut command = Command::new("bwrap");
+                command.args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp", "--die-with-parent"]);
+                if !network {
+                    command.arg("--unshare-net");
+                }
+                command.arg("--chdir").arg(cwd).arg("--").arg(exec);
+                command
+            }
+        };
+
+        let config = config();
+        let memory = config.plugin_memory_limit_mb * 1024 * 1024;
+        let cpu = config.plugin_cpu_limit_s;
+        if memory != 0 || cpu != 0 {
+            // SAFETY: only calls setrlimit, which is async-signal-safe, between fork and exec
+            unsafe {
+                command.pre_exec(move || {
+                    if memory != 0 && libc::setrlimit(libc::RLIMIT_AS, &rlimit(memory)) != 0 {
+                        return Err(std::io::Error::last_os_error())
+                    }
+                    if cpu != 0 && libc::setrlimit(libc::RLIMIT_CPU, &rlimit(cpu)) != 0 {
+                        return Err(std::io::Error::last_os_error())
+                    }
+                    Ok(())
+                });
+            }
+        }
+        command
+    }
+}
+
+/// Same soft and hard limit, so that the plugin can't raise it
+fn rlimit(limit: u64) -> libc::rlimit {
+    libc::rlimit { rlim_cur: limit as libc::rlim_t, rlim_max: limit as libc::rlim_t }
+}
+
+/// Kills a plugin that takes longer than `plugin_timeout_ms` to answer an event, instead of blocking keal forever
+struct Watchdog {
+    /// name of the plugin, for the error message
+    name: String,
+    timeout: Duration,
+    /// shared with the thread of the watchdog, `None` if `plugin_timeout_ms` is 0 and plugins can take as long as they want
+    shared: Option<Arc<(Mutex<WatchdogState>, Condvar)>>
+}
+
+#[derive(Default)]
+struct WatchdogState {
+    /// when to kill the plugin, and wether to kill the commands it started along with it
+    deadline: Option<(Instant, bool)>,
+    /// the plugin was dropped, so the thread can stop
+    stopped: bool
+}
+
+impl Watchdog {
+    /// Starts a thread sleeping until the plugin has to answer, which is woken up whenever the watchdog is armed or disarmed
+    fn new(pid: u32, name: String, timeout_ms: u64) -> Self {
+        let timeout = Duration::from_millis(timeout_ms);
+        if timeout.is_zero() { return Self { name, timeout, shared: None } }
+
+        let shared = Arc::new((Mutex::new(WatchdogState::default()), Condvar::new()));
+        {
+            let (shared, name) = (shared.clone(), name.clone());
+            std::thread::spawn(move || Self::watch(pid, &name, timeout, &shared));
+        }
+
+        Self { name, timeout, shared: Some(shared) }
+    }
+
+    fn watch(pid: u32, name: &str, timeout: Duration, (state, condvar): &(Mutex<WatchdogState>, Condvar)) {
+        let mut state = state.lock().unwrap();
+        while !state.stopped {
+            state = match state.deadline {
+                Some((deadline, group)) if Instant::now() >= deadline => {
+                    state.deadline = None;
+                    eprintln!("plugin `{name}` didn't answer within {}ms, killing it", timeout.as_millis());
+                    // SAFETY: kill doesn't touch memory, and the group is the plugin's own since it was spawned with `process_group(0)`
+                    let pid = if group { -(pid as libc::pid_t) } else { pid as libc::pid_t };
+                    unsafe { libc::kill(pid, libc::SIGKILL) };
+                    state
+                }
+                Some((deadline, _)) => condvar.wait_timeout(state, deadline.saturating_duration_since(Instant::now())).unwrap().0,
+                None => condvar.wait(state).unwrap()
+            };
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut WatchdogState)) {
+        let Some((state, condvar)) = self.shared.as_deref() else { return };
+        f(&mut state.lock().unwrap());
+        condvar.notify_one();
+    }
+
+    /// With `group`, the commands the plugin started are killed along with it, like when it is dropped while loading
+    fn arm(&self, group: bool) {
+        let deadline = Instant::now() + self.timeout;
+        self.update(|state| state.deadline = Some((deadline, group)));
+    }
+
+    fn disarm(&self) {
+        self.update(|state| state.deadline = None);
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.update(|state| state.stopped = true);
+    }
+}
+
+/// What is read from the plugin before it can be used
+struct Loaded {
+    stdout: PluginStdout,
+    events: PluginEvents,
+    options: PluginOptions,
+    entries: Vec<PluginEntry>
+}
 
 // TODO: Better error handling for plugins: instead of panicking or logging to stderr, show feedback in window
-// TODO: Asynchronous/Non blocking plugins
 
 pub struct UserPlugin {
     entries: Vec<PluginEntry>,
     child: std::process::Child,
     stdin: ChildStdin,
-    stdout: Peekable<Lines<BufReader<ChildStdout>>>,
+    /// taken by `loader` while the initial entries are being read
+    stdout: Option<PluginStdout>,
+    /// reads the subscribed events and the initial entries in the background, so slow plugins don't freeze the UI
+    loader: Option<JoinHandle<Loaded>>,
+    /// query typed while the plugin was loading
+    pending_query: Option<String>,
     events: PluginEvents,
+    options: PluginOptions,
+    /// entries hidden while the user is asked to confirm an action with `action:confirm:<message>`
+    confirming: Option<Vec<PluginEntry>>,
+    watchdog: Watchdog,
     cwd: PathBuf
 }
 
 impl UserPlugin {
-    /// creates a `Plugin` with a `UserPlugin` generator
+    /// creates a `Plugin` from its `config.ini`, with a generator depending on its `type`:
+    /// a `UserPlugin` running its executable by default, or one of the declarative plugins that don't need one
     fn create(plugin_path: &Path, mut ini: Ini) -> Option<Plugin> {
         let config = ini.remove_section("config").map(|c| c.into_map()).unwrap_or_default();
-        let mut ini = ini.remove_section("plugin")?.into_map();
+        let mut plugin = ini.remove_section("plugin")?.into_map();
+
+        let generator = match plugin.swap_remove("type").as_deref() {
+            None | Some("exec") => Self::generator(plugin_path, &mut plugin)?,
+            Some("command-template") => TemplatePlugin::generator(plugin_path, &mut plugin, &mut ini)?,
+            Some("list-file") => ListFilePlugin::generator(plugin_path, &mut plugin)?,
+            Some("http") => HttpPlugin::generator(plugin_path, &mut plugin)?,
+            Some(other) => {
+                eprintln!("unknown type `{other}` for plugin in {}, expected `exec`, `command-template`, `list-file` or `http`", plugin_path.display());
+                return None
+            }
+        };
 
-        let exec = plugin_path.join(ini.swap_remove("exec")?);
         Some(Plugin {
-            name: ini.swap_remove("name")?,
-            icon: ini.swap_remove("icon").map(|i| IconPath::new(i, Some(plugin_path))),
-            comment: ini.swap_remove("comment"),
-            prefix: ini.swap_remove("prefix")?,
+            name: plugin.swap_remove("name")?,
+            icon: plugin.swap_remove("icon").map(|i| IconPath::new(i, Some(plugin_path))),
+            default_icon: plugin.swap_remove("default_icon").map(|i| IconPath::new(i, Some(plugin_path))),
+            comment: plugin.swap_remove("comment"),
+            prefix: plugin.swap_remove("prefix")?,
+            keywords: plugin.swap_remove("keywords")
+                .map(|k| k.split(',').map(|k| k.trim().to_owned()).filter(|k| !k.is_empty()).collect())
+                .unwrap_or_default(),
+            aliases: plugin.swap_remove("aliases")
+                .map(|a| a.split(',').map(|a| a.trim().to_owned()).filter(|a| !a.is_empty()).collect())
+                .unwrap_or_default(),
+            source: Some(plugin_path.to_owned()),
             config,
-            generator: Box::new(move |plugin, _| {
-                use std::process::{Stdio, Command};
-
-                let cwd = exec.parent().unwrap().to_path_buf();
-                let mut child = Command::new(&exec)
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .current_dir(&cwd)
-                    .spawn().expect("Couldn't spawn process from plugin");
-
-                let stdin = child.stdin.take().unwrap();
-                let stdout = child.stdout.take().unwrap();
-                let stdout = BufReader::new(stdout).lines().peekable();
-
-                let mut this = Self {
-                    entries: vec![],
-                    child, stdin, stdout, events: PluginEvents::None, cwd
-                };
-
-                this.send_config(plugin);
-                this.get_events();
-                this.entries = this.get_choice_list();
-                Box::new(this)
-            })
+            generator
         })
     }
 
+    /// Reads `exec` and `sandbox` from the `[plugin]` section, for a generator running the plugin's executable
+    fn generator(plugin_path: &Path, ini: &mut IndexMap<String, String>) -> Option<PluginGenerator> {
+        let exec = plugin_path.join(ini.swap_remove("exec")?);
+        let sandbox = match ini.swap_remove("sandbox").as_deref() {
+            None | Some("none") => Sandbox::None,
+            Some("bwrap") => Sandbox::Bubblewrap { network: ini.swap_remove("sandbox_network").as_deref() == Some("true") },
+            Some(other) => {
+                // better not to load the plugin than to run it without the isolation it asked for
+                eprintln!("unknown sandbox `{other}` for plugin in {}, expected `bwrap` or `none`", plugin_path.display());
+                return None
+            }
+        };
+
+        Some(Box::new(move |plugin, _| {
+            use std::process::Stdio;
+
+            let cwd = exec.parent().unwrap().to_path_buf();
+            // in its own process group, so that it can be stopped along with the commands it runs, see `Drop`
+            let mut child = sandbox.command(&exec, &cwd)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .current_dir(&cwd)
+                .process_group(0)
+                .spawn().expect("Couldn't spawn process from plugin");
+
+            let stdin = child.stdin.take().unwrap();
+            let stdout = child.stdout.take().unwrap();
+            let mut stdout = BufReader::new(stdout).lines().peekable();
+
+            let loader_cwd = cwd.clone();
+            let loader = std::thread::spawn(move || {
+                let events = read_events(&mut stdout);
+                let options = read_options(&mut stdout);
+                let entries = read_choice_list(&mut stdout, &loader_cwd);
+                Loaded { stdout, events, options, entries }
+            });
+
+            crate::supervisor::watch_plugin(child.id());
+            let watchdog = Watchdog::new(child.id(), plugin.name.clone(), config().plugin_timeout_ms);
+            let mut this = Self {
+                entries: vec![],
+                child, stdin, stdout: None, loader: Some(loader), pending_query: None,
+                events: PluginEvents::None, options: PluginOptions::default(), confirming: None, watchdog, cwd
+            };
+
+            this.send_config(plugin);
+            Box::new(this)
+        }))
+    }
+
     fn send_config(&mut self, plugin: &Plugin) {
         for config in plugin.config.values() {
-            writeln!(self.stdin, "{config}").unwrap();
+            if let Err(e) = writeln!(self.stdin, "{config}") {
+                // the loader sees the plugin exit, and it ends up without entries
+                eprintln!("failed to send config to plugin `{}`: {e}", plugin.name);
+                return
+            }
         }
     }
 
-    fn get_events(&mut self) {
-        let line = self.stdout.next().unwrap().unwrap();
-
-        match line.split_once(':') {
-            Some(("events", events)) => for event in events.split(' ') {
-                match event {
-                    "enter" => self.events |= PluginEvents::Enter,
-                    "shift-enter" => self.events |= PluginEvents::ShiftEnter,
-                    "query" => self.events |= PluginEvents::Query,
-                    event => panic!("unknown event `{event}`")
-                }
+    /// Blocks until the initial entries are read, then sends the query typed in the meantime
+    /// The plugin is killed if it takes longer than `plugin_timeout_ms`, so that keal doesn't freeze on it
+    fn finish_loading(&mut self) {
+        let Some(loader) = self.loader.take() else { return };
+        let waiting = !loader.is_finished();
+        if waiting { self.watchdog.arm(true) }
+        let loaded = loader.join();
+        if waiting { self.watchdog.disarm() }
+        let Loaded { stdout, events, options, entries } = loaded.expect("plugin loading thread panicked");
+
+        self.stdout = Some(stdout);
+        self.events = events;
+        self.options = options;
+        self.entries = entries;
+
+        if let Some(query) = self.pending_query.take() {
+            if self.events.intersects(PluginEvents::Query) {
+                // the user has typed since, so only the effect of the action on entries is kept
+                let _ = self.send_event("query", &query);
             }
-            _ => panic!("expected subscribed events, got `{line}`") // Perhaps we can assume enter?
         }
     }
 
+    /// Sends an event and reads the answer to it
+    /// A plugin that exited (like one killed for going over its limits) can't be written to anymore, which is logged instead
+    fn send_event(&mut self, event: &str, value: &str) -> Action {
+        if let Err(e) = writeln!(self.stdin, "{event}\n{value}") {
+            eprintln!("failed to send `{event}` to plugin `{}`: {e}", self.watchdog.name);
+            return Action::None
+        }
+        self.get_action()
+    }
+
+    /// Reads the answer to an event, killing the plugin if it takes longer than `plugin_timeout_ms`
     fn get_action(&mut self) -> Action {
-        let line = self.stdout.next().unwrap().unwrap();
+        self.watchdog.arm(false);
+        let action = self.read_action();
+        self.watchdog.disarm();
+        action
+    }
+
+    fn read_action(&mut self) -> Action {
+        // the plugin exited, or was killed by the watchdog
+        let Some(Ok(line)) = self.stdout.as_mut().unwrap().next() else { return Action::None };
 
         match line.split_once(':') {
             Some(("action", action)) => match action.split_once(':') {
                 Some(("change_input", value)) => Action::ChangeInput(value.to_owned()),
                 Some(("change_query", value)) => Action::ChangeQuery(value.to_owned()),
+                Some(("invalid", message)) => Action::Invalid(message.to_owned()),
+                Some(("confirm", message)) => {
+                    // the answer is sent back to the plugin as a `confirm` event, see `send_enter`
+                    let entries = vec![
+                        PluginEntry::new("Yes".to_owned(), None, Some(message.to_owned())),
+                        PluginEntry::new("No".to_owned(), None, None)
+                    ];
+                    self.confirming = Some(std::mem::replace(&mut self.entries, entries));
+                    Action::None
+                }
                 Some(("update", index)) => {
                     let index: usize = index.parse().unwrap();
                     let element = self.get_choice_list().pop().expect("one element for update action");
@@ -129,6 +380,10 @@ impl UserPlugin {
                 _ => match action {
                     "fork" => Action::Fork,
                     "wait_and_close" => Action::WaitAndClose,
+                    "sensitive" => {
+                        crate::sensitive::start();
+                        Action::Sensitive
+                    }
                     "update_all" => {
                         self.entries = self.get_choice_list();
                         Action::None
@@ -142,37 +397,90 @@ impl UserPlugin {
     }
 
     fn get_choice_list(&mut self) -> Vec<PluginEntry> {
-        let mut entries = vec![];
-
-        // Read initial entries line by line
-        while self.stdout.peek().is_some() {
-            // looks at the next line
-            // if it is "end", or an error, break out of the loop
-            match self.stdout.peek().unwrap().as_deref() {
-                Ok("end") => {
-                    self.stdout.next();
-                    break
-                }
-                Err(_) => break,
-                _ => ()
+        read_choice_list(self.stdout.as_mut().unwrap(), &self.cwd)
+    }
+}
+
+fn read_events(stdout: &mut PluginStdout) -> PluginEvents {
+    // the plugin exited before writing anything (it was probably killed while loading)
+    let Some(Ok(line)) = stdout.next() else { return PluginEvents::None };
+
+    let mut subscribed = PluginEvents::None;
+    match line.split_once(':') {
+        Some(("events", events)) => for event in events.split(' ') {
+            match event {
+                "enter" => subscribed |= PluginEvents::Enter,
+                "shift_enter" | "shift-enter" => subscribed |= PluginEvents::ShiftEnter,
+                "query" => subscribed |= PluginEvents::Query,
+                event => panic!("unknown event `{event}`")
             }
+        }
+        _ => panic!("expected subscribed events, got `{line}`") // Perhaps we can assume enter?
+    }
+
+    subscribed
+}
+
+/// Reads the `option:value` lines that may follow the subscribed events
+fn read_options(stdout: &mut PluginStdout) -> PluginOptions {
+    let mut options = PluginOptions::default();
 
-            let (name, icon, comment) = read_entry_from_stream(&mut self.stdout, Some(&self.cwd));
-            entries.push(PluginEntry { name, icon, comment });
+    while let Some(Ok(line)) = stdout.peek() {
+        match line.split_once(':') {
+            Some(("sort", "none")) => options.sort = false,
+            Some(("sort", "score")) => options.sort = true,
+            Some(("sort", sort)) => eprintln!("unknown sort `{sort}`, expected `none` or `score`"),
+            Some(("filter", "plugin")) => options.filter = false,
+            Some(("filter", "keal")) => options.filter = true,
+            Some(("filter", filter)) => eprintln!("unknown filter `{filter}`, expected `plugin` or `keal`"),
+            _ => break
+        }
+        stdout.next();
+    }
+
+    options
+}
+
+fn read_choice_list(stdout: &mut PluginStdout, cwd: &Path) -> Vec<PluginEntry> {
+    let mut entries = vec![];
+
+    // Read initial entries line by line
+    while stdout.peek().is_some() {
+        // looks at the next line
+        // if it is "end", or an error, break out of the loop
+        match stdout.peek().unwrap().as_deref() {
+            Ok("end") => {
+                stdout.next();
+                break
+            }
+            Err(_) => break,
+            _ => ()
         }
 
-        entries
+        let (name, icon, comment) = read_entry_from_stream(stdout, Some(cwd));
+        entries.push(PluginEntry::new(name, icon, comment));
     }
+
+    entries
 }
 
 impl Drop for UserPlugin {
     fn drop(&mut self) {
         match self.child.try_wait() {
             Ok(Some(_)) => (), // process has already exited
+            // the user moved on before the initial entries were read (like typing `files ` and erasing it right away),
+            // so the commands generating them are killed too, instead of running to completion for nothing
+            // the loader then reads the end of the output, and its partial entries are dropped with it
+            _ if self.loader.is_some() => {
+                // SAFETY: kill doesn't touch memory, and the group is the plugin's own since it was spawned with `process_group(0)`
+                unsafe { libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL) };
+            }
+            // once loaded, the plugin may have started applications in its group, which should keep running
             _ => {
                 let _ = self.child.kill(); // ignore any resulting error
             }
         }
+        crate::supervisor::forget_plugin(self.child.id());
     }
 }
 
@@ -182,28 +490,79 @@ impl PluginExecution for UserPlugin {
     }
 
     fn wait(&mut self) {
-        let _ = self.child.wait();
+        // a copy of keal made by `launch::fork_plugin` isn't the plugin's parent, so it waits for the plugin to close its output instead
+        if self.child.wait().is_err() {
+            if let Some(stdout) = &mut self.stdout {
+                while let Some(Ok(_)) = stdout.next() {}
+            }
+        }
+    }
+
+    fn loading(&mut self) -> bool {
+        if self.loader.as_ref().is_some_and(|loader| loader.is_finished()) {
+            self.finish_loading();
+        }
+
+        self.loader.is_some()
+    }
+
+    fn sorted(&self) -> bool {
+        // confirmation answers stay in order
+        self.options.sort && self.options.filter && self.confirming.is_none()
     }
     
     fn send_query(&mut self, _: &Config, query: &str) -> Action {
+        if self.confirming.is_some() || self.finished() { return Action::None }
+        if self.loader.is_some() {
+            self.pending_query = Some(query.to_owned());
+            return Action::None
+        }
+
         if !self.events.intersects(PluginEvents::Query) { return Action::None }
 
-        writeln!(self.stdin, "query\n{query}").unwrap();
-        self.get_action()
+        self.send_event("query", query)
     }
 
     fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        self.finish_loading();
+        if self.finished() { return Action::None }
+
+        if let Some(entries) = self.confirming.take() {
+            let answer = if idx == Some(0) { "yes" } else { "no" };
+            self.entries = entries;
+
+            return self.send_event("confirm", answer)
+        }
+
         if !self.events.intersects(PluginEvents::Enter) { return Action::None }
         let Some(idx) = idx else { return Action::None };
 
-        writeln!(self.stdin, "enter\n{idx}").unwrap();
-        self.get_action()
+        self.send_event("enter", &idx.to_string())
+    }
+
+    fn send_shift_enter(&mut self, config: &Config, query: &str, idx: Option<usize>) -> Action {
+        self.finish_loading();
+        // confirmation answers don't have a secondary action
+        if self.confirming.is_some() || !self.events.intersects(PluginEvents::ShiftEnter) {
+            return self.send_enter(config, query, idx)
+        }
+        if self.finished() { return Action::None }
+        let Some(idx) = idx else { return Action::None };
+
+        self.send_event("shift_enter", &idx.to_string())
     }
 
     fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
-        let mut charbuf = vec![];
+        // both confirmation answers stay visible whatever is typed
+        if self.confirming.is_some() || !self.options.filter {
+            out.extend(self.entries.iter().enumerate().map(|(index, entry)| Entry {
+                name: &entry.name, icon: entry.icon.as_ref(), comment: entry.comment.as_deref(), score: 0, label: Label::index(index)
+            }));
+            return
+        }
+
         for (index, entry) in self.entries.iter().enumerate() {
-            let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, &entry.name, entry.icon.as_ref(), entry.comment.as_deref(), index)
+            let Some(entry) = Entry::from_match_text(matcher, pattern, &entry.to_match, &entry.name, entry.icon.as_ref(), entry.comment.as_deref(), index)
                 else { continue };
 
             out.push(entry);
@@ -239,3 +598,41 @@ pub fn read_entry_from_stream<B: BufRead>(
 
     (name, icon, comment)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+
+    fn sleeper() -> std::process::Child {
+        Command::new("sleep").arg("5").spawn().expect("couldn't run sleep")
+    }
+
+    #[test]
+    fn watchdog_kills_after_the_timeout() {
+        let mut child = sleeper();
+        let watchdog = Watchdog::new(child.id(), "sleep".to_owned(), 50);
+        watchdog.arm(false);
+
+        let start = Instant::now();
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn watchdog_spares_a_plugin_that_answered() {
+        let mut child = sleeper();
+        let watchdog = Watchdog::new(child.id(), "sleep".to_owned(), 50);
+        for _ in 0..3 {
+            watchdog.arm(false);
+            watchdog.disarm();
+        }
+
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(child.try_wait().unwrap().is_none(), "the disarmed watchdog killed the plugin");
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
@@ -0,0 +1,84 @@
+use std::process::Command;
+
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{plugin::{Plugin, PluginExecution, Action, entry::{Entry, Label}}, config::Config};
+
+pub struct WebPlugin {
+    url: String,
+    opener: String,
+    /// the entry shown for the current query, if it isn't empty
+    entry: Option<String>,
+    query: String
+}
+
+impl WebPlugin {
+    pub fn create() -> Plugin {
+        let config = indexmap::IndexMap::from([
+            ("url".to_owned(),    "https://duckduckgo.com/?q=%s".to_owned()), // `%s` is replaced by the query
+            ("opener".to_owned(), "xdg-open".to_owned()),
+        ]);
+
+        Plugin {
+            name: "Web Search".to_owned(),
+            prefix: "web".to_owned(),
+            icon: None,
+            comment: Some("Search the web".to_owned()),
+            config,
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|plugin, _| {
+                Box::new(WebPlugin {
+                    url: plugin.config["url"].clone(),
+                    opener: plugin.config["opener"].clone(),
+                    entry: None,
+                    query: String::new()
+                })
+            })
+        }
+    }
+}
+
+/// Percent-encodes everything except unreserved characters
+fn url_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            b' ' => out.push('+'),
+            byte => out.push_str(&format!("%{byte:02X}"))
+        }
+    }
+    out
+}
+
+impl PluginExecution for WebPlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+
+    fn send_query(&mut self, _: &Config, query: &str) -> Action {
+        self.query = query.trim().to_owned();
+        self.entry = (!self.query.is_empty()).then(|| format!("Search the web for '{}'", self.query));
+        Action::None
+    }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        if idx.is_none() || self.query.is_empty() { return Action::None }
+
+        let mut command = Command::new(&self.opener);
+        command.arg(self.url.replace("%s", &url_encode(&self.query)));
+        Action::Exec(command.into())
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, _: &mut Matcher, _: &Pattern, out: &mut Vec<Entry<'a>>) {
+        if let Some(entry) = &self.entry {
+            out.push(Entry { name: entry, icon: None, comment: None, score: 0, label: Label::index(0) });
+        }
+    }
+
+    fn get_name(&self, _: usize) -> &str {
+        self.entry.as_deref().unwrap_or("")
+    }
+}
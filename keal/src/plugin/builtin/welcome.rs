@@ -0,0 +1,57 @@
+use std::{path::PathBuf, process::Command};
+
+use nucleo_matcher::{Matcher, pattern::Pattern};
+
+use crate::{plugin::{Plugin, PluginExecution, Action, entry::Entry}, config::Config, xdg_utils::config_dir};
+
+/// Shown on first launch, pointing to the configuration that was just written
+pub struct WelcomePlugin {
+    path: PathBuf,
+    comment: String
+}
+
+impl WelcomePlugin {
+    pub fn create() -> Plugin {
+        Plugin {
+            name: "Welcome".to_owned(),
+            prefix: "welcome".to_owned(),
+            icon: None,
+            comment: Some("Shown the first time keal is launched".to_owned()),
+            config: Default::default(),
+            default_icon: None,
+            keywords: vec![],
+            aliases: vec![],
+            source: None,
+            generator: Box::new(|_, _| {
+                let path = config_dir().map(|dir| dir.join("config.ini")).unwrap_or_default();
+                let comment = format!("Your configuration was written to {}, press enter to open it", path.display());
+                Box::new(WelcomePlugin { path, comment })
+            })
+        }
+    }
+}
+
+impl PluginExecution for WelcomePlugin {
+    fn finished(&mut self) -> bool { false }
+    fn wait(&mut self) { }
+    fn send_query(&mut self, _: &Config, _: &str) -> Action { Action::None }
+
+    fn send_enter(&mut self, _: &Config, _: &str, idx: Option<usize>) -> Action {
+        if idx.is_none() { return Action::None }
+
+        let mut command = Command::new("xdg-open");
+        command.arg(&self.path);
+        Action::Exec(command.into())
+    }
+
+    fn get_entries<'a>(&'a self, _: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>) {
+        let mut charbuf = vec![];
+        if let Some(entry) = Entry::new(matcher, pattern, &mut charbuf, "Welcome to keal!", None, Some(&self.comment), 0) {
+            out.push(entry);
+        }
+    }
+
+    fn get_name(&self, _: usize) -> &str {
+        "Welcome to keal!"
+    }
+}
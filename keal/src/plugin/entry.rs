@@ -1,9 +1,60 @@
-use nucleo_matcher::{Matcher, pattern::Pattern, Utf32Str};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::icon::IconPath;
+use nucleo_matcher::{Matcher, pattern::{AtomKind, CaseMatching, Pattern}, Utf32Str, Utf32String};
+
+use crate::{arguments::arguments, icon::IconPath};
 
 use super::PluginIndex;
 
+/// Parses the query into the pattern entries are matched against
+/// With `--exact`, every word of the query has to appear as is instead of being fuzzy matched
+pub fn parse_pattern(pattern: &mut Pattern, query: &str) {
+    if arguments().exact {
+        *pattern = Pattern::new(query, CaseMatching::Ignore, AtomKind::Substring);
+    } else {
+        pattern.reparse(query, CaseMatching::Ignore);
+    }
+}
+
+/// Index to select in `new` entries, so that the entry selected in `old` stays selected wherever it moved
+/// Goes back to the first entry if it disappeared
+/// When the query changed and the first entry was selected, it stays selected, so that the best match is what gets launched
+pub fn follow_selection(old: &[OwnedEntry], new: &[OwnedEntry], selected: usize, query_changed: bool) -> usize {
+    if query_changed && selected == 0 { return 0 }
+
+    old.get(selected)
+        .and_then(|old| new.iter().position(|entry| entry.is_same(old)))
+        .unwrap_or(0)
+}
+
+/// Entries generated by the plugin manager, the best ones out of all those that matched
+#[derive(Debug, Default, Clone)]
+pub struct Page {
+    pub entries: Vec<OwnedEntry>,
+    /// how many of `entries` matched, leaving out loading placeholders
+    pub shown: usize,
+    /// how many entries matched before the list was cut, see [`super::PluginManager::next_page`] to get more of them
+    pub total: usize
+}
+
+impl Page {
+    /// Tells how many entries were left out, like "showing 50 of 1,243", or nothing when every entry is shown
+    pub fn indicator(&self) -> Option<String> {
+        (self.shown < self.total).then(|| format!("showing {} of {}", group_digits(self.shown), group_digits(self.total)))
+    }
+}
+
+/// Separates thousands with commas, like `1,243`
+fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len()/3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) { grouped.push(',') }
+        grouped.push(digit);
+    }
+    grouped
+}
+
 /// Returned by plugins to the plugin manager
 #[derive(Debug)]
 pub struct Entry<'a> {
@@ -22,7 +73,23 @@ pub struct OwnedEntry {
     pub comment: Option<String>,
     /// fuzzy matching score
     pub score: u32,
-    pub label: Label
+    pub label: Label,
+    /// header shown above this entry, set on the first entry of every plugin when `show_sections` is enabled
+    pub section: Option<String>
+}
+
+/// Name and comment of an entry converted once for the matcher, instead of on every keystroke
+/// Worth keeping next to the entries of plugins that can have a lot of them
+#[derive(Debug, Clone)]
+pub struct MatchText {
+    name: Utf32String,
+    comment: Option<Utf32String>
+}
+
+impl MatchText {
+    pub fn new(name: &str, comment: Option<&str>) -> Self {
+        Self { name: Utf32String::from(name), comment: comment.map(Utf32String::from) }
+    }
 }
 
 /// Specifies the origin of the entry
@@ -31,26 +98,50 @@ pub struct Label {
     /// plugin it comes from
     pub plugin_index: PluginIndex,
     /// index in the plugin itself
-    pub index: usize
+    pub index: usize,
+    /// generation of the manager when the entry was given out, see [`super::PluginManager::generation`]
+    pub generation: u64
 }
 
+/// index of the placeholder entry shown while a plugin is loading
+const LOADING_INDEX: usize = usize::MAX;
+
 impl Label {
     pub fn index(index: usize) -> Self {
-        Self { plugin_index: PluginIndex::default(), index }
+        Self { plugin_index: PluginIndex::default(), index, generation: 0 }
+    }
+
+    /// wether this labels the loading placeholder, which doesn't correspond to any plugin entry
+    pub fn is_loading(&self) -> bool {
+        self.index == LOADING_INDEX
     }
 
     fn with_plugin(self, plugin_index: PluginIndex) -> Self {
-        Self { plugin_index, index: self.index }
+        Self { plugin_index, ..self }
     }
 }
 
+/// Score of an entry from the scores of its name and comment, an entry matching only by its name counts it twice
+fn combine_scores(name: Option<u32>, comment: Option<u32>) -> Option<u32> {
+    name.map(|a| comment.map(|b| a + b).unwrap_or(2*a)).or(comment)
+}
+
 impl<'a> Entry<'a> {
     /// creates a new entry by fuzzy matching on the name and comment
     /// returns none if nothing matches
     pub fn new(matcher: &mut Matcher, pattern: &Pattern, charbuf: &mut Vec<char>, name: &'a str, icon: Option<&'a IconPath>, comment: Option<&'a str>, index: usize) -> Option<Self> {
         let a = pattern.score(Utf32Str::new(name, charbuf), matcher);
         let b = comment.and_then(|comment| pattern.score(Utf32Str::new(comment, charbuf), matcher));
-        let score = a.map(|a| b.map(|b| a + b).unwrap_or(2*a)).or(b)?;
+        let score = combine_scores(a, b)?;
+
+        Some(Self { name, icon, comment, score, label: Label::index(index) })
+    }
+
+    /// Same as [`Entry::new`], with `to_match` holding the name and comment already converted
+    pub fn from_match_text(matcher: &mut Matcher, pattern: &Pattern, to_match: &MatchText, name: &'a str, icon: Option<&'a IconPath>, comment: Option<&'a str>, index: usize) -> Option<Self> {
+        let a = pattern.score(to_match.name.slice(..), matcher);
+        let b = to_match.comment.as_ref().and_then(|comment| pattern.score(comment.slice(..), matcher));
+        let score = combine_scores(a, b)?;
 
         Some(Self { name, icon, comment, score, label: Label::index(index) })
     }
@@ -61,6 +152,14 @@ impl<'a> Entry<'a> {
             ..self
         }
     }
+
+    /// Uses the given icon if the entry doesn't have one
+    pub fn or_icon(self, icon: Option<&'a IconPath>) -> Self {
+        Self {
+            icon: self.icon.or(icon),
+            ..self
+        }
+    }
     
     pub fn to_owned(&self) -> OwnedEntry {
         OwnedEntry {
@@ -68,8 +167,32 @@ impl<'a> Entry<'a> {
             icon: self.icon.cloned(),
             comment: self.comment.map(str::to_owned),
             score: self.score,
-            label: self.label
+            label: self.label,
+            section: None
         }
     }
 }
 
+impl OwnedEntry {
+    /// Wether both are the same entry of the same plugin, even if it moved in the list or its index changed
+    pub fn is_same(&self, other: &OwnedEntry) -> bool {
+        self.label.plugin_index == other.label.plugin_index && self.name == other.name
+    }
+
+    /// Placeholder shown while a plugin is still producing its entries
+    /// The spinner advances every time entries are regenerated
+    pub fn loading(plugin_index: PluginIndex, plugin_name: &str, icon: Option<&IconPath>) -> Self {
+        const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let frame = FRAMES[(millis / 100) as usize % FRAMES.len()];
+
+        OwnedEntry {
+            name: format!("{frame} Loading {plugin_name}..."),
+            icon: icon.cloned(),
+            comment: None,
+            score: u32::MAX,
+            label: Label { plugin_index, index: LOADING_INDEX, generation: 0 },
+            section: None
+        }
+    }
+}
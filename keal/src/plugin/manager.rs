@@ -1,13 +1,30 @@
 use indexmap::IndexMap;
-use nucleo_matcher::{Matcher, pattern::Pattern};
+use nucleo_matcher::{Matcher, pattern::{Pattern, CaseMatching}};
 
-use crate::{config::config, arguments::arguments, icon::IconPath, xdg_utils::config_dir, log_time};
+use crate::{config::{config, Preload, ShowOnEmpty}, arguments::{arguments, Protocol}, icon::IconPath, xdg_utils::config_dir, log_time};
 
-use super::{Plugin, PluginExecution, builtin::{user::get_user_plugins, application::ApplicationPlugin, list::ListPlugin, session_manager::SessionPlugin}, Action, usage::Usage, entry::{Label, OwnedEntry}};
+use super::{Plugin, PluginExecution, builtin::{user::get_user_plugins, application::ApplicationPlugin, appimage::AppImagePlugin, list::ListPlugin, session_manager::SessionPlugin, docker::DockerPlugin, git::GitPlugin, date::DatePlugin, currency::CurrencyPlugin, web::WebPlugin, run::RunPlugin, stats::StatsPlugin, recent::RecentPlugin, settings::SettingsPlugin, conf::ConfPlugin, welcome::WelcomePlugin}, Action, usage::{self, Usage}, entry::{Entry, Label, OwnedEntry, Page}};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct PluginIndex(usize);
 
+/// How many plugins `preload = auto` starts
+const PRELOAD_AUTO_COUNT: usize = 3;
+
+/// Weighted contributions to the score entries are sorted by
+#[derive(Debug, Clone, Copy)]
+struct ScoreParts {
+    matching: f64,
+    usage: f64,
+    priority: f64
+}
+
+impl ScoreParts {
+    fn total(&self) -> f64 {
+        self.matching + self.usage + self.priority
+    }
+}
+
 #[derive(Default)]
 pub struct PluginManager {
     /// the list of all loaded plugins
@@ -17,6 +34,22 @@ pub struct PluginManager {
     /// if the user has typed a plugin prefix, then this will be the only plugin shown
     /// usize is an index into `self.plugins`
     current: Option<(PluginIndex, Box<dyn PluginExecution>)>,
+    /// plugins included without a prefix because one of their keywords was typed in the query
+    keyword_plugins: Vec<(PluginIndex, Box<dyn PluginExecution>)>,
+    /// pattern used for keyword plugins, which is the query without the keywords themselves
+    keyword_pattern: Pattern,
+    /// with `global_search`, every other plugin, started on the first query typed without a prefix
+    global_plugins: Vec<(PluginIndex, Box<dyn PluginExecution>)>,
+    /// plugins whose entries are shown when nothing else matches the query
+    fallback_plugins: Vec<(PluginIndex, Box<dyn PluginExecution>)>,
+    /// plugins started along with keal according to `preload`, until their prefix is typed
+    preloaded: Vec<(PluginIndex, Box<dyn PluginExecution>)>,
+    /// plugins that are still producing their initial entries, updated by `update_loading`
+    loading: Vec<PluginIndex>,
+    /// pages requested with `next_page` on top of the first one, until the input changes
+    extra_pages: usize,
+    /// incremented whenever the selected plugin is started, replaced or stopped, see [`Self::generation`]
+    generation: u64,
     /// how frequently different plugin entries are used
     usage: Usage
 }
@@ -26,12 +59,7 @@ impl PluginManager {
         let arguments = arguments();
 
         if arguments.dmenu {
-            let dmenu = super::builtin::dmenu::DmenuPlugin::create(arguments.protocol);
-            self.plugins = IndexMap::from_iter([
-                (dmenu.prefix.clone(), dmenu)
-            ]);
-            // add dmenu to default plugins at startup
-            self.add_default_plugin(0);
+            self.load_dmenu(arguments.protocol);
         } else {
             self.usage = Usage::load();
             self.plugins = get_user_plugins().into_iter().flatten().collect();
@@ -39,17 +67,61 @@ impl PluginManager {
             // insert application and list plugins
             log_time("loading application plugin");
             let current_desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
-            let applications = ApplicationPlugin::create(current_desktop);
+            let applications = ApplicationPlugin::create(current_desktop.clone());
             self.plugins.insert(applications.prefix.clone(), applications);
 
+            log_time("loading appimage plugin");
+            let appimages = AppImagePlugin::create();
+            self.plugins.insert(appimages.prefix.clone(), appimages);
+
             log_time("loading list plugin");
             let list = ListPlugin::create();
             self.plugins.insert(list.prefix.clone(), list);
+            let browser = ListPlugin::create_browser();
+            self.plugins.insert(browser.prefix.clone(), browser);
 
             log_time("loading session manager plugin");
             let session = SessionPlugin::create();
             self.plugins.insert(session.prefix.clone(), session);
 
+            log_time("loading docker plugin");
+            let docker = DockerPlugin::create();
+            self.plugins.insert(docker.prefix.clone(), docker);
+
+            log_time("loading git plugin");
+            let git = GitPlugin::create();
+            self.plugins.insert(git.prefix.clone(), git);
+
+            log_time("loading date plugin");
+            let date = DatePlugin::create();
+            self.plugins.insert(date.prefix.clone(), date);
+
+            log_time("loading currency plugin");
+            let currency = CurrencyPlugin::create();
+            self.plugins.insert(currency.prefix.clone(), currency);
+
+            log_time("loading stats plugin");
+            let stats = StatsPlugin::create();
+            self.plugins.insert(stats.prefix.clone(), stats);
+
+            log_time("loading recent files plugin");
+            let recent = RecentPlugin::create();
+            self.plugins.insert(recent.prefix.clone(), recent);
+
+            log_time("loading settings plugin");
+            let settings = SettingsPlugin::create(current_desktop);
+            self.plugins.insert(settings.prefix.clone(), settings);
+
+            log_time("loading config plugin");
+            let conf = ConfPlugin::create();
+            self.plugins.insert(conf.prefix.clone(), conf);
+
+            log_time("loading web search and run plugins");
+            let web = WebPlugin::create();
+            self.plugins.insert(web.prefix.clone(), web);
+            let run = RunPlugin::create();
+            self.plugins.insert(run.prefix.clone(), run);
+
             log_time("loading plugin overrides");
 
             let config = config();
@@ -67,6 +139,9 @@ impl PluginManager {
 
                     if let Some(icon)    = over.icon.as_ref()    {  plugin.icon    = Some(IconPath::new(icon.to_owned(), config_path.as_deref())) }
                     if let Some(comment) = over.comment.as_ref() {  plugin.comment = Some(comment.clone()) }
+                    if let Some(icon)    = over.default_icon.as_ref() { plugin.default_icon = Some(IconPath::new(icon.to_owned(), config_path.as_deref())) }
+                    if let Some(keywords) = over.keywords.as_ref() { plugin.keywords = keywords.iter().map(|k| k.trim().to_owned()).collect() }
+                    if let Some(aliases) = over.aliases.as_ref() { plugin.aliases = aliases.iter().map(|a| a.trim().to_owned()).filter(|a| !a.is_empty()).collect() }
                 } else {
                     eprintln!("unknown plugin in override: {name}");
                 }
@@ -89,137 +164,450 @@ impl PluginManager {
             }
 
             log_time("loading user default plugins");
+            if config.first_run {
+                let welcome = WelcomePlugin::create();
+                let (index, _) = self.plugins.insert_full(welcome.prefix.clone(), welcome);
+                self.add_default_plugin(index);
+            }
+
             for prefix in &config.default_plugins {
-                let Some(index) = self.plugins.get_index_of(prefix) else {
+                let Some((index, _, _)) = Self::find_plugin(&self.plugins, prefix) else {
                     eprintln!("unknown default plugin in configuration: {prefix}");
                     continue
                 };
 
                 self.add_default_plugin(index);
             }
+
+            for prefix in config.fallback_plugins.iter().filter(|p| !p.is_empty()) {
+                let Some((index, _, plugin)) = Self::find_plugin(&self.plugins, prefix) else {
+                    eprintln!("unknown fallback plugin in configuration: {prefix}");
+                    continue
+                };
+
+                self.fallback_plugins.push((PluginIndex(index), (plugin.generator)(plugin, self)));
+            }
             log_time("finished loading user default plugins");
+
+            self.preload_plugins();
+            log_time("finished preloading plugins");
         }
     }
 
+    /// Starts the plugins whose prefix the user is likely to type, see [`Preload`]
+    fn preload_plugins(&mut self) {
+        let prefixes: Vec<String> = match &config().preload {
+            Preload::Off => return,
+            Preload::List(prefixes) => prefixes.clone(),
+            Preload::Auto => {
+                // typing a prefix is recorded as a use of the list plugin's entry for it
+                let now = usage::now();
+                let mut typed: Vec<_> = self.usage.records()
+                    .filter(|((plugin, _), _)| *plugin == "List")
+                    .map(|((_, prefix), record)| (prefix, record.frecency(now)))
+                    .collect();
+                typed.sort_by(|a, b| b.1.total_cmp(&a.1));
+                typed.into_iter().take(PRELOAD_AUTO_COUNT).map(|(prefix, _)| prefix.to_owned()).collect()
+            }
+        };
+
+        for prefix in prefixes {
+            let Some((index, _, plugin)) = Self::find_plugin(&self.plugins, &prefix) else {
+                // prefixes in the usage history may belong to plugins that were since removed
+                if let Preload::List(_) = config().preload {
+                    eprintln!("unknown plugin to preload in configuration: {prefix}");
+                }
+                continue
+            };
+
+            self.preloaded.push((PluginIndex(index), (plugin.generator)(plugin, self)));
+        }
+    }
+
+    /// Takes the preloaded execution of a plugin, unless it already stopped
+    fn take_preloaded(preloaded: &mut Vec<(PluginIndex, Box<dyn PluginExecution>)>, index: PluginIndex) -> Option<Box<dyn PluginExecution>> {
+        let position = preloaded.iter().position(|(idx, _)| *idx == index)?;
+        let (_, mut execution) = preloaded.swap_remove(position);
+        (!execution.finished()).then_some(execution)
+    }
+
     fn add_default_plugin(&mut self, index: usize) {
         let plugin = &self.plugins[index];
         self.default_plugins.push((PluginIndex(index), (plugin.generator)(plugin, self)));
     }
 
+    /// Only loads the dmenu plugin, showing choices piped into stdin
+    pub fn load_dmenu(&mut self, protocol: Protocol) {
+        let dmenu = super::builtin::dmenu::DmenuPlugin::create(protocol);
+        self.plugins = IndexMap::from_iter([
+            (dmenu.prefix.clone(), dmenu)
+        ]);
+        // add dmenu to default plugins at startup
+        self.add_default_plugin(0);
+    }
+
     pub fn list_plugins(&self) -> impl Iterator<Item = (&String, &Plugin)> {
         self.plugins.iter()
     }
 
-    pub fn get_entries(&self, matcher: &mut Matcher, pattern: &Pattern, n: usize, sort_by_usage: bool) -> Vec<OwnedEntry> {
+    /// Everything that selects a plugin when typed before a space: the prefix of every plugin, then their aliases
+    pub fn prefixes(&self) -> impl Iterator<Item = &str> {
+        let aliases = self.plugins.values().flat_map(|plugin| plugin.aliases.iter());
+        self.plugins.keys().chain(aliases).map(String::as_str)
+    }
+
+    /// Finds a plugin by its prefix, or by one of its aliases
+    /// Takes the plugins rather than `self`, so that the rest of the manager can still be borrowed mutably
+    fn find_plugin<'a>(plugins: &'a IndexMap<String, Plugin>, prefix: &str) -> Option<(usize, &'a String, &'a Plugin)> {
+        plugins.get_full(prefix).or_else(|| {
+            let index = plugins.values().position(|plugin| plugin.aliases.iter().any(|alias| alias == prefix))?;
+            plugins.get_index(index).map(|(prefix, plugin)| (index, prefix, plugin))
+        })
+    }
+
+    /// Matches, sorts and cuts the entries of the shown plugins to `n` per page, with placeholders for those that are loading
+    pub fn get_entries(&self, matcher: &mut Matcher, pattern: &Pattern, n: usize, sort_by_usage: bool) -> Page {
         let config = config();
 
         let mut entries = vec![];
         let mut buf = vec![];
+        let global_search = config.global_search && self.current.is_none() && !pattern.atoms.is_empty();
         if let Some((idx, current)) = &self.current {
             current.get_entries(config, matcher, pattern, &mut buf);
-            entries.extend(buf.drain(..).map(|e| e.label(*idx)));
+            entries.extend(buf.drain(..).map(|e| e.label(*idx).or_icon(self.plugins[idx.0].default_icon.as_ref())));
         } else {
             for (idx, plug) in &self.default_plugins {
                 plug.get_entries(config, matcher, pattern, &mut buf);
-                entries.extend(buf.drain(..).map(|e| e.label(*idx)));
+                entries.extend(buf.drain(..).map(|e| e.label(*idx).or_icon(self.plugins[idx.0].default_icon.as_ref())));
+            }
+
+            for (idx, plug) in &self.keyword_plugins {
+                plug.get_entries(config, matcher, &self.keyword_pattern, &mut buf);
+                entries.extend(buf.drain(..).map(|e| e.label(*idx).or_icon(self.plugins[idx.0].default_icon.as_ref())));
+            }
+
+            // every plugin only gets a few entries, so that one with many matches doesn't bury the others
+            if global_search {
+                for (idx, plug) in &self.global_plugins {
+                    plug.get_entries(config, matcher, pattern, &mut buf);
+                    if plug.sorted() {
+                        buf.sort_by_key(|e| std::cmp::Reverse(e.score));
+                    }
+                    buf.truncate(config.global_search_limit as usize);
+                    entries.extend(buf.drain(..).map(|e| e.label(*idx).or_icon(self.plugins[idx.0].default_icon.as_ref())));
+                }
+            }
+
+            if entries.is_empty() {
+                for (idx, plug) in &self.fallback_plugins {
+                    plug.get_entries(config, matcher, pattern, &mut buf);
+                    entries.extend(buf.drain(..).map(|e| e.label(*idx).or_icon(self.plugins[idx.0].default_icon.as_ref())));
+                }
             }
         }
 
-        if sort_by_usage {
-            // first sort by score, then by usage
-            entries.sort_by_key(|entry| (
-                std::cmp::Reverse(entry.score),
-                std::cmp::Reverse(self.usage.get((&self.plugins[entry.label.plugin_index.0].name, &entry.name))),
-            ));
-        } else {
-            // only sort by score
-            entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        let now = usage::now();
+        if self.current.is_none() && pattern.atoms.is_empty() {
+            match config.show_on_empty {
+                ShowOnEmpty::Recent => entries.retain(|entry| self.usage.frecency((&self.plugins[entry.label.plugin_index.0].name, entry.name), now) > 0.0),
+                ShowOnEmpty::All => (),
+                ShowOnEmpty::None => entries.clear()
+            }
         }
 
-        entries.truncate(n);
+        // blend the fuzzy matching score with usage and plugin priority
+        // frecency grows without bounds, so it is flattened to keep frequently used entries from burying better matches
+        let priorities: Vec<f32> = self.plugins.values()
+            .map(|plugin| config.plugin_overrides.get(&plugin.name).and_then(|o| o.priority).unwrap_or(0.0))
+            .collect();
+        let usage_sorts: Vec<bool> = self.plugins.values()
+            .map(|plugin| config.plugin_overrides.get(&plugin.name).and_then(|o| o.usage_sort).unwrap_or(sort_by_usage))
+            .collect();
+        let blended = |entry: &Entry| {
+            let plugin = entry.label.plugin_index.0;
+            let usage = if usage_sorts[plugin] { self.usage.frecency((&self.plugins[plugin].name, entry.name), now).ln_1p() } else { 0.0 };
+
+            ScoreParts {
+                matching: config.match_weight as f64 * entry.score as f64,
+                usage: config.usage_weight as f64 * usage,
+                priority: config.priority_weight as f64 * priorities[plugin] as f64
+            }
+        };
+        let mut entries: Vec<_> = entries.into_iter().map(|entry| (blended(&entry), entry)).collect();
+        let total = entries.len();
+        let limit = (n*(self.extra_pages + 1)).saturating_sub(self.loading.len());
+
+        // with multiple default plugins, one with many matches (like applications) could take the whole page
+        if self.current.is_none() && self.default_plugins.len() > 1 {
+            let plugins: Vec<_> = self.default_plugins.iter().map(|(idx, plug)| (*idx, plug.sorted())).collect();
+            fair_share(&mut entries, &plugins, limit);
+        }
+
+        // plugins with their own ordering keep it when they are the only one shown
+        if self.current.as_ref().map(|(_, current)| current.sorted()).unwrap_or(true) {
+            entries.sort_by(|(a, _), (b, _)| b.total().total_cmp(&a.total()));
+        }
+
+        // sections are only useful when results come from multiple plugins
+        let show_sections = (config.show_sections || global_search) && self.current.is_none();
+        if show_sections {
+            // gather entries by plugin, ordering plugins by their best entry
+            let mut order: Vec<PluginIndex> = vec![];
+            for (_, entry) in &entries {
+                if !order.contains(&entry.label.plugin_index) { order.push(entry.label.plugin_index) }
+            }
+            entries.sort_by_key(|(_, entry)| order.iter().position(|&idx| idx == entry.label.plugin_index));
+        }
+
+        entries.truncate(limit);
+        let shown = entries.len();
+
+        let loading = self.loading.iter().map(|idx| {
+            let plugin = &self.plugins[idx.0];
+            OwnedEntry::loading(*idx, &plugin.name, plugin.icon.as_ref())
+        });
 
         // this clones the value of only the top keys, which should incur pretty minimal performance loss
         // in response, it allows putting plugins in an async future, which is a much bigger win than a few avoided clones
-        entries.into_iter().map(|e| e.to_owned()).collect()
+        let mut previous = None;
+        let entries = entries.into_iter().map(|(score, e)| {
+            let mut owned = e.to_owned();
+            if !config.show_icons { owned.icon = None }
+            if !config.show_comments { owned.comment = None }
+            if arguments().debug_scores {
+                let plugin = &self.plugins[e.label.plugin_index.0].name;
+                let breakdown = format!("[{:.1} = match {:.1} + usage {:.1} + priority {:.1}, {plugin}]", score.total(), score.matching, score.usage, score.priority);
+                owned.comment = Some(match owned.comment {
+                    Some(comment) => format!("{comment} {breakdown}"),
+                    None => breakdown
+                });
+            }
+            if show_sections && previous != Some(e.label.plugin_index) {
+                owned.section = Some(self.plugins[e.label.plugin_index.0].name.clone());
+            }
+            previous = Some(e.label.plugin_index);
+            owned
+        });
+
+        let mut entries: Vec<_> = loading.chain(entries).collect();
+        for entry in &mut entries {
+            entry.label.generation = self.generation;
+        }
+
+        Page { entries, shown, total }
+    }
+
+    /// Counts the changes of the selected plugin, and is given to the labels of entries by [`Self::get_entries`]
+    /// Entries given out before the plugin was replaced are stale, and launching them does nothing,
+    /// since their index would point to an entry of the new plugin
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Shows another page of entries on the next `get_entries`, when more entries matched than were shown
+    pub fn next_page(&mut self) {
+        self.extra_pages += 1;
+    }
+
+    /// With `auto_accept_single`, returns the entry to launch when the query narrows the list down to it
+    /// Fallback entries are made from the query itself, so they never count, and neither does a list that is still loading
+    pub fn auto_accept(&self, query: &str, entries: &[OwnedEntry]) -> Option<Label> {
+        if !config().auto_accept_single || query.is_empty() || !self.loading.is_empty() { return None }
+
+        let [entry] = entries else { return None };
+        let fallback = self.fallback_plugins.iter().any(|(idx, _)| *idx == entry.label.plugin_index);
+        (!fallback).then_some(entry.label)
+    }
+
+    /// Checks which of the shown plugins are still loading, so that `get_entries` can show a placeholder for them
+    /// Returns wether any plugin is loading, in which case entries should be regenerated periodically
+    pub fn update_loading(&mut self) -> bool {
+        self.loading.clear();
+
+        if let Some((idx, current)) = &mut self.current {
+            if current.loading() { self.loading.push(*idx) }
+        } else {
+            for (idx, execution) in self.default_plugins.iter_mut().chain(&mut self.keyword_plugins).chain(&mut self.global_plugins).chain(&mut self.fallback_plugins) {
+                if execution.loading() { self.loading.push(*idx) }
+            }
+        }
+
+        !self.loading.is_empty()
     }
 
     /// Changes the input field to a new value
+    /// `prefix` is the prefix of the plugin selected by the user (shown apart from the query by frontends), if any
     /// `from_user` describes wether this change originates from user interaction
     /// Or wether it comes from a plugin action, (and should therefore not be propagated as an event, to avoid cycles).
-    /// Returns the actual query string, and the action that resulted from the input
-    pub fn update_input(&mut self, input: &str, from_user: bool) -> (String, Action) {
-        let filter_starts_with_plugin = if let Some((name, remainder)) = input.split_once(' ') {
-            self.plugins.get_full(name).map(|(idx, _, plugin)| ((PluginIndex(idx), plugin), remainder))
-        } else { None };
-
-        // launch or stop plugin execution depending on new state of filter
-        // if in plugin mode, remove plugin prefix from filter
-        let (query, action) = match (filter_starts_with_plugin, &mut self.current) {
-            (Some(((idx, plugin), remainder)), None) => { // launch plugin
+    /// Returns the action that resulted from the input
+    pub fn update_input(&mut self, prefix: Option<&str>, query: &str, from_user: bool) -> Action {
+        self.extra_pages = 0;
+
+        let selected_plugin = prefix
+            .and_then(|prefix| Self::find_plugin(&self.plugins, prefix))
+            .map(|(idx, _, plugin)| (PluginIndex(idx), plugin));
+
+        // launch or stop plugin execution depending on the selected plugin
+        match (selected_plugin, &mut self.current) {
+            (Some((idx, plugin)), None) => { // launch plugin
                 self.usage.add_use(("List", &plugin.prefix));
                 
-                let mut execution = (plugin.generator)(plugin, self);
-                let action = execution.send_query(config(), remainder);
+                let mut execution = match Self::take_preloaded(&mut self.preloaded, idx) {
+                    Some(execution) => execution,
+                    None => (plugin.generator)(plugin, self)
+                };
+                let action = execution.send_query(config(), query);
 
                 self.current = Some((idx, execution));
+                self.generation += 1;
 
-                (remainder.to_owned(), action)
+                action
             }
-            (Some(((idx, plugin), remainder)), Some((execution_idx, execution))) => {
-                let remainder = remainder.to_owned();
-
+            (Some((idx, plugin)), Some((execution_idx, execution))) => {
                 // relaunch plugin if it is done executing or if we're currently executing the wrong plugin
                 if execution.finished() || idx != *execution_idx {
-                    let execution = (plugin.generator)(plugin, self);
+                    let execution = match Self::take_preloaded(&mut self.preloaded, idx) {
+                        Some(execution) => execution,
+                        None => (plugin.generator)(plugin, self)
+                    };
                     self.current = Some((idx, execution));
+                    self.generation += 1;
                 } else if from_user { // send query event
-                    let action = execution.send_query(config(), &remainder);
-                    return (remainder, action);
+                    return execution.send_query(config(), query);
                 }
 
-                (remainder, Action::None)
+                Action::None
             }
             (None, current) => {
                 if current.is_some() { // stop plugin
                     *current = None;
-                } 
+                    self.generation += 1;
+                }
+
+                self.update_keyword_plugins(query);
+                if config().global_search && !query.trim().is_empty() && self.global_plugins.is_empty() {
+                    self.start_global_plugins();
+                }
 
                 if from_user {
                     for (_, execution) in self.default_plugins.iter_mut() {
-                        let action = execution.send_query(config(), input);
+                        let action = execution.send_query(config(), query);
                         match action {
                             Action::None => (),
-                            action => return (input.to_owned(), action)
+                            action => return action
+                        }
+                    }
+
+                    for (_, execution) in self.global_plugins.iter_mut() {
+                        let action = execution.send_query(config(), query);
+                        match action {
+                            Action::None => (),
+                            action => return action
+                        }
+                    }
+
+                    let query = without_keywords(query, |word| self.keyword_plugins.iter().any(|(idx, _)| self.plugins[idx.0].has_keyword(word)));
+                    for (_, execution) in self.keyword_plugins.iter_mut() {
+                        let action = execution.send_query(config(), &query);
+                        match action {
+                            Action::None => (),
+                            action => return action
+                        }
+                    }
+
+                    for (_, execution) in self.fallback_plugins.iter_mut() {
+                        let action = execution.send_query(config(), &query);
+                        match action {
+                            Action::None => (),
+                            action => return action
                         }
                     }
                 }
 
-                (input.to_owned(), Action::None)
+                Action::None
             }
-        };
+        }
+    }
+
+    /// Launches plugins whose keywords appear in the input, and stops those whose keywords were removed
+    fn update_keyword_plugins(&mut self, input: &str) {
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let typed = |plugin: &Plugin| words.iter().any(|word| plugin.has_keyword(word));
+
+        self.keyword_plugins.retain(|(idx, _)| typed(&self.plugins[idx.0]));
+
+        for (index, (_, plugin)) in self.plugins.iter().enumerate() {
+            let index = PluginIndex(index);
+            let already_shown = self.default_plugins.iter().chain(&self.keyword_plugins).chain(&self.global_plugins).any(|(idx, _)| *idx == index);
+
+            if !already_shown && typed(plugin) {
+                self.keyword_plugins.push((index, (plugin.generator)(plugin, self)));
+            }
+        }
+
+        let query = without_keywords(input, |word| self.keyword_plugins.iter().any(|(idx, _)| self.plugins[idx.0].has_keyword(word)));
+        self.keyword_pattern.reparse(&query, CaseMatching::Ignore);
+    }
 
-        (query, action)
+    /// Starts every plugin that isn't shown already, for `global_search`
+    fn start_global_plugins(&mut self) {
+        for (index, (_, plugin)) in self.plugins.iter().enumerate() {
+            let index = PluginIndex(index);
+            let already_shown = self.default_plugins.iter().chain(&self.keyword_plugins).chain(&self.fallback_plugins).any(|(idx, _)| *idx == index);
+
+            if !already_shown {
+                self.global_plugins.push((index, (plugin.generator)(plugin, self)));
+            }
+        }
     }
 
     /// `selected` contains the `plugin_idx` field of a `LabelledEntry`, and the `index` field of an `Entry`
     pub fn launch(&mut self, query: &str, selected: Option<Label>) -> Action {
+        self.launch_with(query, selected, false)
+    }
+
+    /// Launches the secondary action of the selected entry, see [`PluginExecution::send_shift_enter`]
+    pub fn launch_secondary(&mut self, query: &str, selected: Option<Label>) -> Action {
+        self.launch_with(query, selected, true)
+    }
+
+    fn launch_with(&mut self, query: &str, selected: Option<Label>, secondary: bool) -> Action {
         let config = config();
-        if let Some((plug, current)) = &mut self.current {
-            if let Some(Label { index, .. }) = selected {
+        let enter = |execution: &mut Box<dyn PluginExecution>, index: Option<usize>| if secondary {
+            execution.send_shift_enter(config, query, index)
+        } else {
+            execution.send_enter(config, query, index)
+        };
+
+        if selected.is_some_and(|s| s.is_loading() || s.generation != self.generation) {
+            Action::None
+        } else if let Some((plug, current)) = &mut self.current {
+            if let Some(Label { index, .. }) = selected.filter(|_| current.record_usage()) {
                 self.usage.add_use((&self.plugins[plug.0].name, current.get_name(index)));
             }
 
-            current.send_enter(config, query, selected.map(|s| s.index))
+            enter(current, selected.map(|s| s.index))
+        } else if let Some((plugin_index, execution)) = selected.and_then(|s| self.keyword_plugins.iter_mut().chain(&mut self.global_plugins).find(|(idx, _)| *idx == s.plugin_index)) {
+            let index = selected.unwrap().index;
+            if execution.record_usage() {
+                self.usage.add_use((&self.plugins[plugin_index.0].name, execution.get_name(index)));
+            }
+            enter(execution, Some(index))
+        } else if let Some((_, execution)) = selected.and_then(|s| self.fallback_plugins.iter_mut().find(|(idx, _)| *idx == s.plugin_index)) {
+            // fallback entries are synthesized from the query, so they aren't worth recording in usage
+            enter(execution, selected.map(|s| s.index))
         } else if self.default_plugins.len() == 1 {
             let (plugin_index, plug) = &mut self.default_plugins[0];
-            if let Some(Label { index, .. }) = selected {
+            if let Some(Label { index, .. }) = selected.filter(|_| plug.record_usage()) {
                 self.usage.add_use((&self.plugins[plugin_index.0].name, plug.get_name(index)));
             }
-            plug.send_enter(config, query, selected.map(|s| s.index))
-        } else if let Some(Label { plugin_index, index }) = selected {
+            enter(plug, selected.map(|s| s.index))
+        } else if let Some(Label { plugin_index, index, .. }) = selected {
             if let Some((_, execution)) = self.default_plugins.iter_mut().find(|(idx, _)| *idx == plugin_index) {
-                self.usage.add_use((&self.plugins[plugin_index.0].name, execution.get_name(index)));
-                execution.send_enter(config, query, Some(index))
+                if execution.record_usage() {
+                    self.usage.add_use((&self.plugins[plugin_index.0].name, execution.get_name(index)));
+                }
+                enter(execution, Some(index))
             } else { Action::None }
         } else { Action::None }
     }
@@ -227,6 +615,18 @@ impl PluginManager {
     /// kills current running plugin
     pub fn kill(&mut self) {
         self.current = None;
+        self.keyword_plugins.clear();
+        self.global_plugins.clear();
+    }
+
+    /// Stops showing the current plugin, but lets it run in the background until it finishes
+    /// Used instead of forking when keal stays open
+    pub fn detach(&mut self) {
+        if let Some((_, mut execution)) = self.current.take() {
+            std::thread::spawn(move || execution.wait());
+        }
+        self.keyword_plugins.clear();
+        self.global_plugins.clear();
     }
 
     /// gets the plugin reference of the currently running execution
@@ -234,6 +634,11 @@ impl PluginManager {
         self.current.as_ref().map(|(idx, _)| self.plugins.get_index(idx.0).unwrap().1)
     }
 
+    /// gets the plugin an entry comes from
+    pub fn get_plugin(&self, index: PluginIndex) -> &Plugin {
+        &self.plugins[index.0]
+    }
+
     /// wait for the current plugin to finish executing
     pub fn wait(&mut self) {
         if let Some((_, execution)) = &mut self.current {
@@ -241,3 +646,36 @@ impl PluginManager {
         }
     }
 }
+
+/// Keeps at most `limit / k` entries of each of the `k` given plugins, their best ones if they are `sorted`
+/// Plugins with fewer entries than that leave their unused slots to the others
+/// Entries of other plugins are left alone
+fn fair_share(entries: &mut Vec<(ScoreParts, Entry)>, plugins: &[(PluginIndex, bool)], limit: usize) {
+    let mut counts: Vec<_> = plugins.iter()
+        .map(|&(idx, sorted)| (idx, sorted, entries.iter().filter(|(_, e)| e.label.plugin_index == idx).count()))
+        .collect();
+    counts.sort_by_key(|&(_, _, count)| count);
+
+    // the plugins with the fewest entries take theirs first, so that what they leave is split among the rest
+    let mut remaining = limit;
+    let mut keep = vec![true; entries.len()];
+    for (i, &(idx, sorted, count)) in counts.iter().enumerate() {
+        let quota = count.min(remaining / (counts.len() - i));
+        remaining -= quota;
+        if quota == count { continue }
+
+        let mut own: Vec<usize> = (0..entries.len()).filter(|&e| entries[e].1.label.plugin_index == idx).collect();
+        if sorted {
+            own.sort_by(|&a, &b| entries[b].0.total().total_cmp(&entries[a].0.total()));
+        }
+        for &e in &own[quota..] { keep[e] = false }
+    }
+
+    let mut keep = keep.into_iter();
+    entries.retain(|_| keep.next().unwrap());
+}
+
+/// Removes every word matching `is_keyword` from the query
+fn without_keywords(input: &str, is_keyword: impl Fn(&str) -> bool) -> String {
+    input.split_whitespace().filter(|word| !is_keyword(word)).collect::<Vec<_>>().join(" ")
+}
@@ -1,4 +1,4 @@
-use std::process;
+use std::{path::PathBuf, process};
 
 use crate::{ icon::IconPath, config::Config };
 use indexmap::IndexMap;
@@ -16,20 +16,44 @@ pub type PluginGenerator = Box<dyn Fn(&Plugin, &PluginManager) -> Box<dyn Plugin
 pub struct Plugin {
     pub name: String,
     pub icon: Option<IconPath>,
+    /// icon used for entries that don't supply one
+    pub default_icon: Option<IconPath>,
     pub comment: Option<String>,
     pub prefix: String,
+    /// words that include this plugin's results when typed anywhere in a query without a prefix
+    pub keywords: Vec<String>,
+    /// other prefixes that select this plugin, for the synonyms people guess
+    pub aliases: Vec<String>,
+    /// directory the plugin was loaded from, `None` for builtin plugins
+    pub source: Option<PathBuf>,
     pub config: IndexMap<String, String>,
     pub generator: PluginGenerator
 }
 
+impl Plugin {
+    pub fn has_keyword(&self, word: &str) -> bool {
+        self.keywords.iter().any(|keyword| keyword.eq_ignore_ascii_case(word))
+    }
+}
+
 pub trait PluginExecution: Send {
     /// The plugin is done executing
     fn finished(&mut self) -> bool;
     /// Wait for the plugin to finish executing
     fn wait(&mut self);
+    /// The plugin is still producing its initial entries
+    fn loading(&mut self) -> bool { false }
+    /// Wether entries should be sorted by score, instead of being kept in the order the plugin gives them
+    fn sorted(&self) -> bool { true }
+    /// Wether launched entries are recorded in the usage history, which isn't worth it for entries computed from the query
+    fn record_usage(&self) -> bool { true }
 
     fn send_query(&mut self, config: &Config, query: &str) -> Action;
     fn send_enter(&mut self, config: &Config, query: &str, idx: Option<usize>) -> Action;
+    /// Secondary action of an entry, launched with Shift+Enter, which is the same as enter for plugins without one
+    fn send_shift_enter(&mut self, config: &Config, query: &str, idx: Option<usize>) -> Action {
+        self.send_enter(config, query, idx)
+    }
 
     fn get_entries<'a>(&'a self, config: &Config, matcher: &mut Matcher, pattern: &Pattern, out: &mut Vec<Entry<'a>>);
 
@@ -45,6 +69,10 @@ pub enum Action {
     // Universal
     ChangeInput(String),
     ChangeQuery(String),
+    /// shows a message under the input until it changes, like an error in a form, without changing the entries
+    Invalid(String),
+    /// the session handles secrets: the input is wiped, and nothing is remembered until keal closes, see [`crate::sensitive`]
+    Sensitive,
     // Desktop file related
     Exec(ClonableCommand),
     // Dmenu related
@@ -54,6 +82,27 @@ pub enum Action {
     WaitAndClose
 }
 
+/// Copies `text` to the clipboard, and returns the action closing keal
+/// This goes through `wl-copy` or `xclip`, since the clipboard's content would be lost as soon as keal closes otherwise
+/// The text is written to their stdin, as it could be a secret and the arguments of a process are visible to every user
+/// In a sensitive session, the text can only be pasted once, so that it doesn't stay in the clipboard
+pub fn copy_to_clipboard(text: &str) -> Action {
+    let sensitive = crate::sensitive::is_sensitive();
+    let command = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        let mut command = process::Command::new("wl-copy");
+        if sensitive { command.arg("--paste-once"); }
+        command
+    } else {
+        let mut command = process::Command::new("xclip");
+        command.args(["-selection", "clipboard", "-loops", if sensitive { "1" } else { "0" }]);
+        command
+    };
+
+    crate::launch::spawn_with_input(command.into(), text.as_bytes());
+    // the plugin has nothing left to do, so this only closes keal
+    Action::WaitAndClose
+}
+
 #[derive(Debug)]
 pub struct ClonableCommand(pub process::Command);
 
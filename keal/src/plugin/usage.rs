@@ -1,5 +1,6 @@
-use std::{borrow::Borrow, hash::Hash, collections::HashMap, path::PathBuf};
-use serde::{Serialize, Deserialize};
+use std::{borrow::Borrow, hash::Hash, collections::HashMap, path::PathBuf, time::Duration};
+use rusqlite::{Connection, ErrorCode, ffi, params};
+use serde::{Serialize, Deserialize, Deserializer, de};
 
 use crate::log_time;
 
@@ -39,49 +40,241 @@ impl<'a> UsageKey for (&'a str, &'a str) {
     fn b(&self) -> &'a str { self.1 }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
-pub struct Usage(HashMap<(String, String), usize>);
+/// How often and how recently an entry was launched
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Record {
+    pub count: usize,
+    /// unix timestamp in seconds, 0 for records written before it was tracked
+    pub last_used: u64
+}
+
+impl Record {
+    /// Launch count weighted by how recently the entry was last used
+    pub fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_used);
+        let weight = match age {
+            _ if self.last_used == 0 => 0.25,
+            a if a < 3600 => 4.0,
+            a if a < 86400 => 2.0,
+            a if a < 7*86400 => 1.0,
+            a if a < 30*86400 => 0.5,
+            _ => 0.25
+        };
+
+        self.count as f64 * weight
+    }
+}
+
+pub fn now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+type Records = HashMap<(String, String), Record>;
+
+/// Launch statistics of every entry, stored in an SQLite database
+/// Records are also kept in memory, since they are looked up every time entries are sorted
+#[derive(Debug, Default)]
+pub struct Usage {
+    records: Records,
+    /// `None` if the database couldn't be opened, in which case usage is only kept until keal closes
+    db: Option<Connection>
+}
+
+/// Version of the database schema, stored in `PRAGMA user_version`
+const SCHEMA_VERSION: i32 = 1;
 
 impl Usage {
-    /// Gets the canonical file path to the usage file
+    /// Gets the canonical path to a file in the state directory
     /// NOTE: this creates the state directory if it doesn't exist!
-    fn file_path() -> PathBuf {
+    fn file_path(name: &str) -> PathBuf {
         use crate::xdg_utils::state_dir;
         let mut path = state_dir().unwrap();
         let _ = std::fs::create_dir_all(&path);
 
-        path.push("usage.cbor");
+        path.push(name);
         path
     }
 
     pub fn load() -> Self {
         log_time("loading usage");
-        let usage = Usage::file_path();
-        if let Ok(file) = std::fs::File::open(&usage) {
-            serde_cbor::from_reader(file).unwrap_or_else(|_| {
-                // assume corrupted file and delete it if you can't read it
-                let _ = std::fs::remove_file(&usage);
+        let opened = match Usage::open() {
+            Err(e) if is_corruption(&e) => {
+                // keep the damaged database around, so that it can still be salvaged by hand
+                eprintln!("usage database is corrupted ({e}), moving it to `usage.db.corrupt` and starting over");
+                for suffix in ["", "-wal", "-shm"] {
+                    let _ = std::fs::rename(Usage::file_path(&format!("usage.db{suffix}")), Usage::file_path(&format!("usage.db.corrupt{suffix}")));
+                }
+                Usage::open()
+            }
+            opened => opened
+        };
+
+        match opened {
+            Ok((db, records)) => Usage { records, db: Some(db) },
+            Err(e) => {
+                eprintln!("failed to open usage database: {e}");
                 Usage::default()
-            })
-        } else { Usage::default() }
+            }
+        }
     }
 
-    #[inline(always)]
-    pub fn get(&self, k: (&str, &str)) -> Option<&usize> {
-        self.0.get(&k as &dyn UsageKey)
+    /// Opens the database, creating it or updating its schema if needed, and reads every record
+    fn open() -> rusqlite::Result<(Connection, Records)> {
+        let mut db = Connection::open(Usage::file_path("usage.db"))?;
+        // other keal instances may be writing at the same time
+        db.busy_timeout(Duration::from_secs(2))?;
+        db.pragma_update(None, "journal_mode", "WAL")?;
+
+        // a database that was damaged outside of sqlite (by a full disk, or a bad copy) can still open fine
+        let check: String = db.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+        if check != "ok" {
+            return Err(rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_CORRUPT), Some(check)))
+        }
+
+        let version: i32 = db.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version < SCHEMA_VERSION {
+            let transaction = db.transaction()?;
+            transaction.execute_batch("
+                CREATE TABLE IF NOT EXISTS usage (
+                    plugin TEXT NOT NULL,
+                    entry TEXT NOT NULL,
+                    count INTEGER NOT NULL,
+                    last_used INTEGER NOT NULL,
+                    PRIMARY KEY (plugin, entry)
+                );
+            ")?;
+
+            let (legacy, complete) = Usage::read_legacy();
+            for ((plugin, entry), record) in legacy {
+                transaction.execute(
+                    "INSERT OR IGNORE INTO usage VALUES (?1, ?2, ?3, ?4)",
+                    params![plugin, entry, record.count as i64, record.last_used as i64]
+                )?;
+            }
+
+            transaction.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            transaction.commit()?;
+
+            // only remove the old file once its records are safely in the database
+            if complete {
+                let _ = std::fs::remove_file(Usage::file_path("usage.cbor"));
+            } else {
+                let _ = std::fs::rename(Usage::file_path("usage.cbor"), Usage::file_path("usage.cbor.corrupt"));
+            }
+        }
+
+        let records = db.prepare("SELECT plugin, entry, count, last_used FROM usage")?
+            .query_map([], |row| Ok((
+                (row.get(0)?, row.get(1)?),
+                Record { count: row.get::<_, i64>(2)? as usize, last_used: row.get::<_, i64>(3)? as u64 }
+            )))?
+            .collect::<Result<_, _>>()?;
+
+        Ok((db, records))
+    }
+
+    /// Reads records from the CBOR file used before the database
+    /// Files that were truncated when keal was killed mid-write are read up to the damaged record,
+    /// in which case the returned boolean is false
+    fn read_legacy() -> (Records, bool) {
+        let Ok(data) = std::fs::read(Usage::file_path("usage.cbor")) else { return (HashMap::new(), true) };
+
+        let (records, complete) = read_partial_map::<Record>(&data);
+        if complete { return (records, true) }
+
+        // the oldest files only stored launch counts
+        let (counts, counts_complete) = read_partial_map::<usize>(&data);
+        if counts_complete || counts.len() > records.len() {
+            let records = counts.into_iter().map(|(k, count)| (k, Record { count, last_used: 0 })).collect();
+            if !counts_complete { eprintln!("usage file is corrupted, recovered what could be read") }
+            (records, counts_complete)
+        } else {
+            eprintln!("usage file is corrupted, recovered what could be read");
+            (records, false)
+        }
+    }
+
+    /// Gets the frecency of a given entry, 0 if it was never used
+    pub fn frecency(&self, k: (&str, &str), now: u64) -> f64 {
+        self.records.get(&k as &dyn UsageKey).map(|r| r.frecency(now)).unwrap_or(0.0)
+    }
+
+    /// Iterates over every record, as `((plugin name, entry name), record)`
+    pub fn records(&self) -> impl Iterator<Item = ((&str, &str), &Record)> {
+        self.records.iter().map(|((a, b), r)| ((a.as_str(), b.as_str()), r))
     }
 
     /// Adds one use to a given entry (and saves it to disk)
     /// If it doesn't exist, this inserts it and sets its count to 1 (by cloning the input `&str`)
+    /// The count is taken from the database, to keep uses recorded by other keal instances
+    /// Nothing is recorded in a sensitive session
     pub fn add_use(&mut self, k: (&str, &str)) {
-        if let Some(v) = self.0.get_mut(&k as &dyn UsageKey) {
-            *v += 1;
+        if crate::sensitive::is_sensitive() { return }
+
+        let last_used = now();
+        let stored = self.db.as_ref().map(|db| db.query_row(
+            "INSERT INTO usage VALUES (?1, ?2, 1, ?3)
+            ON CONFLICT (plugin, entry) DO UPDATE SET count = count + 1, last_used = ?3
+            RETURNING count",
+            params![k.0, k.1, last_used as i64],
+            |row| row.get::<_, i64>(0)
+        ));
+
+        let count = match stored {
+            Some(Ok(count)) => Some(count as usize),
+            Some(Err(e)) => {
+                eprintln!("failed to save usage: {e}");
+                None
+            }
+            None => None
+        };
+
+        if let Some(v) = self.records.get_mut(&k as &dyn UsageKey) {
+            v.count = count.unwrap_or(v.count + 1);
+            v.last_used = last_used;
         } else {
-            self.0.insert((k.0.to_owned(), k.1.to_owned()), 1);
+            self.records.insert((k.0.to_owned(), k.1.to_owned()), Record { count: count.unwrap_or(1), last_used });
+        }
+    }
+
+    /// Removes the record of a given entry (and saves it to disk)
+    pub fn forget(&mut self, k: (&str, &str)) {
+        self.records.remove(&k as &dyn UsageKey);
+
+        if let Some(db) = &self.db {
+            if let Err(e) = db.execute("DELETE FROM usage WHERE plugin = ?1 AND entry = ?2", params![k.0, k.1]) {
+                eprintln!("failed to forget usage: {e}");
+            }
+        }
+    }
+}
+
+fn is_corruption(e: &rusqlite::Error) -> bool {
+    matches!(e.sqlite_error_code(), Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase))
+}
+
+/// Reads the entries of a CBOR map until the first one that can't be read
+/// Returns wether the whole map was read
+fn read_partial_map<'de, V: Deserialize<'de>>(data: &'de [u8]) -> (HashMap<(String, String), V>, bool) {
+    struct Visitor<'a, V>(&'a mut HashMap<(String, String), V>);
+
+    impl<'de, V: Deserialize<'de>> de::Visitor<'de> for Visitor<'_, V> {
+        type Value = ();
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a map of usage records")
         }
 
-        let usage = Usage::file_path();
-        let file = std::fs::File::create(usage).expect("failed to write to usage file");
-        let _ = serde_cbor::to_writer(file, self);
+        fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+            while let Some((k, v)) = map.next_entry()? {
+                self.0.insert(k, v);
+            }
+            Ok(())
+        }
     }
+
+    let mut out = HashMap::new();
+    let mut deserializer = serde_cbor::Deserializer::from_slice(data);
+    let complete = deserializer.deserialize_map(Visitor(&mut out)).and_then(|_| deserializer.end()).is_ok();
+    (out, complete)
 }
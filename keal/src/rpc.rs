@@ -0,0 +1,168 @@
+use std::{io::{BufRead, Write}, process::Stdio, sync::mpsc::{channel, RecvTimeoutError}, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{engine::Engine, headless::{json_entry, JsonEntry, NoTheme}, launch, plugin::Action};
+
+/// Message sent by the external UI, one JSON object per line on stdin
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    /// the input changed, `prefix` being the prefix of the selected plugin, if any
+    UpdateInput {
+        prefix: Option<String>,
+        query: String,
+        /// should be false when the change comes from a `change_input` or `change_query` action
+        #[serde(default = "default_from_user")]
+        from_user: bool
+    },
+    /// launch the entry at the given index of the last entry list, or launch the query itself if there is none
+    /// with `secondary`, the entry's secondary action is launched instead, like with Shift+Enter
+    Launch {
+        index: Option<usize>,
+        #[serde(default)]
+        secondary: bool
+    }
+}
+
+fn default_from_user() -> bool { true }
+
+/// Message sent to the external UI, one JSON object per line on stdout
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Response<'a> {
+    /// plugins are loaded, and requests can be sent
+    Ready { prefixes: Vec<&'a str> },
+    /// new list of entries, sent after every input change, and regularly while a plugin is `loading`
+    Entries { entries: Vec<JsonEntry<'a>>, loading: bool },
+    Action(JsonAction),
+    Error { message: String }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "action", content = "value", rename_all = "snake_case")]
+enum JsonAction {
+    None,
+    /// set the whole input, clearing the selected plugin
+    ChangeInput(String),
+    /// set the query, keeping the selected plugin
+    ChangeQuery(String),
+    /// show the message under the input until it changes
+    Invalid(String),
+    /// wipe the input, and don't keep anything typed or copied until keal closes
+    Sensitive,
+    PrintAndClose(String),
+    /// the UI should close, keal exits once the launched application or plugin doesn't need it anymore
+    Close
+}
+
+fn send(response: &Response) {
+    let mut stdout = std::io::stdout().lock();
+    match serde_json::to_string(response) {
+        Ok(line) => {
+            let _ = writeln!(stdout, "{line}");
+            let _ = stdout.flush();
+        }
+        Err(e) => eprintln!("failed to serialize rpc response: {e}")
+    }
+}
+
+/// Serves the plugin manager over stdio using newline-delimited JSON, so that other programs can be used as keal's UI
+pub fn run() -> anyhow::Result<()> {
+    let mut engine = Engine::builder().build(&mut NoTheme);
+
+    send(&Response::Ready { prefixes: engine.prefixes().collect() });
+
+    let (sender, reciever) = channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() { continue }
+
+            if sender.send(serde_json::from_str::<Request>(&line)).is_err() { break }
+        }
+    });
+
+    loop {
+        // while a plugin is loading, refresh entries regularly to animate the placeholder and send the results once they are ready
+        let request = if engine.loading() {
+            match reciever.recv_timeout(Duration::from_millis(100)) {
+                Ok(request) => Some(request),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break
+            }
+        } else {
+            let Ok(request) = reciever.recv() else { break };
+            Some(request)
+        };
+
+        let action = match request {
+            None => {
+                engine.refresh();
+                None
+            }
+            Some(Err(e)) => {
+                send(&Response::Error { message: format!("invalid request: {e}") });
+                continue
+            }
+            Some(Ok(Request::UpdateInput { prefix, query, from_user })) => {
+                Some(engine.update_input(prefix.as_deref(), &query, from_user))
+            }
+            Some(Ok(Request::Launch { index, secondary })) => {
+                let action = match index {
+                    Some(index) if secondary => engine.launch_secondary(index),
+                    index => engine.launch(index)
+                };
+                match action {
+                    Some(action) => {
+                        // launching can change the plugin's entries
+                        engine.refresh();
+                        Some(action)
+                    }
+                    None => {
+                        send(&Response::Error { message: format!("no entry at index {}", index.unwrap_or_default()) });
+                        continue
+                    }
+                }
+            }
+        };
+
+        send(&Response::Entries {
+            entries: engine.entries().iter().map(|entry| json_entry(&engine, entry)).collect(),
+            loading: engine.loading()
+        });
+
+        let Some(action) = action else { continue };
+        let action = match action {
+            Action::None => JsonAction::None,
+            Action::ChangeInput(input) => {
+                engine.kill();
+                JsonAction::ChangeInput(input)
+            }
+            Action::ChangeQuery(query) => JsonAction::ChangeQuery(query),
+            Action::Invalid(message) => JsonAction::Invalid(message),
+            Action::Sensitive => JsonAction::Sensitive,
+            Action::PrintAndClose(message) => {
+                send(&Response::Action(JsonAction::PrintAndClose(message)));
+                break
+            }
+            Action::Exec(mut command) => {
+                // spawned instead of exec'd, and without the UI's pipes, which the application would read from and write to
+                // its errors still go to keal's stderr, which isn't part of the protocol
+                command.0.stdin(Stdio::null()).stdout(Stdio::null());
+                launch::spawn_detached(command);
+                send(&Response::Action(JsonAction::Close));
+                break
+            }
+            Action::Fork | Action::WaitAndClose => {
+                // the UI can close right away, but keal has to stay alive for the plugin to keep running
+                send(&Response::Action(JsonAction::Close));
+                engine.wait();
+                break
+            }
+        };
+        send(&Response::Action(action));
+    }
+
+    Ok(())
+}
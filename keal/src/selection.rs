@@ -0,0 +1,56 @@
+/// Keyboard selection and mouse hover of the entry list
+///
+/// Moving the selection with the keyboard hides the hover until the mouse moves again,
+/// so that a row under a still cursor doesn't get highlighted while navigating.
+#[derive(Debug, Default)]
+pub struct Selection {
+    /// index of the entry selected with the keyboard
+    selected: usize,
+    /// index of the entry under the mouse
+    hovered: Option<usize>,
+    /// set by keyboard motion, cleared when the mouse moves
+    hover_suppressed: bool
+}
+
+impl Selection {
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Selects the given entry with the keyboard
+    pub fn select(&mut self, index: usize) {
+        self.selected = index;
+        self.hovered = None;
+        self.hover_suppressed = true;
+    }
+
+    /// Selects the next entry, stopping at the last of `len` entries
+    pub fn select_next(&mut self, len: usize) {
+        self.select((self.selected + 1).min(len.saturating_sub(1)));
+    }
+
+    pub fn select_previous(&mut self) {
+        self.select(self.selected.saturating_sub(1));
+    }
+
+    /// Lets entries be hovered again after keyboard navigation
+    pub fn mouse_moved(&mut self) {
+        self.hover_suppressed = false;
+    }
+
+    /// Forgets the hovered entry, call this before finding which entry is under the mouse
+    pub fn clear_hover(&mut self) {
+        self.hovered = None;
+    }
+
+    /// Marks the entry under the mouse, ignored while keyboard navigation suppresses hover
+    pub fn hover(&mut self, index: usize) {
+        if !self.hover_suppressed {
+            self.hovered = Some(index);
+        }
+    }
+
+    pub fn hovered(&self) -> Option<usize> {
+        self.hovered
+    }
+}
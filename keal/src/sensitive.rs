@@ -0,0 +1,33 @@
+//! Sensitive sessions, started by plugins handling secrets (like a password manager) with `action:sensitive`
+//! Until keal closes, what is typed isn't recorded anywhere, and copied text doesn't stay in the clipboard
+
+use std::sync::atomic::{compiler_fence, AtomicBool, Ordering};
+
+static SENSITIVE: AtomicBool = AtomicBool::new(false);
+
+/// Marks the rest of the session as sensitive, this can't be undone
+pub fn start() {
+    SENSITIVE.store(true, Ordering::Relaxed);
+}
+
+/// Wether a plugin marked the session as sensitive
+/// Usage and undo history aren't recorded, and the primary selection isn't set
+pub fn is_sensitive() -> bool {
+    SENSITIVE.load(Ordering::Relaxed)
+}
+
+/// Overwrites the whole buffer of the string with zeroes and empties it, so that its content doesn't linger in memory once freed
+/// Copies made by earlier reallocations can't be reached, and are left as is
+pub fn zeroize(text: &mut String) {
+    // SAFETY: the whole capacity is allocated, and an empty string is valid UTF-8
+    unsafe {
+        let bytes = text.as_mut_vec();
+        let ptr = bytes.as_mut_ptr();
+        for i in 0..bytes.capacity() {
+            // volatile, so that the writes aren't optimized away as the string is about to be freed
+            ptr.add(i).write_volatile(0);
+        }
+        bytes.set_len(0);
+    }
+    compiler_fence(Ordering::SeqCst);
+}
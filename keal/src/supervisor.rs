@@ -0,0 +1,109 @@
+//! Cleans up after a frontend panics, so that nothing keal started keeps running without it
+
+use std::{cell::Cell, io::Write, ops::{Deref, DerefMut}, sync::{atomic::{AtomicBool, Ordering}, Mutex, MutexGuard}, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::{plugin::PluginManager, xdg_utils::state_dir};
+
+/// Processes of the user plugins that are running
+static PLUGINS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+/// Set in the copy of keal made by [`crate::launch::fork_detached`], which isn't the parent of the plugins it keeps running
+static FORKED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// How many [`ManagerLock`]s the current thread holds
+    static MANAGING: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Installs the panic hook, call this before starting the frontend
+/// Panics are logged to `~/.local/state/keal/keal.log`, and a panic of the main thread, or of a thread using the plugin manager
+/// through a [`ManagerLock`], kills the running plugins, since they are never dropped then, which would leave them running
+/// (like after an [`crate::plugin::Action::Fork`])
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let thread = std::thread::current();
+        let thread = thread.name().unwrap_or("unnamed");
+        if let Err(e) = log_panic(&format!("thread `{thread}` {info}")) {
+            eprintln!("failed to log panic: {e}");
+        }
+
+        let managing = MANAGING.try_with(|managing| managing.get() > 0).unwrap_or(false);
+        if thread == "main" || managing {
+            kill_plugins();
+        }
+    }));
+}
+
+fn log_panic(message: &str) -> std::io::Result<()> {
+    let dir = state_dir().map_err(std::io::Error::other)?;
+    std::fs::create_dir_all(&dir)?;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut file = std::fs::OpenOptions::new().append(true).create(true).open(dir.join("keal.log"))?;
+    writeln!(file, "[{now}] keal {} (pid {}): {message}", env!("CARGO_PKG_VERSION"), std::process::id())
+}
+
+fn kill_plugins() {
+    let plugins = PLUGINS.lock().unwrap_or_else(|e| e.into_inner());
+    for &pid in plugins.iter() {
+        let pid = pid as libc::pid_t;
+        // only kill processes that are still our children, since the pid of one that exited may have been reused
+        // a forked copy isn't their parent, so it can only check that they still exist
+        // SAFETY: waitpid and kill don't touch memory
+        unsafe {
+            let running = match libc::waitpid(pid, std::ptr::null_mut(), libc::WNOHANG) {
+                0 => true,
+                -1 => FORKED.load(Ordering::Relaxed) && libc::kill(pid, 0) == 0,
+                _ => false
+            };
+            if running {
+                libc::kill(pid, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// The plugin manager, locked by a frontend: a panic while it is held kills the plugins, whichever thread it happens in
+/// Frontends drive the manager from their own threads (or from an executor's), which are otherwise left out by [`install`]
+pub struct ManagerLock<'a>(MutexGuard<'a, PluginManager>);
+
+impl<'a> ManagerLock<'a> {
+    pub fn new(manager: &'a Mutex<PluginManager>) -> Self {
+        let guard = manager.lock().unwrap();
+        MANAGING.with(|managing| managing.set(managing.get() + 1));
+        Self(guard)
+    }
+}
+
+impl Deref for ManagerLock<'_> {
+    type Target = PluginManager;
+    fn deref(&self) -> &PluginManager { &self.0 }
+}
+
+impl DerefMut for ManagerLock<'_> {
+    fn deref_mut(&mut self) -> &mut PluginManager { &mut self.0 }
+}
+
+impl Drop for ManagerLock<'_> {
+    fn drop(&mut self) {
+        let _ = MANAGING.try_with(|managing| managing.set(managing.get() - 1));
+    }
+}
+
+/// Marks this process as the copy made by [`crate::launch::fork_detached`]
+pub fn set_forked() {
+    FORKED.store(true, Ordering::Relaxed);
+}
+
+/// Remembers a plugin process, to kill it if keal panics
+pub fn watch_plugin(pid: u32) {
+    PLUGINS.lock().unwrap_or_else(|e| e.into_inner()).push(pid);
+}
+
+/// Forgets a plugin process, once it was stopped
+pub fn forget_plugin(pid: u32) {
+    PLUGINS.lock().unwrap_or_else(|e| e.into_inner()).retain(|&p| p != pid);
+}
@@ -44,3 +44,33 @@ pub fn state_dir() -> Result<PathBuf, &'static str> {
 
     Ok(dir)
 }
+
+/// Returns the path equivalent to `~/.cache` (not specific to keal, since the cache directory is shared with other applications)
+pub fn cache_home() -> Result<PathBuf, &'static str> {
+    if let Some(cache) = std::env::var_os("XDG_CACHE_HOME") {
+        Ok(PathBuf::from(cache))
+    } else if let Some(home) = std::env::var_os("HOME") {
+        Ok(Path::new(&home).join(".cache"))
+    } else {
+        Err("neither $XDG_CACHE_HOME nor $HOME are defined")
+    }
+}
+
+/// Returns the path equivalent to `~/.local/share` (not specific to keal)
+pub fn data_home() -> Result<PathBuf, &'static str> {
+    if let Some(data) = std::env::var_os("XDG_DATA_HOME") {
+        Ok(PathBuf::from(data))
+    } else if let Some(home) = std::env::var_os("HOME") {
+        Ok(Path::new(&home).join(".local/share"))
+    } else {
+        Err("neither $XDG_DATA_HOME nor $HOME are defined")
+    }
+}
+
+/// Expands a leading `~` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(path)
+    }
+}
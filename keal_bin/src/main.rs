@@ -0,0 +1,59 @@
+use keal::{arguments::{self, arguments, Arguments}, config::read_frontend, start_log_time};
+
+/// Frontends compiled in, enabled with cargo features
+const FRONTENDS: &[&str] = &[
+    #[cfg(feature = "iced")] "iced",
+    #[cfg(feature = "piet")] "piet",
+    #[cfg(feature = "raylib")] "raylib",
+];
+
+fn main() -> anyhow::Result<()> {
+    start_log_time();
+    keal::supervisor::install();
+    match Arguments::init() {
+        Ok(_) => (),
+        Err(arguments::Error::Exit) => return Ok(()),
+        Err(e) => anyhow::bail!("error: {e}")
+    };
+
+    if arguments().install_service {
+        let path = keal::install::install_desktop_entry()?;
+        println!("wrote desktop entry to `{}`", path.display());
+        return Ok(())
+    }
+    if let Some(input) = &arguments().headless {
+        return keal::headless::run(input, arguments().json)
+    }
+    if arguments().rpc {
+        return keal::rpc::run()
+    }
+
+    let frontend = arguments().frontend.clone().unwrap_or_else(read_frontend);
+
+    // fall back on the only frontend there is, so that a single-frontend build works whatever the config says
+    let frontend = match (frontend.as_str(), FRONTENDS) {
+        (name, _) if FRONTENDS.contains(&name) => name,
+        (name, &[only]) => {
+            eprintln!("frontend `{name}` wasn't compiled in, using `{only}` instead");
+            only
+        }
+        (name, _) => anyhow::bail!("error: unknown frontend `{name}`, available frontends are: {}", FRONTENDS.join(", "))
+    };
+
+    let result: anyhow::Result<()> = match frontend {
+        #[cfg(feature = "iced")]
+        "iced" => keal_iced::run(),
+        #[cfg(feature = "piet")]
+        "piet" => {
+            keal_piet::run();
+            Ok(())
+        }
+        #[cfg(feature = "raylib")]
+        "raylib" => keal_raylib::run(),
+        _ => unreachable!("frontend was checked to be compiled in")
+    };
+    result?;
+
+    keal::exit_on_error_code();
+    Ok(())
+}
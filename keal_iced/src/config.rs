@@ -10,6 +10,10 @@ pub struct Theme {
     pub background: Color,
 
     pub input_placeholder: Color,
+    /// color of the prompt label, see [`keal::arguments::Arguments::prompt`]
+    pub input_prompt: Color,
+    /// border of the input and message under it, when a plugin says the query is invalid
+    pub input_error: Color,
     pub input_selection: Color,
     pub input_background: Color,
 
@@ -35,7 +39,7 @@ impl FrontendConfig for Theme {
     fn add_field(&mut self, field: (String, String)) {
         parse_fields!(self, field, (
             background,
-            input_placeholder, input_selection, input_background,
+            input_placeholder, input_prompt, input_error, input_selection, input_background,
             text, matched_text, selected_matched_text, comment,
             choice_background, selected_choice_background, hovered_choice_background, pressed_choice_background,
             scrollbar_enabled, scrollbar, hovered_scrollbar, scrollbar_border_radius
@@ -0,0 +1,73 @@
+#![allow(non_snake_case)]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use keal::{config::{WindowLevel, WindowPosition}, log_time};
+use iced::{window, Font};
+use ui::{Keal, WindowFit};
+
+mod ui;
+mod config;
+
+/// logical width of the monitor the window opened on, as bits of an `f32`, recorded when placing a bar
+/// iced has no way to ask for the monitor's size, but gives it to `Position::SpecificWith` when the window is created
+static MONITOR_WIDTH: AtomicU32 = AtomicU32::new(0);
+
+/// The width of the monitor a bar opened on, `None` if it wasn't known when the window was created
+fn monitor_width() -> Option<f32> {
+    let width = f32::from_bits(MONITOR_WIDTH.load(Ordering::Relaxed));
+    (width > 0.0).then_some(width)
+}
+
+fn top_bar(_: iced::Size, monitor: iced::Size) -> iced::Point {
+    MONITOR_WIDTH.store(monitor.width.to_bits(), Ordering::Relaxed);
+    iced::Point::ORIGIN
+}
+
+fn bottom_bar(window: iced::Size, monitor: iced::Size) -> iced::Point {
+    MONITOR_WIDTH.store(monitor.width.to_bits(), Ordering::Relaxed);
+    iced::Point::new(0.0, monitor.height - window.height)
+}
+
+/// Runs the iced frontend
+/// Arguments should have been initialized beforehand
+pub fn run() -> anyhow::Result<()> {
+    let mut theme = config::Theme::default();
+    let config = keal::config::Config::init_launcher(&mut theme);
+
+    log_time("read config");
+
+    // iced gives text a line height of 1.3 times its size
+    let input_height = config.font_size*1.25*1.3 + 2.0*config.font_size;
+    let row_height = (config.font_size*1.3).max(config.icon_size + 8.0) + 2.0*config.density.row_padding();
+    let (width, height) = keal::arguments::arguments().window_size((1920.0/3.0, 1080.0/2.0), input_height, row_height, None);
+
+    iced::application("Keal", Keal::update, Keal::view)
+        .theme(Keal::theme)
+        .subscription(Keal::subscription)
+        .settings(iced::Settings {
+            fonts: vec![include_bytes!("../../public/iosevka-regular.ttf").as_slice().into()],
+            default_font: Font::with_name("Iosevka"),
+            ..Default::default()
+        })
+        .window(window::Settings {
+            size: iced::Size::new(width, height),
+            position: match config.position {
+                WindowPosition::Center => window::Position::Centered,
+                WindowPosition::TopBar => window::Position::SpecificWith(top_bar),
+                WindowPosition::BottomBar => window::Position::SpecificWith(bottom_bar)
+            },
+            resizable: false,
+            decorations: false,
+            transparent: true,
+            level: match config.window_level {
+                WindowLevel::Normal => window::Level::Normal,
+                // iced can't get the keyboard without the window manager, so it can't bypass it to stay above fullscreen windows
+                WindowLevel::OnTop | WindowLevel::AboveFullscreen => window::Level::AlwaysOnTop
+            },
+            ..Default::default()
+        })
+        .run_with(move || Keal::new(theme, WindowFit { width, max_height: height, input_height, row_height }))?;
+
+    Ok(())
+}
@@ -1,19 +1,27 @@
-use std::sync::{Mutex, Arc, MutexGuard};
+use std::{sync::{Mutex, Arc, MutexGuard, OnceLock}, time::Duration};
 use iced::futures::{channel::mpsc, SinkExt, Stream, StreamExt};
 
 use nucleo_matcher::{Matcher, pattern::Pattern};
 
-use keal::{plugin::{PluginManager, entry::Label}, log_time};
+use keal::{plugin::{PluginManager, entry::{parse_pattern, Label}}, supervisor::ManagerLock, log_time};
 
 use super::Message;
 
 pub enum Event {
-    UpdateInput(String, bool),
-    Launch(Option<Label>)
+    /// plugin prefix, query, and wether the change comes from the user
+    UpdateInput(Option<String>, String, bool),
+    /// selected entry, and wether its secondary action is launched (with Shift+Enter)
+    Launch(Option<Label>, bool),
+    /// regenerate entries without changing the input, used while plugins are loading
+    Refresh,
+    /// show the next page of entries, see [`PluginManager::next_page`]
+    NextPage
 }
 
 pub struct AsyncManager {
     manager: Arc<Mutex<PluginManager>>,
+    /// prefixes and aliases of every plugin, known once plugins are loaded
+    prefixes: Arc<OnceLock<Vec<String>>>,
 
     // data used to regenerate entries
     data: Arc<Mutex<Data>>,
@@ -30,6 +38,7 @@ pub struct Data {
 impl AsyncManager {
     pub fn subscription(&self) -> impl Stream<Item = super::Message> {
         let manager = self.manager.clone();
+        let prefixes = self.prefixes.clone();
 
         let data = self.data.clone();
         let num_entries = self.num_entries;
@@ -38,41 +47,99 @@ impl AsyncManager {
         iced::stream::channel(50, move |mut output| async move {
             {
                 log_time("locking sync manager");
-                let mut manager = manager.lock().unwrap();
+                let mut manager = ManagerLock::new(&manager);
 
                 log_time("loading plugins");
                 manager.load_plugins();
+                let _ = prefixes.set(manager.prefixes().map(str::to_owned).collect());
             }
 
             let (sender, mut reciever) = mpsc::channel(50);
-            output.send(Message::SenderLoaded(sender)).await.unwrap();
-
+            output.send(Message::SenderLoaded(sender.clone())).await.unwrap();
+
+            // while a plugin is loading, refresh entries regularly to animate the placeholder and show the results once they are ready
+            // only one refresh is scheduled at a time, so that typing doesn't start multiple refresh loops
+            let mut refresh_scheduled = false;
+            let schedule_refresh = |loading: bool, refresh_scheduled: &mut bool| if loading && !*refresh_scheduled {
+                *refresh_scheduled = true;
+                let mut sender = sender.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(100));
+                    let _ = sender.try_send(Event::Refresh);
+                });
+            };
+
+            // event received while checking for newer queries, handled next
+            let mut pending = None;
             loop {
-                let event = reciever.select_next_some().await;
+                let event = match pending.take() {
+                    Some(event) => event,
+                    None => reciever.select_next_some().await
+                };
 
                 match event {
-                    Event::UpdateInput(s, from_user) => {
-                        let (entries, action) = {
-                            let mut manager = manager.lock().unwrap();
-                            let (new_query, action) = manager.update_input(&s, from_user);
+                    Event::UpdateInput(prefix, query, from_user) => {
+                        let (entries, action, accepted) = {
+                            let mut manager = ManagerLock::new(&manager);
+                            let action = manager.update_input(prefix.as_deref(), &query, from_user);
+                            schedule_refresh(manager.update_loading(), &mut refresh_scheduled);
 
                             let data = &mut *data.lock().unwrap();
-                            data.pattern.reparse(&new_query, nucleo_matcher::pattern::CaseMatching::Ignore);
-                            data.query = new_query;
-
-                            let entries = manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage);
-                            (entries, action)
+                            parse_pattern(&mut data.pattern, &query);
+                            data.query = query;
+
+                            // when typing faster than entries are matched, only the latest query gets matched, instead of queuing a full pass per keystroke
+                            pending = reciever.try_next().ok().flatten();
+                            let entries = match pending {
+                                Some(Event::UpdateInput(..)) => None,
+                                _ => Some(manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage))
+                            };
+                            let accepted = entries.as_ref().filter(|_| from_user).and_then(|page| manager.auto_accept(&data.query, &page.entries));
+                            (entries, action, accepted)
                         };
 
-                        output.send(Message::Entries(entries)).await.unwrap();
+                        if let Some(entries) = entries {
+                            output.send(Message::Entries(entries)).await.unwrap();
+                        }
                         output.send(Message::Action(action)).await.unwrap();
+                        if let Some(label) = accepted {
+                            output.send(Message::Launch(Some(label))).await.unwrap();
+                        }
+                    }
+                    Event::Refresh => {
+                        let entries = {
+                            let mut manager = ManagerLock::new(&manager);
+                            refresh_scheduled = false;
+                            schedule_refresh(manager.update_loading(), &mut refresh_scheduled);
+
+                            let data = &mut *data.lock().unwrap();
+                            manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage)
+                        };
+
+                        output.send(Message::Refreshed(entries)).await.unwrap();
+                    }
+                    Event::NextPage => {
+                        let entries = {
+                            let mut manager = ManagerLock::new(&manager);
+                            manager.next_page();
+
+                            let data = &mut *data.lock().unwrap();
+                            manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage)
+                        };
+
+                        output.send(Message::Refreshed(entries)).await.unwrap();
                     }
-                    Event::Launch(label) => {
-                        let action = {
-                            let mut manager = manager.lock().unwrap();
-                            let data = data.lock().unwrap();
-                            manager.launch(&data.query, label)
+                    Event::Launch(label, secondary) => {
+                        let (entries, action) = {
+                            let mut manager = ManagerLock::new(&manager);
+                            let data = &mut *data.lock().unwrap();
+                            let action = if secondary { manager.launch_secondary(&data.query, label) } else { manager.launch(&data.query, label) };
+
+                            // the plugin may have updated its entries
+                            let entries = manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage);
+                            (entries, action)
                         };
+                        output.send(Message::Refreshed(entries)).await.unwrap();
                         output.send(Message::Action(action)).await.unwrap();
                     }
                 }
@@ -83,6 +150,7 @@ impl AsyncManager {
     pub fn new(matcher: Matcher, num_entries: usize, sort_by_usage: bool) -> Self {
         Self {
             manager: Default::default(),
+            prefixes: Default::default(),
             data: Arc::new(Mutex::new(Data {
                 matcher,
                 query: String::default(),
@@ -92,19 +160,20 @@ impl AsyncManager {
         }
     }
 
+    /// Splits a plugin prefix typed at the start of the input, like `app firefox` into `app` and `firefox`
+    /// Always returns `None` until plugins are loaded
+    pub fn split_prefix<'a>(&self, input: &'a str) -> Option<(&'a str, &'a str)> {
+        let (prefix, query) = input.split_once(' ')?;
+        self.prefixes.get()?.iter().any(|p| p == prefix).then_some((prefix, query))
+    }
+
     /// Use the plugin manager mutably and synchronously
     /// WARN: This may change plugin entries! Make sure to send an event to regenerate them in the UI if it does!
     pub fn with_manager<T>(&mut self, mut f: impl FnMut(&mut PluginManager) -> T) -> T {
-        let mut manager = self.manager.lock().unwrap();
+        let mut manager = ManagerLock::new(&self.manager);
         f(&mut manager)
     }
 
-    /// Use the plugin manager immutably and synchronously
-    pub fn use_manager<T>(&self, mut f: impl FnMut(&PluginManager) -> T) -> T {
-        let manager = self.manager.lock().unwrap();
-        f(&manager)
-    }
-
     /// Use synced data for pattern matching
     /// WARN: Trying to use this data at the same time as the plugin manager is very likely to cause a deadlock!
     pub fn get_data(&self) -> MutexGuard<Data> { self.data.lock().unwrap() }
@@ -1,18 +1,16 @@
-use std::os::unix::process::CommandExt;
+use std::{collections::HashMap, ops::Range, path::PathBuf, sync::Arc};
 
-use fork::{fork, Fork};
-use iced::{futures::channel::mpsc, keyboard::{self, key::{Key, Named}, Modifiers}, widget::{button, column as icolumn, container, image, row as irow, scrollable, svg, text, text_input, Space}, Element, Length, Padding, Subscription, Task};
+use iced::{event, futures::channel::{mpsc, oneshot}, keyboard::{self, key::{self, Key, Named}, Modifiers}, widget::{button, column as icolumn, container, image, row as irow, scrollable, text, text_input, Space}, Element, Length, Padding, Subscription, Task};
 use nucleo_matcher::Matcher;
 
-use keal::{icon::{IconCache, Icon}, config::config, plugin::{Action, entry::{Label, OwnedEntry}}, log_time};
+use keal::{arguments::arguments, icon::{raster, IconCache, IconPath}, config::{config, EscapeAction, Layout}, edit_history::{EditHistory, EditKind, Snapshot}, keybind::{self, KeyAction}, launch, match_span::MatchSpan, plugin::{Action, entry::{follow_selection, Label, OwnedEntry, Page}}, sensitive::zeroize, log_time};
 
 pub use crate::config::Theme;
-use styled::{ButtonStyle, TextStyle};
+use styled::{ButtonStyle, ContainerStyle, TextStyle};
 
-use self::{match_span::MatchSpan, async_manager::AsyncManager};
+use self::async_manager::AsyncManager;
 
 mod styled;
-mod match_span;
 mod async_manager;
 
 pub struct Keal {
@@ -21,30 +19,96 @@ pub struct Keal {
 
     // UI state
     input: String,
+    /// prefix of the selected plugin, shown as a chip left of the input
+    prefix: Option<String>,
     selected: usize,
+    /// when enabled, typing a digit selects the corresponding entry instead of being inserted in the query
+    jump_mode: bool,
+    history: EditHistory,
+    /// modifiers currently held, since the input doesn't say if shift was held when submitting
+    modifiers: Modifiers,
 
     // data state
-    icons: IconCache,
+    /// `None` until the icon cache is loaded
+    icons: Option<Arc<IconCache>>,
+    /// icons are rasterized in the background, and entries show a blank space in their place until then
+    rendered_icons: HashMap<IconPath, RenderedIcon>,
 
     entries: Vec<OwnedEntry>,
+    /// how many entries are shown out of those that matched, see [`Page::indicator`]
+    indicator: Option<String>,
+    /// message of a plugin saying the query is invalid, shown under the input until it changes
+    invalid: Option<String>,
     manager: AsyncManager,
     sender: Option<mpsc::Sender<async_manager::Event>>,
+    fit: WindowFit,
 
     first_event: bool
 }
 
+/// Sizes the window is opened with, which `auto_shrink` uses to fit its height to the entries
+#[derive(Debug, Clone, Copy)]
+pub struct WindowFit {
+    pub width: f32,
+    /// `auto_shrink` never makes the window higher than this
+    pub max_height: f32,
+    pub input_height: f32,
+    /// estimated height of an entry, since iced doesn't say how high the list is
+    pub row_height: f32
+}
+
+#[derive(Debug, Clone)]
+enum RenderedIcon {
+    Pending,
+    Failed,
+    Ready(PathBuf)
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     // UI events
     TextInput(String),
     Launch(Option<Label>),
-    KeyPress(Key, Modifiers),
+    /// launch the secondary action of the entry, from Shift+Enter
+    LaunchSecondary(Label),
+    KeyPress(Key, key::Physical, Modifiers),
+    ModifiersChanged(Modifiers),
 
     // Worker events
-    IconCacheLoaded(IconCache),
+    IconCacheLoaded(Arc<IconCache>),
+    /// path of the rasterized icon, which the image widget loads
+    RenderedIcon(IconPath, Option<PathBuf>),
     SenderLoaded(mpsc::Sender<async_manager::Event>),
-    Entries(Vec<OwnedEntry>),
+    Entries(Page),
+    /// entries regenerated without the query changing, which keep the selected entry selected
+    Refreshed(Page),
     Action(Action),
+    Resized(iced::Size),
+}
+
+/// Returns the digit inserted in `old` to get `new`, if that is the only change
+fn inserted_digit(old: &str, new: &str) -> Option<u32> {
+    let start = old.chars().zip(new.chars()).take_while(|(a, b)| a == b).count();
+    let mut rest = new.chars().skip(start);
+    let digit = rest.next()?.to_digit(10)?;
+
+    old.chars().skip(start).eq(rest).then_some(digit)
+}
+
+/// Finds the action of the `[keybinds]` section bound to a key press
+fn keybind_action(key: &Key, physical_key: &key::Physical, mods: Modifiers) -> Option<KeyAction> {
+    let key_name = match key {
+        Key::Character(c) => Some(c.to_string()),
+        Key::Named(named) => Some(format!("{named:?}")),
+        Key::Unidentified => None
+    };
+    let code_name = match physical_key {
+        key::Physical::Code(code) => Some(format!("{code:?}")),
+        key::Physical::Unidentified(_) => None
+    };
+
+    let modifiers = keybind::Modifiers { ctrl: mods.control(), shift: mods.shift(), alt: mods.alt() };
+    config().keybinds.action(modifiers, key_name.as_deref(), code_name.as_deref())
 }
 
 fn close_main_window() -> Task<Message> {
@@ -58,19 +122,34 @@ impl Keal {
         self.theme.clone()
     }
 
-    pub fn new(theme: Theme) -> (Self, Task<Message>) {
+    pub fn new(theme: Theme, fit: WindowFit) -> (Self, Task<Message>) {
         log_time("initializing app");
 
         let config = config();
 
         let focus = text_input::focus(text_input::Id::new("query_input")); // focus input on start up
+        // ask for the focus explicitly, in case the window manager doesn't give it to new windows
+        let gain_focus = iced::window::get_oldest().and_then(iced::window::gain_focus);
 
         let icon_theme = config.icon_theme.clone();
+        let icon_size = config.icon_size;
+        // loaded on its own thread, so that it doesn't hold up the executor running the plugin manager
         let load_icons = Task::perform(async move {
-            IconCache::new(&icon_theme)
+            let (sender, receiver) = oneshot::channel();
+            std::thread::spawn(move || { let _ = sender.send(Arc::new(IconCache::new(&icon_theme, icon_size))); });
+            receiver.await.unwrap_or_default()
         }, Message::IconCacheLoaded);
 
-        let command = Task::batch(vec![focus, load_icons]);
+        // bars span the monitor the window opened on, whose size is only known once the window is placed
+        // the window is opened before this task runs, so the width was recorded by then
+        let fit_monitor = match crate::monitor_width() {
+            Some(width) if config.position.is_bar() => iced::window::get_oldest().and_then(move |id| {
+                iced::window::resize(id, iced::Size::new(width, fit.max_height))
+            }),
+            _ => Task::none()
+        };
+
+        let command = Task::batch(vec![focus, gain_focus, load_icons, fit_monitor]);
         let manager = AsyncManager::new(Matcher::default(), 50, true);
 
         log_time("finished initializing");
@@ -78,22 +157,44 @@ impl Keal {
         (Keal {
             theme,
             input: String::new(),
+            prefix: None,
             selected: 0,
-            icons: IconCache::default(),
+            jump_mode: false,
+            history: EditHistory::default(),
+            modifiers: Modifiers::default(),
+            icons: None,
+            rendered_icons: HashMap::new(),
             entries: Vec::new(),
+            indicator: None,
+            invalid: None,
             manager,
             sender: None,
+            fit,
             first_event: false
         }, command)
     }
 
     pub fn subscription(&self) -> iced::Subscription<Message> {
-        let key_press = keyboard::on_key_press(|key, mods| {
-            Some(Message::KeyPress(key, mods))
+        // like `keyboard::on_key_press`, but keeping the physical key for `code:` keybinds
+        let key_press = event::listen_with(|event, status, _| match (event, status) {
+            (iced::Event::Keyboard(keyboard::Event::KeyPressed { key, physical_key, modifiers, .. }), event::Status::Ignored) => Some(Message::KeyPress(key, physical_key, modifiers)),
+            // with the horizontal layout, Left and Right move the selection even though the input took them
+            (iced::Event::Keyboard(keyboard::Event::KeyPressed { key: key @ Key::Named(Named::ArrowLeft | Named::ArrowRight), physical_key, modifiers, .. }), event::Status::Captured)
+                if config().layout == Layout::Horizontal => Some(Message::KeyPress(key, physical_key, modifiers)),
+            _ => None
         });
 
+        // the input captures key presses but lets modifier changes through
+        let modifiers = event::listen_with(|event, _, _| match event {
+            iced::Event::Keyboard(keyboard::Event::ModifiersChanged(mods)) => Some(Message::ModifiersChanged(mods)),
+            _ => None
+        });
+
+        // the horizontal layout fits entries to the width of the window
+        let resized = iced::window::resize_events().map(|(_, size)| Message::Resized(size));
+
         let manager = Subscription::run_with_id("manager", self.manager.subscription());
-        Subscription::batch([key_press, manager])
+        Subscription::batch([key_press, modifiers, resized, manager])
     }
 
     pub fn view(&self) -> iced::Element<'_, Message, Theme> {
@@ -102,11 +203,44 @@ impl Keal {
 
         let input = text_input(&config.placeholder_text, &self.input)
             .on_input(Message::TextInput)
-            .on_submit(Message::Launch(entries.get(self.selected).map(|e| e.label)))
+            .on_submit(match (self.modifiers.shift(), entries.get(self.selected)) {
+                (true, _) if arguments().dmenu => Message::Launch(None), // print the query as typed, even if an entry matches
+                (true, Some(entry)) => Message::LaunchSecondary(entry.label),
+                (_, entry) => Message::Launch(entry.map(|e| e.label))
+            })
             .size(config.font_size * 1.25).padding(config.font_size)
             .id(text_input::Id::new("query_input"));
 
-        let input = container(input)
+        let prompt = arguments().prompt();
+        let input: Element<_, _> = if prompt.is_none() && self.prefix.is_none() && self.indicator.is_none() && self.invalid.is_none() {
+            input.into()
+        } else {
+            let prompt = prompt.map(|prompt| container(text(prompt).size(config.font_size * 1.25).shaping(self.theme.text_shaping).class(TextStyle::Prompt))
+                .padding(Padding { right: 8.0, ..Padding::ZERO }));
+            let chip = self.prefix.as_ref().map(|prefix| container(text(prefix).size(config.font_size).shaping(self.theme.text_shaping))
+                .padding([2.0, 8.0])
+                .class(ContainerStyle::Chip));
+            // with the horizontal layout, there is no room under the input, so the message takes the place of the indicator
+            let indicator = match &self.invalid {
+                Some(invalid) if config.layout == Layout::Horizontal => Some(text(invalid).size(config.font_size).class(TextStyle::Error)),
+                _ => self.indicator.as_ref().map(|indicator| text(indicator).size(config.font_size).class(TextStyle::Comment))
+            };
+
+            let left = if prompt.is_some() || chip.is_some() { config.font_size } else { 0.0 };
+            let right = if indicator.is_some() { config.font_size } else { 0.0 };
+            container(irow![].push_maybe(prompt).push_maybe(chip).push(input).push_maybe(indicator).align_y(iced::alignment::Vertical::Center))
+                .padding(Padding { left, right, ..Padding::ZERO })
+                .class(if self.invalid.is_some() { ContainerStyle::InvalidInput } else { ContainerStyle::Input })
+                .into()
+        };
+
+        if config.layout == Layout::Horizontal {
+            return self.view_horizontal(input)
+        }
+
+        let invalid = self.invalid.as_ref().map(|invalid| container(text(invalid).size(config.font_size).shaping(self.theme.text_shaping).class(TextStyle::Error))
+            .padding(Padding { top: 5.0, bottom: 5.0, left: config.font_size, right: config.font_size }));
+        let input = container(icolumn![input].push_maybe(invalid))
             .width(Length::Fill);
 
         let data = &mut *self.manager.get_data();
@@ -118,18 +252,26 @@ impl Keal {
 
                 let mut item = irow(vec![]);
 
-                if let Some(icon) = &entry.icon {
-                    if let Some(icon) = self.icons.get(icon) {
-                        let element: Element<_, _> = match icon {
-                            Icon::Svg(path) => svg(svg::Handle::from_path(path)).width(config.font_size).height(config.font_size).into(),
-                            Icon::Other(path) => image(path).width(config.font_size).height(config.font_size).into()
-                        };
-                        item = item.push(container(element).padding(4));
+                if self.jump_mode && index < 10 {
+                    item = item.push(text(((index + 1) % 10).to_string()).size(config.font_size).width(config.font_size).class(TextStyle::Comment));
+                }
+
+                if let Some(IconPath::Glyph(glyph)) = &entry.icon {
+                    let glyph = text(glyph).size(config.icon_size).width(config.icon_size).shaping(self.theme.text_shaping);
+                    item = item.push(container(glyph).padding([4.0, config.icon_padding]));
+                } else if let Some(icon) = &entry.icon {
+                    let element: Option<Element<_, _>> = match self.rendered_icons.get(icon) {
+                        Some(RenderedIcon::Ready(path)) => Some(image(path).width(config.icon_size).height(config.icon_size).into()),
+                        Some(RenderedIcon::Failed) => None,
+                        Some(RenderedIcon::Pending) | None => Some(Space::new(config.icon_size, config.icon_size).into())
+                    };
+                    if let Some(element) = element {
+                        item = item.push(container(element).padding([4.0, config.icon_padding]));
                     }
                 }
 
                 for (span, highlighted) in MatchSpan::new(&entry.name, &mut data.matcher, &data.pattern, &mut buf) {
-                    item = item.push(text(span).size(config.font_size).shaping(self.theme.text_shaping).class(
+                    item = item.push(text(&entry.name[span]).size(config.font_size).shaping(self.theme.text_shaping).class(
                         match highlighted {
                             false => TextStyle::Normal,
                             true => TextStyle::Matched { selected },
@@ -148,12 +290,21 @@ impl Keal {
                     );
                 }
 
-                button(item)
-                    .on_press(Message::Launch(Some(entry.label)))
+                let item = button(item)
+                    .on_press_maybe(config.mouse_enabled.then_some(Message::Launch(Some(entry.label))))
                     .class(if selected { ButtonStyle::Selected } else { ButtonStyle::Normal })
-                    .padding(Padding { right: 20.0, ..Padding::new(10.0) })
+                    .padding(Padding { top: config.density.row_padding(), bottom: config.density.row_padding(), left: 10.0, right: 20.0 });
+
+                // section headers aren't entries, so they can't be hovered or selected
+                match &entry.section {
+                    Some(section) => Element::from(icolumn![
+                        container(text(section).size(config.font_size * 0.85).shaping(self.theme.text_shaping).class(TextStyle::Comment))
+                            .padding(Padding { top: 5.0, ..Padding::new(10.0) }),
+                        item
+                    ]),
+                    None => Element::from(item)
+                }
             })
-                .map(Element::<_, _>::from)
         })).id(scrollable::Id::new("scrollable"));
 
         icolumn![ input, entries ]
@@ -161,6 +312,81 @@ impl Keal {
             .into()
     }
 
+    /// Shows the entries after the search bar, on the same line, like dmenu
+    /// Only their names are shown, and only the page of those that fit which has the selected entry
+    fn view_horizontal<'a>(&'a self, input: Element<'a, Message, Theme>) -> Element<'a, Message, Theme> {
+        let config = config();
+
+        // room is left on both sides for the arrows showing that there are more entries
+        let arrow_width = config.font_size * 1.5;
+        let arrow = |arrow: &'static str, shown: bool| container(text(if shown { arrow } else { "" }).size(config.font_size).class(TextStyle::Comment))
+            .center_x(arrow_width);
+
+        let page = self.horizontal_page(self.fit.width * 2.0/3.0 - 2.0*arrow_width);
+
+        let data = &mut *self.manager.get_data();
+        let mut buf = vec![];
+
+        let mut entries = irow![arrow("<", page.start > 0)];
+        for index in page.clone() {
+            let entry = &self.entries[index];
+            let selected = self.selected == index;
+
+            let mut item = irow(vec![]);
+            if self.jump_mode && index < 10 {
+                item = item.push(text(((index + 1) % 10).to_string()).size(config.font_size).width(config.font_size).class(TextStyle::Comment));
+            }
+            for (span, highlighted) in MatchSpan::new(&entry.name, &mut data.matcher, &data.pattern, &mut buf) {
+                item = item.push(text(&entry.name[span]).size(config.font_size).shaping(self.theme.text_shaping).class(
+                    match highlighted {
+                        false => TextStyle::Normal,
+                        true => TextStyle::Matched { selected },
+                    }
+                ));
+            }
+
+            entries = entries.push(button(item)
+                .on_press_maybe(config.mouse_enabled.then_some(Message::Launch(Some(entry.label))))
+                .class(if selected { ButtonStyle::Selected } else { ButtonStyle::Normal })
+                .height(Length::Fill)
+                .padding(Padding { top: 0.0, bottom: 0.0, left: 10.0, right: 10.0 }));
+        }
+        entries = entries.push(Space::with_width(Length::Fill));
+        entries = entries.push(arrow(">", page.end < self.entries.len()));
+
+        // like dmenu, the input takes a third of the bar
+        irow![
+            container(input).width(Length::FillPortion(1)),
+            container(entries.align_y(iced::alignment::Vertical::Center).height(Length::Fill)).width(Length::FillPortion(2))
+        ]
+            .width(Length::Fill).height(Length::Fill)
+            .into()
+    }
+
+    /// Splits the entries in pages that fit in `width` and returns the one with the selected entry
+    /// iced doesn't measure text before drawing it, so widths are estimated from the monospace font, where characters are half as wide as high
+    fn horizontal_page(&self, width: f32) -> Range<usize> {
+        let config = config();
+        let entry_width = |index: usize| {
+            let number_width = if self.jump_mode && index < 10 { config.font_size } else { 0.0 };
+            self.entries[index].name.chars().count() as f32 * config.font_size * 0.5 + number_width + 20.0
+        };
+
+        let mut start = 0;
+        let mut used = 0.0;
+        for index in 0..self.entries.len() {
+            let width_of_entry = entry_width(index);
+            // a page always has at least one entry, even if it doesn't fit
+            if used + width_of_entry > width && index > start {
+                if index > self.selected { return start..index }
+                start = index;
+                used = 0.0;
+            }
+            used += width_of_entry;
+        }
+        start..self.entries.len()
+    }
+
     pub fn update(&mut self, message: Message) -> Task<Message> {
         if !self.first_event {
             self.first_event = true;
@@ -171,31 +397,120 @@ impl Keal {
         // scrollable::Properties::default().width
 
         match message {
-            Message::KeyPress(key, mods) => match (key.as_ref(), mods) {
-                (Key::Named(Named::Escape), _) => return close_main_window(),
+            Message::ModifiersChanged(mods) => self.modifiers = mods,
+            Message::KeyPress(key, physical_key, mods) => match (key.as_ref(), mods, keybind_action(&key, &physical_key, mods)) {
+                (Key::Named(Named::Escape), _, _) => {
+                    // the first escape only clears the query and exits plugin mode
+                    if config().escape_action == EscapeAction::ClearThenClose && (!self.input.is_empty() || self.prefix.is_some()) {
+                        self.prefix = None;
+                        self.update_input(String::new(), true);
+                    } else {
+                        return close_main_window()
+                    }
+                }
+                // the input also moved its cursor, which is put back at the end like in dmenu
+                (Key::Named(Named::ArrowRight), _, _) if config().layout == Layout::Horizontal => {
+                    self.selected = (self.selected + 1).min(self.entries.len().saturating_sub(1));
+                    return text_input::move_cursor_to_end(text_input::Id::new("query_input"))
+                }
+                (Key::Named(Named::ArrowLeft), _, _) if config().layout == Layout::Horizontal => {
+                    self.selected = self.selected.saturating_sub(1);
+                    return text_input::move_cursor_to_end(text_input::Id::new("query_input"))
+                }
                 // TODO: gently scroll window to selected choice
-                (Key::Character("j" | "n"), Modifiers::CTRL)  | (Key::Named(Named::ArrowDown), _)  => {
+                (Key::Named(Named::ArrowDown), _, _) | (_, _, Some(KeyAction::SelectNext)) => {
                     self.selected += 1;
                     self.selected = self.selected.min(self.entries.len().saturating_sub(1));
                 }
-                (Key::Character("k" | "p"), Modifiers::CTRL) | (Key::Named(Named::ArrowUp), _) => {
+                (Key::Named(Named::ArrowUp), _, _) | (_, _, Some(KeyAction::SelectPrevious)) => {
                     self.selected = self.selected.saturating_sub(1);
                 }
+                (_, _, Some(KeyAction::JumpMode)) => self.jump_mode = !self.jump_mode,
+                (_, _, Some(KeyAction::NextPage)) => if let Some(sender) = &mut self.sender {
+                    sender.try_send(async_manager::Event::NextPage).expect("failed to send next page command");
+                }
+                // custom keybinds, Ctrl+1 picks the selection and exits with 10, Ctrl+2 with 11, etc, like rofi's kb-custom-N
+                (Key::Character(digit @ ("1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9")), Modifiers::CTRL, _) if arguments().dmenu => {
+                    keal::set_exit_code(9 + digit.parse::<i32>().unwrap());
+                    return self.update(Message::Launch(self.entries.get(self.selected).map(|e| e.label)))
+                }
+                (Key::Character("z"), Modifiers::CTRL, _) => {
+                    let snapshot = self.history.undo(self.prefix.as_deref(), &self.input, self.input.len());
+                    return self.restore(snapshot)
+                }
+                (Key::Character("z" | "Z"), mods, _) if mods == Modifiers::CTRL | Modifiers::SHIFT => {
+                    let snapshot = self.history.redo(self.prefix.as_deref(), &self.input, self.input.len());
+                    return self.restore(snapshot)
+                }
                 _ => ()
             }
-            Message::TextInput(input) => self.update_input(input, true),
+            Message::TextInput(input) => {
+                // in jump mode, a typed digit selects an entry and is never inserted in the query
+                if let Some(digit) = inserted_digit(&self.input, &input).filter(|_| self.jump_mode) {
+                    let index = (digit as usize + 9) % 10; // 1 is the first entry, 0 the tenth
+                    if index < self.entries.len() {
+                        self.selected = index;
+                    }
+                    return Task::none()
+                }
+                self.jump_mode = false; // editing the query leaves jump mode
+
+                // backspace on an empty query removes the plugin chip
+                if input.is_empty() && self.input.is_empty() && self.prefix.is_some() {
+                    self.history.record(EditKind::Other, self.prefix.as_deref(), &self.input, 0);
+                    self.prefix = None;
+                }
+
+                if input != self.input {
+                    // the widget doesn't expose its cursor, so edits are told apart by how many characters changed
+                    let kind = match input.chars().count() as isize - self.input.chars().count() as isize {
+                        1 => EditKind::Insert,
+                        -1 => EditKind::Delete,
+                        _ => EditKind::Other
+                    };
+                    self.history.record(kind, self.prefix.as_deref(), &self.input, self.input.len());
+                }
+                self.update_input(input, true)
+            }
             Message::Launch(selected) => {
                 if let Some(sender) = &mut self.sender {
-                    sender.try_send(async_manager::Event::Launch(selected)).expect("failed to send launch command");
+                    sender.try_send(async_manager::Event::Launch(selected, false)).expect("failed to send launch command");
+                }
+            }
+            Message::LaunchSecondary(selected) => {
+                if let Some(sender) = &mut self.sender {
+                    sender.try_send(async_manager::Event::Launch(Some(selected), true)).expect("failed to send launch command");
                 }
             }
-            Message::IconCacheLoaded(icon_cache) => self.icons = icon_cache,
-            Message::Entries(entries) => self.entries = entries,
+            Message::IconCacheLoaded(icon_cache) => {
+                self.icons = Some(icon_cache);
+                return self.render_icons()
+            }
+            Message::RenderedIcon(icon, path) => {
+                let rendered = match path {
+                    Some(path) => RenderedIcon::Ready(path),
+                    None => RenderedIcon::Failed
+                };
+                self.rendered_icons.insert(icon, rendered);
+            }
+            Message::Entries(page) => {
+                self.selected = follow_selection(&self.entries, &page.entries, self.selected, true);
+                self.indicator = page.indicator();
+                self.entries = page.entries;
+                return Task::batch([self.render_icons(), self.fit_height()])
+            }
+            Message::Refreshed(page) => {
+                self.selected = follow_selection(&self.entries, &page.entries, self.selected, false);
+                self.indicator = page.indicator();
+                self.entries = page.entries;
+                return Task::batch([self.render_icons(), self.fit_height()])
+            }
             Message::SenderLoaded(sender) => {
                 self.sender = Some(sender);
                 self.update_input(self.input.clone(), true); // in case the user typed in before the manager was loaded
             },
             Message::Action(action) => return self.handle_action(action),
+            Message::Resized(size) => self.fit.width = size.width,
         };
 
         Task::none()
@@ -203,47 +518,126 @@ impl Keal {
 }
 
 impl Keal {
-    pub fn update_input(&mut self, input: String, from_user: bool) {
+    pub fn update_input(&mut self, mut input: String, from_user: bool) {
+        // a plugin prefix followed by a space becomes a chip, the input only keeps the query
+        if self.prefix.is_none() {
+            if let Some((prefix, query)) = self.manager.split_prefix(&input) {
+                self.prefix = Some(prefix.to_owned());
+                input = query.to_owned();
+            }
+        }
+
         self.input = input.clone();
+        self.invalid = None;
         if let Some(sender) = &mut self.sender {
-            sender.try_send(async_manager::Event::UpdateInput(input, from_user)).expect("failed to send update input command");
+            sender.try_send(async_manager::Event::UpdateInput(self.prefix.clone(), input, from_user)).expect("failed to send update input command");
         }
     }
 
+    /// With `auto_shrink`, resizes the window to be about as high as the entries, up to its height when it was opened
+    fn fit_height(&self) -> Task<Message> {
+        if !config().auto_shrink { return Task::none() }
+
+        let WindowFit { width, max_height, input_height, row_height } = self.fit;
+        let height = (input_height + self.entries.len() as f32*row_height).min(max_height).ceil();
+        iced::window::get_oldest().and_then(move |id| iced::window::resize(id, iced::Size::new(width, height)))
+    }
+
+    /// Rasterizes the icons of the entries that weren't yet, on another thread, since the view can't wait for them
+    fn render_icons(&mut self) -> Task<Message> {
+        let Some(icons) = self.icons.clone() else { return Task::none() };
+
+        let mut requested = vec![];
+        for icon in self.entries.iter().filter_map(|entry| entry.icon.as_ref()) {
+            if matches!(icon, IconPath::Glyph(_)) || self.rendered_icons.contains_key(icon) { continue }
+
+            self.rendered_icons.insert(icon.clone(), RenderedIcon::Pending);
+            requested.push(icon.clone());
+        }
+        if requested.is_empty() { return Task::none() }
+
+        let (sender, receiver) = mpsc::unbounded();
+        std::thread::spawn(move || {
+            for icon in requested {
+                // rendered at twice their size, so that they stay sharp on scaled screens
+                let path = icons.get(&icon).and_then(|found| raster::cached_png(found, (config().icon_size * 2.0).ceil() as u32));
+                if sender.unbounded_send(Message::RenderedIcon(icon, path)).is_err() { break }
+            }
+        });
+        Task::stream(receiver)
+    }
+
+    /// Restores the input to a state from the edit history
+    fn restore(&mut self, snapshot: Option<Snapshot>) -> Task<Message> {
+        let Some(snapshot) = snapshot else { return Task::none() };
+
+        self.prefix = snapshot.prefix;
+        self.update_input(snapshot.text, true);
+        text_input::move_cursor_to_end(text_input::Id::new("query_input"))
+    }
+
     fn handle_action(&mut self, action: Action) -> Task<Message> {
         match action {
             Action::None => (),
             Action::ChangeInput(new) => {
                 self.manager.with_manager(|m| m.kill());
+                self.prefix = None;
                 self.update_input(new, false);
                 return text_input::move_cursor_to_end(text_input::Id::new("query_input"));
             }
             Action::ChangeQuery(new) => {
-                let new = self.manager.use_manager(|m| m.current().map(
-                    |plugin| format!("{} {}", plugin.prefix, new) 
-                )).unwrap_or(new);
+                // the plugin chip is kept
                 self.update_input(new, false);
 
                 return text_input::move_cursor_to_end(text_input::Id::new("query_input"));
             }
-            Action::Exec(mut command) => {
-                let _ = command.0.exec();
+            Action::Invalid(message) => self.invalid = Some(message),
+            Action::Sensitive => {
+                // the plugin chip is kept
+                zeroize(&mut self.input);
+                self.history.clear();
+                self.update_input(String::new(), false);
+            }
+            Action::Exec(command) if arguments().stay_open => {
+                launch::spawn_detached(command);
+                return self.reset();
+            }
+            Action::Exec(command) => {
+                launch::exec(command);
                 return close_main_window();
             }
             Action::PrintAndClose(message) => {
                 println!("{message}");
-                return close_main_window();
+                return if arguments().stay_open { self.reset() } else { close_main_window() };
+            }
+            Action::Fork if arguments().stay_open => {
+                self.manager.with_manager(|m| m.detach());
+                return self.reset();
             }
-            Action::Fork => match fork().expect("failed to fork") {
-                Fork::Parent(_) => return close_main_window(),
-                Fork::Child => ()
+            Action::Fork => {
+                self.manager.with_manager(launch::fork_plugin);
+                return close_main_window();
             }
             Action::WaitAndClose => {
                 self.manager.with_manager(|m| m.wait());
-                return close_main_window();
+                return if arguments().stay_open { self.reset() } else { close_main_window() };
             }
         }
 
         Task::none()
     }
+
+    /// Goes back to an empty query with the input focused, after launching something with `--stay-open`
+    fn reset(&mut self) -> Task<Message> {
+        self.prefix = None;
+        self.selected = 0;
+        self.update_input(String::new(), true);
+        text_input::focus(text_input::Id::new("query_input"))
+    }
+}
+
+impl Drop for Keal {
+    fn drop(&mut self) {
+        zeroize(&mut self.input);
+    }
 }
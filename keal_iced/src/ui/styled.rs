@@ -1,5 +1,5 @@
 use iced::{
-    widget::{button, text_input, text, container, scrollable, svg},
+    widget::{button, text_input, text, container, scrollable},
     Color, application,
 };
 
@@ -43,7 +43,11 @@ pub enum TextStyle {
     Matched {
         selected: bool
     },
-    Comment
+    Comment,
+    /// label left of the input
+    Prompt,
+    /// message of a plugin saying the query is invalid
+    Error
 }
 
 impl text::Catalog for Theme {
@@ -55,7 +59,9 @@ impl text::Catalog for Theme {
                 TextStyle::Normal => self.text,
                 TextStyle::Matched { selected: false } => self.matched_text,
                 TextStyle::Matched { selected: true } => self.selected_matched_text,
-                TextStyle::Comment => self.comment
+                TextStyle::Comment => self.comment,
+                TextStyle::Prompt => self.input_prompt,
+                TextStyle::Error => self.input_error
             })
         }
     }
@@ -101,13 +107,13 @@ impl button::Catalog for Theme {
     fn style(&self, style: &Self::Class<'_>, status: button::Status) -> button::Style {
         button::Style {
             background: Some(match status {
-                button::Status::Active => match style {
+                // buttons are only disabled when mouse interaction is turned off
+                button::Status::Active | button::Status::Disabled => match style {
                     ButtonStyle::Normal => self.choice_background,
                     ButtonStyle::Selected => self.selected_choice_background
                 }
                 button::Status::Hovered => self.hovered_choice_background,
-                button::Status::Pressed => self.pressed_choice_background,
-                button::Status::Disabled => color!(0xdddddd)
+                button::Status::Pressed => self.pressed_choice_background
             }.into()),
             text_color: self.text,
             ..Default::default()
@@ -115,13 +121,45 @@ impl button::Catalog for Theme {
     }
 }
 
-impl container::Catalog for Theme {
-    type Class<'a> = ();
-
-    fn default<'a>() -> Self::Class<'a> { () }
+#[derive(Default)]
+pub enum ContainerStyle {
+    #[default]
+    Normal,
+    /// wraps the text input and the plugin chip
+    Input,
+    /// wraps the text input when a plugin said the query is invalid
+    InvalidInput,
+    /// prefix of the selected plugin, shown left of the text input
+    Chip
+}
 
-    fn style(&self, _class: &Self::Class<'_>) -> container::Style {
-        container::Style { text_color: Some(self.text), ..Default::default() }
+impl container::Catalog for Theme {
+    type Class<'a> = ContainerStyle;
+
+    fn default<'a>() -> Self::Class<'a> { ContainerStyle::default() }
+
+    fn style(&self, class: &Self::Class<'_>) -> container::Style {
+        match class {
+            ContainerStyle::Normal => container::Style { text_color: Some(self.text), ..Default::default() },
+            ContainerStyle::Input => container::Style {
+                text_color: Some(self.text),
+                background: Some(self.input_background.into()),
+                border: iced::Border { radius: iced::border::top(5.0), ..Default::default() },
+                ..Default::default()
+            },
+            ContainerStyle::InvalidInput => container::Style {
+                text_color: Some(self.text),
+                background: Some(self.input_background.into()),
+                border: iced::Border { color: self.input_error, width: 1.0, radius: iced::border::top(5.0) },
+                ..Default::default()
+            },
+            ContainerStyle::Chip => container::Style {
+                text_color: Some(self.text),
+                background: Some(self.selected_choice_background.into()),
+                border: iced::Border { radius: 5.0.into(), ..Default::default() },
+                ..Default::default()
+            }
+        }
     }
 }
 
@@ -162,13 +200,3 @@ impl scrollable::Catalog for Theme {
         style
     }
 }
-
-impl svg::Catalog for Theme {
-    type Class<'a> = ();
-
-    fn style(&self, _: &Self::Class<'_>, _status: svg::Status) -> svg::Style {
-        svg::Style::default()
-    }
-
-    fn default<'a>() -> Self::Class<'a> { () }
-}
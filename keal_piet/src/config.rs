@@ -6,9 +6,18 @@ pub struct Theme {
     pub background: Color,
 
     pub input_placeholder: Color,
+    /// color of the prompt label, see [`keal::arguments::Arguments::prompt`]
+    pub input_prompt: Color,
+    /// border of the input and message under it, when a plugin says the query is invalid
+    pub input_error: Color,
     pub input_selection: Color,
     pub input_background: Color,
 
+    pub cursor_color: Color,
+    pub cursor_width: f32,
+    /// how long the cursor stays visible, then hidden, 0 disables blinking
+    pub cursor_blink_ms: u64,
+
     pub text: Color,
     pub matched_text: Color,
     pub selected_matched_text: Color,
@@ -31,8 +40,13 @@ impl Default for Theme {
             scrollbar_border_radius: 0.0,
             background: Color::BLACK,
             input_placeholder: Color::BLACK,
+            input_prompt: Color::BLACK,
+            input_error: Color::BLACK,
             input_selection: Color::BLACK,
             input_background: Color::BLACK,
+            cursor_color: Color::BLACK,
+            cursor_width: 0.0,
+            cursor_blink_ms: 0,
             text: Color::BLACK,
             matched_text: Color::BLACK,
             selected_matched_text: Color::BLACK,
@@ -56,7 +70,8 @@ impl FrontendConfig for Theme {
     fn add_field(&mut self, field: (String, String)) {
         parse_fields!(self, field, (
                 background,
-                input_placeholder, input_selection, input_background,
+                input_placeholder, input_prompt, input_error, input_selection, input_background,
+                cursor_color, cursor_width, cursor_blink_ms,
                 text, matched_text, selected_matched_text, comment,
                 choice_background, selected_choice_background, hovered_choice_background, pressed_choice_background,
                 scrollbar_enabled, scrollbar, hovered_scrollbar, scrollbar_border_radius
@@ -104,3 +119,9 @@ impl MyFromStr<f32> for str {
         self.parse().map_err(|_| "couldn't parse number")
     }
 }
+
+impl MyFromStr<u64> for str {
+    fn my_parse(&self) -> Result<u64, &'static str> {
+        self.parse().map_err(|_| "couldn't parse integer")
+    }
+}
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use winit::{raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle}, window::Window};
+use x11_dl::xlib::{self, Xlib};
+
+/// how many times to try grabbing the keyboard, waiting a millisecond in between, like dmenu
+const GRAB_ATTEMPTS: u32 = 1000;
+
+/// Grabs the keyboard on X11, so that keys go to keal even if the window manager didn't give it the focus
+/// The window has to be visible, and the grab ends by itself when it closes
+pub fn grab_keyboard(window: &Window) {
+    let (Ok(display), Ok(handle)) = (window.display_handle(), window.window_handle()) else { return };
+    let (RawDisplayHandle::Xlib(display), RawWindowHandle::Xlib(handle)) = (display.as_raw(), handle.as_raw()) else {
+        eprintln!("grab_keyboard is only supported on X11");
+        return
+    };
+    let Some(display) = display.display else { return };
+
+    let xlib = match Xlib::open() {
+        Ok(xlib) => xlib,
+        Err(e) => {
+            eprintln!("failed to grab keyboard: {e}");
+            return
+        }
+    };
+
+    // grabbing fails until the window manager has mapped the window
+    for _ in 0..GRAB_ATTEMPTS {
+        // SAFETY: the display is winit's connection, which lives as long as the window
+        let status = unsafe {
+            (xlib.XGrabKeyboard)(display.as_ptr().cast(), handle.window, xlib::True, xlib::GrabModeAsync, xlib::GrabModeAsync, xlib::CurrentTime)
+        };
+        if status == xlib::GrabSuccess { return }
+
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    eprintln!("failed to grab keyboard");
+}
@@ -0,0 +1,374 @@
+mod winit_app;
+mod focus;
+
+use config::Theme;
+use keal::config::{WindowLevel, WindowPosition};
+use ui::Damage;
+use keal::animation::Animation;
+use softbuffer::Surface;
+
+use std::{num::NonZeroU32, rc::Rc};
+use winit::{dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize}, event::{ElementState, Event, MouseButton, MouseScrollDelta, WindowEvent}, event_loop::{ControlFlow, EventLoop}, platform::{startup_notify::{self, EventLoopExtStartupNotify, WindowAttributesExtStartupNotify}, x11::WindowAttributesExtX11}, raw_window_handle::{HasDisplayHandle, HasWindowHandle}, window::{self, Window}};
+
+use piet_tiny_skia::{self as pts, piet::FontFamily, tiny_skia::Pixmap, AsPixmapMut};
+use pts::piet::{kurbo::{Affine, Rect}, RenderContext, Text};
+
+mod ui;
+mod config;
+
+/// logical pixels scrolled by one notch of a mouse wheel
+const LINE_SCROLL: f64 = 20.0;
+
+struct State {
+    cache: pts::Cache,
+    pixmap: Pixmap,
+    ui_state: UiState,
+    theme: &'static Theme,
+    animation: Animation,
+    /// exposes the ui to screen readers
+    accessibility: accesskit_winit::Adapter,
+    keal: ui::Keal
+}
+
+/// Events sent to the event loop from other threads
+pub enum UserEvent {
+    /// the ui has messages to handle
+    Wake,
+    /// a screen reader asked for something
+    AccessKit(accesskit_winit::Event)
+}
+
+impl From<accesskit_winit::Event> for UserEvent {
+    fn from(event: accesskit_winit::Event) -> Self {
+        UserEvent::AccessKit(event)
+    }
+}
+
+/// Positions and sizes are in logical pixels, which are scaled by `scale` when drawing
+struct UiState {
+    screen_width: f64,
+    screen_height: f64,
+    /// scale factor of the monitor the window is on
+    scale: f64,
+    mouse_pos: LogicalPosition<f64>,
+    ctrl: bool,
+    shift: bool,
+    alt: bool
+}
+
+fn redraw<D, W>(state: &mut State, window: &mut Rc<Window>, surface: &mut Surface<D, W>) 
+    where D: HasDisplayHandle, W: HasWindowHandle
+{
+    let size = window.inner_size();
+    if size.width == 0 || size.height == 0 { return }
+
+    let mut damage = state.keal.take_damage();
+    if state.pixmap.width() != size.width || state.pixmap.height() != size.height {
+        state.pixmap = Pixmap::new(size.width, size.height).unwrap();
+        damage = Damage::Full;
+    }
+
+    // slide the content in from slightly below, and fade it from the background color
+    let visibility = state.animation.visibility() as f64;
+    if visibility < 1.0 || state.animation.running() {
+        damage = Damage::Full;
+    }
+
+    let search_bar_height = (keal::config::config().font_size as f64 * 3.25).ceil();
+
+    let mut render_context = state.cache.render_context(state.pixmap.as_mut());
+    render_context.transform(Affine::scale(state.ui_state.scale));
+    match damage {
+        Damage::None => (),
+        Damage::Input => {
+            let search_bar = Rect::new(0.0, 0.0, state.ui_state.screen_width, search_bar_height);
+            render_context.fill(search_bar, &state.theme.background);
+            state.keal.render_input(&mut render_context);
+        }
+        Damage::Full => {
+            render_context.clear(None, state.theme.background);
+
+            let _ = render_context.save();
+            render_context.transform(Affine::translate((0.0, (1.0 - visibility) * 16.0)));
+            state.keal.render(&state.ui_state, &mut render_context);
+            let _ = render_context.restore();
+
+            if visibility < 1.0 {
+                let screen = Rect::new(0.0, 0.0, state.ui_state.screen_width, state.ui_state.screen_height);
+                render_context.fill(screen, &state.theme.background.with_alpha(1.0 - visibility));
+            }
+        }
+    }
+    drop(render_context);
+
+    // what changed on screen may have changed for screen readers too
+    if damage == Damage::Full {
+        state.accessibility.update_if_active(|| state.keal.accessibility_tree());
+    }
+
+    let mut buffer = surface.buffer_mut().unwrap();
+    // only the changed rows are converted, unless the buffer doesn't hold the last frame
+    let rows = match damage {
+        _ if buffer.age() != 1 => size.height,
+        Damage::None => 0,
+        Damage::Input => ((search_bar_height * state.ui_state.scale).ceil() as u32).min(size.height),
+        Damage::Full => size.height
+    };
+
+    let pixels = (rows * size.width) as usize;
+    for (i, pixel) in state.pixmap.pixels()[..pixels].iter().enumerate() {
+        buffer[i] = ((pixel.red() as u32) << 16) | ((pixel.green() as u32) << 8) | ((pixel.blue() as u32));
+    }
+
+    match NonZeroU32::new(rows) {
+        Some(height) => {
+            let width = NonZeroU32::new(size.width).unwrap();
+            buffer.present_with_damage(&[softbuffer::Rect { x: 0, y: 0, width, height }]).unwrap();
+        }
+        None => buffer.present().unwrap()
+    }
+}
+
+/// Updates the logical screen size from the physical size of the window
+fn resize(state: &mut State, size: PhysicalSize<u32>) {
+    let size = size.to_logical::<f64>(state.ui_state.scale);
+    state.ui_state.screen_width = size.width;
+    state.ui_state.screen_height = size.height;
+
+    let mut rc = state.cache.render_context(state.pixmap.as_pixmap_mut());
+    state.keal.on_resize(&mut rc);
+}
+
+/// Runs the piet frontend
+/// Arguments should have been initialized beforehand
+pub fn run() {
+    keal::log_time("reading config");
+
+    let mut theme = config::Theme::default();
+    let config = keal::config::Config::init_launcher(&mut theme);
+
+    let theme: &'static Theme = Box::leak(Box::new(theme));
+
+    keal::log_time("initializing winit");
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build().unwrap();
+    // wakes up the event loop when plugins send entries or actions, and when screen readers make requests
+    let proxy = event_loop.create_proxy();
+
+    let app = winit_app::WinitAppBuilder::with_init(
+        move |elwt| {
+            keal::log_time("initializing window state");
+
+            // the activation token of whatever started keal lets the compositor focus it despite focus stealing prevention
+            // it can only be used once, so it's removed from the environment programs are launched with
+            let token = elwt.read_token_from_env();
+            if token.is_some() { startup_notify::reset_activation_token_env(); }
+
+            // the window manager doesn't stack windows it doesn't manage, so they stay above fullscreen ones
+            // bars aren't managed either, like in dmenu, so that tiling window managers don't tile them
+            let unmanaged = config.window_level == WindowLevel::AboveFullscreen || config.position.is_bar();
+
+            // the accessibility adapter has to be created before the window is shown
+            let window = winit_app::make_window(elwt, |w| {
+                let w = w.with_visible(false)
+                    .with_window_level(match config.window_level {
+                        WindowLevel::Normal => window::WindowLevel::Normal,
+                        WindowLevel::OnTop | WindowLevel::AboveFullscreen => window::WindowLevel::AlwaysOnTop
+                    })
+                    .with_override_redirect(unmanaged);
+                match token {
+                    Some(token) => w.with_activation_token(token),
+                    None => w
+                }
+            });
+            let accessibility = accesskit_winit::Adapter::with_event_loop_proxy(&window, proxy.clone());
+            window.set_visible(true);
+            window.set_title("Keal");
+            window.set_decorations(false);
+            window.focus_window();
+
+            // entries are about as high as their text, which is a bit more than the font size
+            let monitor_width = window.current_monitor().map(|m| m.size().to_logical::<f32>(m.scale_factor()).width);
+            let row_height = (config.font_size*1.25).max(config.icon_size) + 2.0*config.density.row_padding();
+            let (width, height) = keal::arguments::arguments().window_size((1920.0/3.0, 1080.0/2.0), (config.font_size*3.25).ceil(), row_height, monitor_width);
+            let _ = window.request_inner_size(LogicalSize::new(width, height));
+
+            if unmanaged {
+                // without a window manager, nothing places the window or gives it the focus
+                if let Some(monitor) = window.current_monitor() {
+                    let size = LogicalSize::new(width, height).to_physical::<i32>(monitor.scale_factor());
+                    let (position, monitor_size) = (monitor.position(), monitor.size());
+                    window.set_outer_position(PhysicalPosition::new(
+                        position.x + (monitor_size.width as i32 - size.width)/2,
+                        match config.position {
+                            WindowPosition::Center => position.y + (monitor_size.height as i32 - size.height)/2,
+                            WindowPosition::TopBar => position.y,
+                            WindowPosition::BottomBar => position.y + monitor_size.height as i32 - size.height
+                        }
+                    ));
+                }
+            }
+            if config.grab_keyboard || unmanaged {
+                focus::grab_keyboard(&window);
+            }
+
+            let context = softbuffer::Context::new(window.clone()).unwrap();
+
+            let mut cache = pts::Cache::new();
+            let mut pixmap = Pixmap::new(1, 1).unwrap();
+
+            keal::log_time("loading font");
+            let mut rc = cache.render_context(pixmap.as_pixmap_mut());
+            let text = rc.text();
+            let font = text.font_family(&config.font).unwrap_or_else(|| {
+                eprintln!("couldn't find find the font `{}`, falling back on default font", config.font);
+                FontFamily::SYSTEM_UI
+            });
+
+            keal::log_time("initializing keal state");
+            let mut keal = ui::Keal::new(&mut rc, &window, font, theme, height as f64, proxy.clone());
+            keal.on_scale_changed(window.scale_factor());
+
+            let state = State {
+                cache,
+                pixmap,
+                keal,
+                theme,
+                animation: Animation::new(config.animation_ms),
+                accessibility,
+                ui_state: UiState { 
+                    screen_width: 1.0, screen_height: 1.0, scale: window.scale_factor(),
+                    mouse_pos: LogicalPosition::new(0.0, 0.0), ctrl: false, shift: false, alt: false
+                }
+            };
+
+            // window.set_ime_allowed(true);
+
+            (window, context, state)
+        },
+        |_elwt, (window, context, _state)| softbuffer::Surface::new(context, window.clone()).unwrap(),
+    );
+
+    let app = app.with_event_handler(|(window, _context, state), surface, event, elwt| {
+        if state.keal.quit {
+            state.animation.close();
+            if state.animation.closed() {
+                elwt.exit();
+                return;
+            }
+
+            // only keep drawing the closing animation
+            state.keal.damage(window, Damage::Full);
+            if !matches!(event, Event::WindowEvent { event: WindowEvent::RedrawRequested, .. }) { return }
+        }
+
+        if let Event::WindowEvent { event, .. } = &event {
+            state.accessibility.process_event(window, event);
+        }
+
+        match event {
+            Event::UserEvent(UserEvent::AccessKit(event)) => match event.window_event {
+                accesskit_winit::WindowEvent::InitialTreeRequested => {
+                    state.accessibility.update_if_active(|| state.keal.accessibility_tree());
+                }
+                accesskit_winit::WindowEvent::ActionRequested(request) => {
+                    state.keal.on_accessibility_action(request);
+                    state.keal.damage(window, Damage::Full);
+                }
+                accesskit_winit::WindowEvent::AccessibilityDeactivated => ()
+            }
+            Event::AboutToWait => {
+                if state.animation.running() {
+                    state.keal.damage(window, Damage::Full);
+                }
+
+                let mut rc = state.cache.render_context(state.pixmap.as_pixmap_mut());
+                state.keal.update(&mut rc, window, &state.ui_state);
+                if state.keal.quit {
+                    // the closing animation starts on the next event
+                    window.request_redraw();
+                }
+
+                // sleep until the next event, or until the cursor has to blink or the held key to repeat
+                elwt.set_control_flow(match [state.keal.next_blink(), state.keal.next_repeat()].into_iter().flatten().min() {
+                    Some(deadline) => ControlFlow::WaitUntil(deadline),
+                    None => ControlFlow::Wait
+                });
+            }
+            Event::WindowEvent { window_id, event } if window_id == window.id() => match event {
+                WindowEvent::RedrawRequested => {
+                    let Some(surface) = surface else {
+                        eprintln!("RedrawRequested fired before Resumed or after Suspended");
+                        return;
+                    };
+
+                    redraw(state, window, surface);
+                }
+                WindowEvent::Resized(size) => {
+                    let Some(surface) = surface else {
+                        eprintln!("Resized fired before Resumed or after Suspended");
+                        return;
+                    };
+
+                    if let (Some(width), Some(height)) =
+                    (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+                    {
+                        surface.resize(width, height).unwrap();
+                        state.pixmap = Pixmap::new(width.get(), height.get()).unwrap();
+                        resize(state, size);
+                    }
+                }
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    // winit resizes the window on its own to keep the same logical size
+                    state.ui_state.scale = scale_factor;
+                    state.keal.on_scale_changed(scale_factor);
+                    resize(state, window.inner_size());
+                    state.keal.damage(window, Damage::Full);
+                }
+                WindowEvent::CursorMoved { device_id: _, position }=> {
+                    let pos = position.to_logical(state.ui_state.scale);
+                    state.ui_state.mouse_pos = pos;
+                    state.keal.on_cursor_moved(window, pos);
+                }
+                WindowEvent::MouseInput { device_id: _, state: ElementState::Pressed, button: MouseButton::Left } => {
+                    state.keal.on_left_click(window, &state.ui_state);
+                }
+                WindowEvent::MouseInput { device_id: _, state: ElementState::Pressed, button: MouseButton::Middle } => {
+                    let mut rc = state.cache.render_context(state.pixmap.as_pixmap_mut());
+                    state.keal.on_middle_click(&mut rc, window, &state.ui_state);
+                }
+                WindowEvent::MouseInput { device_id: _, state: ElementState::Pressed, button: MouseButton::Right } => {
+                    state.keal.on_right_click(window);
+                }
+                WindowEvent::MouseWheel { device_id: _, delta: MouseScrollDelta::LineDelta(_, delta), phase: winit::event::TouchPhase::Moved } => {
+                    state.keal.on_scroll(window, &state.ui_state, delta as f64 * LINE_SCROLL);
+                }
+                WindowEvent::MouseWheel { device_id: _, delta: MouseScrollDelta::PixelDelta(delta), phase: _ } => {
+                    // touchpads scroll by physical pixels, in small steps
+                    let delta: LogicalPosition<f64> = delta.to_logical(state.ui_state.scale);
+                    state.keal.on_scroll(window, &state.ui_state, delta.y);
+                }
+                WindowEvent::KeyboardInput { device_id: _, event: key, is_synthetic: _ } => {
+                    if let ElementState::Pressed = key.state {
+                        let mut rc = state.cache.render_context(state.pixmap.as_pixmap_mut());
+                        state.keal.on_key_press(&mut rc, window, &state.ui_state, key);
+                    } else {
+                        state.keal.on_key_release(&key);
+                    }
+                }
+                WindowEvent::ModifiersChanged(modifiers) => {
+                    state.ui_state.ctrl = modifiers.state().control_key();
+                    state.ui_state.shift = modifiers.state().shift_key();
+                    state.ui_state.alt = modifiers.state().alt_key();
+                    state.keal.stop_key_repeat();
+                }
+                WindowEvent::Focused(false) => state.keal.stop_key_repeat(),
+                WindowEvent::CloseRequested => { elwt.exit(); }
+                _ => ()
+            }
+            _ => {}
+        }
+    });
+
+    winit_app::run_app(event_loop, app);
+}
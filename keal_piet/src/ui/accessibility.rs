@@ -0,0 +1,71 @@
+use accesskit::{Action, ActionRequest, Node, NodeId, Role, Tree, TreeUpdate};
+
+use super::{Keal, Message};
+
+const WINDOW: NodeId = NodeId(0);
+const INPUT: NodeId = NodeId(1);
+const LIST: NodeId = NodeId(2);
+
+/// Entries come after the fixed nodes
+fn entry_id(index: usize) -> NodeId {
+    NodeId(3 + index as u64)
+}
+
+fn entry_index(id: NodeId) -> Option<usize> {
+    id.0.checked_sub(3).map(|index| index as usize)
+}
+
+impl Keal {
+    /// Describes the window to screen readers: the search input, and the list of entries with the selected one
+    /// The input keeps the focus, and the selected entry is its active descendant, so that selection changes get announced while typing
+    pub fn accessibility_tree(&self) -> TreeUpdate {
+        let mut nodes = Vec::with_capacity(self.entries.list.len() + 3);
+
+        let mut window = Node::new(Role::Window);
+        window.set_label("Keal");
+        window.set_children(vec![INPUT, LIST]);
+        nodes.push((WINDOW, window));
+
+        let mut input = Node::new(Role::SearchInput);
+        input.set_label(self.input.chip.as_deref().unwrap_or("Search"));
+        input.set_value(self.input.text.as_str());
+        input.set_controls(vec![LIST]);
+        input.add_action(Action::Focus);
+        if !self.entries.list.is_empty() {
+            input.set_active_descendant(entry_id(self.selection.selected()));
+        }
+        nodes.push((INPUT, input));
+
+        let mut list = Node::new(Role::ListBox);
+        list.set_label("Results");
+        list.set_children((0..self.entries.list.len()).map(entry_id).collect::<Vec<_>>());
+        nodes.push((LIST, list));
+
+        for (index, entry) in self.entries.list.iter().enumerate() {
+            let mut option = Node::new(Role::ListBoxOption);
+            option.set_label(entry.name.as_str());
+            if let Some(comment) = &entry.comment {
+                option.set_description(comment.as_str());
+            }
+            option.set_selected(index == self.selection.selected());
+            option.add_action(Action::Click);
+            option.add_action(Action::Focus);
+            nodes.push((entry_id(index), option));
+        }
+
+        TreeUpdate { nodes, tree: Some(Tree::new(WINDOW)), focus: INPUT }
+    }
+
+    /// Focusing an entry selects it, and clicking it launches it
+    pub fn on_accessibility_action(&mut self, request: ActionRequest) {
+        let Some(index) = entry_index(request.target).filter(|&index| index < self.entries.list.len()) else { return };
+
+        match request.action {
+            Action::Focus => self.selection.select(index),
+            Action::Click => {
+                let _ = self.message_sender.send(Message::Launch(Some(self.entries.list[index].label)));
+            }
+            _ => ()
+        }
+    }
+}
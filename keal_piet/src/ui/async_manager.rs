@@ -1,20 +1,28 @@
-use std::sync::{mpsc::{channel, Sender}, Arc, Mutex, MutexGuard};
+use std::{sync::{mpsc::{channel, Sender, RecvTimeoutError}, Arc, Mutex, MutexGuard, OnceLock}, time::Duration};
 
 use nucleo_matcher::{Matcher, pattern::Pattern};
 
-use keal::{plugin::{PluginManager, entry::Label}, log_time};
+use keal::{plugin::{PluginManager, entry::{parse_pattern, Label}}, supervisor::ManagerLock, log_time};
 
-use super::Message;
+use super::{Message, MessageSender};
 
 pub enum Event {
-    UpdateInput(String, bool),
-    Launch(Option<Label>)
+    /// plugin prefix, query, and wether the change comes from the user
+    UpdateInput(Option<String>, String, bool),
+    /// selected entry, and wether its secondary action is launched (with Shift+Enter)
+    Launch(Option<Label>, bool),
+    /// regenerate entries without changing the input, used while plugins are loading
+    Refresh,
+    /// show the next page of entries, see [`PluginManager::next_page`]
+    NextPage
 }
 
 pub struct AsyncManager {
     event_sender: Sender<Event>,
 
     manager: Arc<Mutex<PluginManager>>,
+    /// prefixes and aliases of every plugin, known once plugins are loaded
+    prefixes: Arc<OnceLock<Vec<String>>>,
 
     // data used to regenerate entries
     data: Arc<Mutex<Data>>,
@@ -29,12 +37,13 @@ pub struct Data {
 }
 
 impl AsyncManager {
-    pub fn new(matcher: Matcher, num_entries: usize, sort_by_usage: bool, message_sender: Sender<Message>) -> Self {
+    pub fn new(matcher: Matcher, num_entries: usize, sort_by_usage: bool, message_sender: MessageSender) -> Self {
         let (event_sender, event_rec) = channel();
 
         let this = Self {
             event_sender,
             manager: Default::default(),
+            prefixes: Default::default(),
             data: Arc::new(Mutex::new(Data {
                 matcher,
                 query: String::default(),
@@ -44,51 +53,109 @@ impl AsyncManager {
         };
 
         let manager = this.manager.clone();
+        let prefixes = this.prefixes.clone();
 
         let data = this.data.clone();
         let num_entries = this.num_entries;
         let sort_by_usage = this.sort_by_usage;
 
-        std::thread::spawn(move || {
+        // named so that panics logged by the supervisor say where they come from
+        let thread = std::thread::Builder::new().name("plugin manager".to_owned());
+        thread.spawn(move || {
             {
                 log_time("locking sync manager");
-                let mut manager = manager.lock().unwrap();
+                let mut manager = ManagerLock::new(&manager);
 
                 log_time("loading plugins");
                 manager.load_plugins();
+                let _ = prefixes.set(manager.prefixes().map(str::to_owned).collect());
             }
 
+            let mut loading = false;
+            // event received while checking for newer queries, handled next
+            let mut pending = None;
             loop {
-                let Ok(event) = event_rec.recv() else { break };
+                // while a plugin is loading, refresh entries regularly to animate the placeholder and show the results once they are ready
+                let event = if let Some(event) = pending.take() {
+                    event
+                } else if loading {
+                    match event_rec.recv_timeout(Duration::from_millis(100)) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => Event::Refresh,
+                        Err(RecvTimeoutError::Disconnected) => break
+                    }
+                } else {
+                    let Ok(event) = event_rec.recv() else { break };
+                    event
+                };
 
                 match event {
-                    Event::UpdateInput(s, from_user) => {
-                        let (entries, action) = {
-                            let mut manager = manager.lock().unwrap();
-                            let (new_query, action) = manager.update_input(&s, from_user);
+                    Event::UpdateInput(prefix, query, from_user) => {
+                        let (entries, action, accepted) = {
+                            let mut manager = ManagerLock::new(&manager);
+                            let action = manager.update_input(prefix.as_deref(), &query, from_user);
+                            loading = manager.update_loading();
 
                             let data = &mut *data.lock().unwrap();
-                            data.pattern.reparse(&new_query, nucleo_matcher::pattern::CaseMatching::Ignore);
-                            data.query = new_query;
-
-                            let entries = manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage);
-                            (entries, action)
+                            parse_pattern(&mut data.pattern, &query);
+                            data.query = query;
+
+                            // when typing faster than entries are matched, only the latest query gets matched, instead of queuing a full pass per keystroke
+                            pending = event_rec.try_recv().ok();
+                            let entries = match pending {
+                                Some(Event::UpdateInput(..)) => None,
+                                _ => Some(manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage))
+                            };
+                            let accepted = entries.as_ref().filter(|_| from_user).and_then(|page| manager.auto_accept(&data.query, &page.entries));
+                            (entries, action, accepted)
                         };
 
-                        message_sender.send(Message::Entries(entries)).unwrap();
+                        if let Some(entries) = entries {
+                            message_sender.send(Message::Entries(entries)).unwrap();
+                        }
                         message_sender.send(Message::Action(action)).unwrap();
+                        if let Some(label) = accepted {
+                            message_sender.send(Message::Launch(Some(label))).unwrap();
+                        }
+                    }
+                    Event::Refresh => {
+                        let entries = {
+                            let mut manager = ManagerLock::new(&manager);
+                            loading = manager.update_loading();
+
+                            let data = &mut *data.lock().unwrap();
+                            manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage)
+                        };
+
+                        message_sender.send(Message::Refreshed(entries)).unwrap();
+                    }
+                    Event::NextPage => {
+                        let entries = {
+                            let mut manager = ManagerLock::new(&manager);
+                            manager.next_page();
+
+                            let data = &mut *data.lock().unwrap();
+                            manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage)
+                        };
+
+                        message_sender.send(Message::Refreshed(entries)).unwrap();
                     }
-                    Event::Launch(label) => {
-                        let action = {
-                            let mut manager = manager.lock().unwrap();
-                            let data = data.lock().unwrap();
-                            manager.launch(&data.query, label)
+                    Event::Launch(label, secondary) => {
+                        let (entries, action) = {
+                            let mut manager = ManagerLock::new(&manager);
+                            let data = &mut *data.lock().unwrap();
+                            let action = if secondary { manager.launch_secondary(&data.query, label) } else { manager.launch(&data.query, label) };
+
+                            // the plugin may have updated its entries
+                            let entries = manager.get_entries(&mut data.matcher, &data.pattern, num_entries, sort_by_usage);
+                            (entries, action)
                         };
+                        message_sender.send(Message::Refreshed(entries)).unwrap();
                         message_sender.send(Message::Action(action)).unwrap();
                     }
                 }
             }
-        });
+        }).expect("failed to spawn the plugin manager thread");
 
         this
     }
@@ -97,19 +164,20 @@ impl AsyncManager {
         let _ = self.event_sender.send(event);
     }
 
+    /// Splits a plugin prefix typed at the start of the input, like `app firefox` into `app` and `firefox`
+    /// Always returns `None` until plugins are loaded
+    pub fn split_prefix<'a>(&self, input: &'a str) -> Option<(&'a str, &'a str)> {
+        let (prefix, query) = input.split_once(' ')?;
+        self.prefixes.get()?.iter().any(|p| p == prefix).then_some((prefix, query))
+    }
+
     /// Use the plugin manager mutably and synchronously
     /// WARN: This may change plugin entries! Make sure to send an event to regenerate them in the UI if it does!
     pub fn with_manager<T>(&mut self, mut f: impl FnMut(&mut PluginManager) -> T) -> T {
-        let mut manager = self.manager.lock().unwrap();
+        let mut manager = ManagerLock::new(&self.manager);
         f(&mut manager)
     }
 
-    /// Use the plugin manager immutably and synchronously
-    pub fn use_manager<T>(&self, mut f: impl FnMut(&PluginManager) -> T) -> T {
-        let manager = self.manager.lock().unwrap();
-        f(&manager)
-    }
-
     /// Use synced data for pattern matching
     /// WARN: Trying to use this data at the same time as the plugin manager is very likely to cause a deadlock!
     pub fn get_data(&self) -> MutexGuard<Data> { self.data.lock().unwrap() }
@@ -1,24 +1,23 @@
-use std::{os::unix::process::CommandExt, sync::mpsc::{channel, Receiver, Sender, TryRecvError}};
+use std::{borrow::Cow, ops::Range, rc::Rc, sync::mpsc::{channel, Receiver, SendError, Sender, TryRecvError}, time::Instant};
 
 use async_manager::Data;
-use fork::{fork, Fork};
 use nucleo_matcher::Matcher;
 
-use keal::{config::{config, Config}, icon::{Icon, IconCache, IconPath}, log_time, plugin::{entry::{Label, OwnedEntry}, Action}};
-use resvg::{tiny_skia::{FilterQuality, Pixmap, PixmapPaint}, usvg::{Size, Transform}};
+use keal::{arguments::arguments, config::{config, Config, EscapeAction, Layout, Wrap}, ellipsis::ellipsize, icon::{raster, IconCache, IconPath}, keybind::{KeyAction, Modifiers}, key_repeat::KeyRepeat, layout_cache::LayoutCache, log_time, launch, match_span::MatchSpan, plugin::{entry::{follow_selection, Label, OwnedEntry, Page}, Action}, selection::Selection};
+use resvg::{tiny_skia::{ColorU8, FilterQuality, IntSize, Pixmap, PixmapPaint}, usvg::Transform};
 use text_input::TextInput;
-use winit::{dpi::PhysicalPosition, event::KeyEvent, keyboard::{KeyCode, PhysicalKey}, window::{CursorIcon, Window}};
-use crate::config::Theme;
+use winit::{dpi::{LogicalPosition, LogicalSize}, event::KeyEvent, event_loop::EventLoopProxy, keyboard::{Key, KeyCode, NamedKey, PhysicalKey}, window::{CursorIcon, Window}};
+use crate::{config::Theme, UserEvent};
 
-use self::{match_span::MatchSpan, async_manager::AsyncManager};
+use self::async_manager::AsyncManager;
 
-use piet_tiny_skia::{self as pts, piet::TextAttribute, AsPixmapMut};
+use piet_tiny_skia::{self as pts, piet::TextAttribute};
 use pts::{TextLayout, piet::{kurbo, FontFamily, Text as TextTrait, TextLayout as TextLayoutTrait, TextLayoutBuilder as TextLayoutBuilderTrait, RenderContext as RenderContextTrait}};
 
 pub type RenderContext<'a> = pts::RenderContext<'a, pts::tiny_skia::PixmapMut<'a>>;
 
-mod match_span;
 mod async_manager;
+mod accessibility;
 
 mod text_input;
 
@@ -26,15 +25,50 @@ pub fn pixels_to_pts(pixel: f64) -> f64 {
     (pixel * 72.0 / 96.0).ceil()
 }
 
+/// Shortens text that doesn't fit in `max_width` when `wrap = ellipsis`, otherwise it is left to wrap
+fn fit_text<'a>(text: &mut impl TextTrait, font: &FontFamily, s: &'a str, max_width: f64) -> Cow<'a, str> {
+    let config = config();
+    match config.wrap {
+        Wrap::Wrap => Cow::Borrowed(s),
+        Wrap::Ellipsis => ellipsize(s, max_width as f32, config.ellipsize_paths, |s| text.new_text_layout(s.to_owned())
+            .font(font.clone(), pixels_to_pts(config.font_size as f64))
+            .build().unwrap()
+            .size().width as f32
+        )
+    }
+}
+
+/// Everything the layouts of an entry depend on, to reuse them when the entry stays the same
+#[derive(PartialEq, Eq, Hash)]
+struct LayoutKey {
+    /// name of the entry, possibly shortened
+    name: String,
+    comment: Option<String>,
+    section: Option<String>,
+    icon: bool,
+    /// bits of the logical width of the screen
+    screen_width: u64,
+    /// highlighted parts of the name
+    spans: Vec<(Range<usize>, bool)>
+}
+
 struct CachedLayout {
     name: TextLayout,
     name_selected: TextLayout,
-    comment: Option<TextLayout>
+    comment: Option<TextLayout>,
+    /// header of the plugin section starting at this entry
+    section: Option<TextLayout>,
+    icon: bool
 }
 
 impl CachedLayout {
     fn max_height(&self) -> f64 {
-        self.name.size().height.max(self.comment.as_ref().map(|x| x.size().height).unwrap_or(0.0))
+        let icon_height = if self.icon { config().icon_size as f64 } else { 0.0 };
+        self.name.size().height.max(self.comment.as_ref().map(|x| x.size().height).unwrap_or(0.0)).max(icon_height)
+    }
+
+    fn section_height(&self) -> f64 {
+        self.section.as_ref().map(|x| x.size().height + 10.0).unwrap_or(0.0)
     }
 }
 
@@ -42,24 +76,26 @@ impl CachedLayout {
 struct Entries {
     list: Vec<OwnedEntry>,
     /// info for entry.name and entry.comment (optional)
-    wrap_info: Vec<CachedLayout>,
+    wrap_info: Vec<Rc<CachedLayout>>,
     total_height: f64
 }
 
 impl Entries {
-    fn new(list: Vec<OwnedEntry>, rc: &mut RenderContext, theme: &Theme, font: &FontFamily, data: &mut Data) -> Self {
+    fn new(list: Vec<OwnedEntry>, rc: &mut RenderContext, scale: f64, theme: &Theme, font: &FontFamily, cache: &mut LayoutCache<LayoutKey, Rc<CachedLayout>>, data: &mut Data) -> Self {
         let mut this = Self {
             list,
             wrap_info: Vec::new(),
             total_height: 0.0
         };
 
-        this.recalculate(rc, theme, font, data);
+        this.recalculate(rc, scale, theme, font, cache, data);
         this
     }
 
     /// call this when the screen width changes
-    fn recalculate(&mut self, rc: &mut RenderContext, theme: &Theme, font: &FontFamily, data: &mut Data) {
+    /// `scale` is the scale factor of the window, to convert the width of the render target to logical pixels
+    /// Layouts of entries that didn't change are taken from `cache`
+    fn recalculate(&mut self, rc: &mut RenderContext, scale: f64, theme: &Theme, font: &FontFamily, cache: &mut LayoutCache<LayoutKey, Rc<CachedLayout>>, data: &mut Data) {
         let config = config();
 
         let mut buf = vec![];
@@ -67,46 +103,67 @@ impl Entries {
         self.total_height = 0.0;
         self.wrap_info.clear();
         self.wrap_info.extend(self.list.iter().map(|entry| {
-            let icon_width = entry.icon.as_ref().map(|_| config.font_size as f64 + 4.0).unwrap_or_default();
+            let icon_width = entry.icon.as_ref().map(|_| (config.icon_size + config.icon_padding) as f64).unwrap_or_default();
 
-            let screen_width = rc.target().width() as f64;
+            let screen_width = rc.target().width() as f64 / scale;
 
             let text = rc.text();
 
-            let mut name = text.new_text_layout(entry.name.clone())
-                .max_width(screen_width/2.0 - icon_width)
-                .font(font.clone(), pixels_to_pts(config.font_size as f64));
-            
-            let mut name_selected = text.new_text_layout(entry.name.clone())
-                .max_width(screen_width/2.0 - icon_width)
-                .font(font.clone(), pixels_to_pts(config.font_size as f64));
-
-            for (span, highlighted) in MatchSpan::new(&entry.name, &mut data.matcher, &data.pattern, &mut buf) {
-                let (color, color_selected) = match highlighted {
-                    false => (theme.text, theme.text),
-                    true => (theme.matched_text, theme.selected_matched_text)
-                };
+            let name_text = fit_text(text, font, &entry.name, screen_width/2.0 - icon_width).into_owned();
+            let spans: Vec<_> = MatchSpan::new(&name_text, &mut data.matcher, &data.pattern, &mut buf).collect();
 
-                name = name.range_attribute(span.clone(), TextAttribute::TextColor(color));
-                name_selected = name_selected.range_attribute(span, TextAttribute::TextColor(color_selected));
-            }
+            let key = LayoutKey {
+                name: name_text.clone(),
+                comment: entry.comment.clone(),
+                section: entry.section.clone(),
+                icon: entry.icon.is_some(),
+                screen_width: screen_width.to_bits(),
+                spans: spans.clone()
+            };
 
-            let name = name.build().unwrap();
-            let name_selected = name_selected.build().unwrap();
+            let layout = cache.get_or_insert_with(key, || {
+                let mut name = text.new_text_layout(name_text.clone())
+                    .max_width(screen_width/2.0 - icon_width)
+                    .font(font.clone(), pixels_to_pts(config.font_size as f64));
 
-            let name_size = name.size();
+                let mut name_selected = text.new_text_layout(name_text.clone())
+                    .max_width(screen_width/2.0 - icon_width)
+                    .font(font.clone(), pixels_to_pts(config.font_size as f64));
 
-            let comment_width = screen_width - name_size.width - icon_width - 10.0 - 20.0 - 10.0; // this removes: name left padding, name-comment inner padding, comment right padding
-            let comment = entry.comment.as_ref()
-                .map(|comment| text.new_text_layout(comment.clone())
-                    .max_width(comment_width)
-                    .font(font.clone(), pixels_to_pts(config.font_size as f64))
-                    .text_color(theme.comment)
-                    .build().unwrap());
-            
-            let layout = CachedLayout { name, name_selected, comment };
+                for (span, highlighted) in spans {
+                    let (color, color_selected) = match highlighted {
+                        false => (theme.text, theme.text),
+                        true => (theme.matched_text, theme.selected_matched_text)
+                    };
 
-            self.total_height += layout.max_height() + 26.0;
+                    name = name.range_attribute(span.clone(), TextAttribute::TextColor(color));
+                    name_selected = name_selected.range_attribute(span, TextAttribute::TextColor(color_selected));
+                }
+
+                let name = name.build().unwrap();
+                let name_selected = name_selected.build().unwrap();
+
+                let name_size = name.size();
+
+                let comment_width = screen_width - name_size.width - icon_width - 10.0 - 20.0 - 10.0; // this removes: name left padding, name-comment inner padding, comment right padding
+                let comment = entry.comment.as_ref()
+                    .map(|comment| fit_text(text, font, comment, comment_width).into_owned())
+                    .map(|comment| text.new_text_layout(comment)
+                        .max_width(comment_width)
+                        .font(font.clone(), pixels_to_pts(config.font_size as f64))
+                        .text_color(theme.comment)
+                        .build().unwrap());
+
+                let section = entry.section.as_ref()
+                    .map(|section| text.new_text_layout(section.clone())
+                        .font(font.clone(), pixels_to_pts(config.font_size as f64 * 0.85))
+                        .text_color(theme.comment)
+                        .build().unwrap());
+
+                Rc::new(CachedLayout { name, name_selected, comment, section, icon: entry.icon.is_some() })
+            }).clone();
+
+            self.total_height += layout.section_height() + layout.max_height() + 2.0*config.density.row_padding() as f64;
 
             layout
         }));
@@ -118,51 +175,129 @@ pub struct Keal {
     input: text_input::TextInput,
 
     scroll: f64,
-
-    selected: usize,
-    hovered_choice: Option<usize>,
+    /// index of the first entry shown with the horizontal layout, which scrolls by entries instead of pixels
+    first_shown: usize,
+
+    selection: Selection,
+    /// when enabled, typing a digit selects the corresponding entry instead of being inserted in the query
+    jump_mode: bool,
+    /// the entry being launched was middle-clicked, so keal stays open after the action
+    keep_open: bool,
+    /// the physical key moving the selection that is held down
+    key_repeat: KeyRepeat<(PhysicalKey, KeyAction)>,
+    damage: Damage,
 
     theme: &'static Theme,
 
-    rendered_icons: std::collections::HashMap<IconPath, Option<Pixmap>>,
+    /// icons are rendered at the scale of the window, so they need to be rendered again when it changes
+    rendered_icons: std::collections::HashMap<IconPath, RenderedIcon>,
+    /// layouts of recently shown entries, which often come back after the next keystroke
+    layout_cache: LayoutCache<LayoutKey, Rc<CachedLayout>>,
+    /// scale factor of the window, everything else is in logical pixels
+    scale: f64,
+    /// height the window was opened with, which `auto_shrink` never goes above
+    max_height: f64,
 
     pub quit: bool,
 
     // -- Data state --
-    icons: IconCache,
+    /// asks the icon worker to render an icon at the given scale
+    icon_requests: Sender<(IconPath, f32)>,
     font: FontFamily,
 
     entries: Entries,
     manager: AsyncManager,
 
-    message_sender: Sender<Message>,
+    message_sender: MessageSender,
     message_rec: Receiver<Message>
 }
 
+/// Icons are loaded and rasterized by the icon worker, so that it doesn't hold up drawing
+enum RenderedIcon {
+    /// requested from the icon worker, drawn as a placeholder in the meantime
+    Pending,
+    Failed,
+    Ready(Pixmap)
+}
+
+/// Loads an icon at the size it's drawn at, `scale` being the scale factor of the window
+fn rasterize_icon(icon_cache: &IconCache, icon: &IconPath, scale: f32) -> Option<Pixmap> {
+    let raster = raster::load(icon_cache, icon, (config().icon_size * scale).ceil() as u32)?;
+
+    // tiny-skia works with premultiplied alpha
+    let pixels = raster.pixels.chunks_exact(4)
+        .flat_map(|pixel| {
+            let color = ColorU8::from_rgba(pixel[0], pixel[1], pixel[2], pixel[3]).premultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    Pixmap::from_vec(pixels, IntSize::from_wh(raster.width, raster.height)?)
+}
+
+/// Sends messages to the ui, and wakes up the event loop so that they're handled right away
+#[derive(Clone)]
+pub struct MessageSender {
+    sender: Sender<Message>,
+    proxy: EventLoopProxy<UserEvent>
+}
+
+impl MessageSender {
+    pub fn send(&self, message: Message) -> Result<(), Box<SendError<Message>>> {
+        self.sender.send(message)?;
+        let _ = self.proxy.send_event(UserEvent::Wake);
+        Ok(())
+    }
+}
+
+/// Parts of the window that changed since the last frame, so that only those are drawn again
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Damage {
+    /// nothing changed, the last frame can be shown again
+    #[default]
+    None,
+    /// only the search bar changed, like when the cursor blinks
+    Input,
+    /// everything has to be drawn again
+    Full
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     // UI events
     Launch(Option<Label>),
+    /// launch the secondary action of the entry, from Shift+Enter
+    LaunchSecondary(Label),
+    /// launch without closing, from a middle-click
+    LaunchAndStay(Label),
 
     // Worker events
-    IconCacheLoaded(IconCache),
-    Entries(Vec<OwnedEntry>),
+    /// `scale` is the one the icon was requested at, the icon is dropped if it changed since
+    RenderedIcon { path: IconPath, scale: f32, pixmap: Option<Pixmap> },
+    Entries(Page),
+    /// entries regenerated without the query changing, which keep the selected entry selected
+    Refreshed(Page),
     Action(Action)
 }
 
 impl Keal {
-    pub fn new(rc: &mut RenderContext, font: FontFamily, theme: &'static Theme) -> Self {
+    pub fn new(rc: &mut RenderContext, window: &Window, font: FontFamily, theme: &'static Theme, max_height: f64, proxy: EventLoopProxy<UserEvent>) -> Self {
         log_time("initializing app");
 
         let config = config();
 
-        let (message_sender, message_rec) = channel();
+        let (sender, message_rec) = channel();
+        let message_sender = MessageSender { sender, proxy };
 
+        // requests sent while the icon cache loads wait in the channel
+        let (icon_requests, requests) = channel::<(IconPath, f32)>();
         {
             let message_sender = message_sender.clone();
             std::thread::spawn(move || {
-                let icon_cache = IconCache::new(&config.icon_theme);
-                let _ = message_sender.send(Message::IconCacheLoaded(icon_cache));
+                let icon_cache = IconCache::new(&config.icon_theme, config.icon_size);
+                for (path, scale) in requests {
+                    let pixmap = rasterize_icon(&icon_cache, &path, scale);
+                    if message_sender.send(Message::RenderedIcon { path, scale, pixmap }).is_err() { break }
+                }
             });
         }
 
@@ -171,14 +306,21 @@ impl Keal {
         log_time("finished initializing");
 
         let mut this = Keal {
-            input: TextInput::new(rc, config, theme, font.clone()),
+            input: TextInput::new(rc, window, config, theme, font.clone()),
             scroll: 0.0,
-            selected: 0,
-            hovered_choice: None,
+            first_shown: 0,
+            selection: Selection::default(),
+            jump_mode: false,
+            keep_open: false,
+            key_repeat: KeyRepeat::default(),
+            damage: Damage::Full,
             rendered_icons: Default::default(),
+            layout_cache: LayoutCache::new(512),
+            scale: 1.0,
+            max_height,
             quit: false,
             theme,
-            icons: Default::default(),
+            icon_requests,
             font,
             entries: Default::default(),
             manager,
@@ -189,7 +331,36 @@ impl Keal {
         this
     }
 
+    /// Requests a redraw, of at least the given part of the window
+    pub fn damage(&mut self, window: &Window, damage: Damage) {
+        self.damage = self.damage.max(damage);
+        window.request_redraw();
+    }
+
+    /// What changed since the last frame, which is considered drawn afterwards
+    pub fn take_damage(&mut self) -> Damage {
+        match std::mem::take(&mut self.damage) {
+            // the input shares its line with the entries, which drawing it alone would erase
+            Damage::Input if config().layout == Layout::Horizontal => Damage::Full,
+            damage => damage
+        }
+    }
+
+    /// When the window has to be drawn again for the cursor to blink, if it has to
+    pub fn next_blink(&self) -> Option<Instant> {
+        self.input.next_blink(self.theme)
+    }
+
+    /// Only draws the search bar, over what was there before
+    pub fn render_input(&mut self, rc: &mut RenderContext) {
+        self.input.render(rc, config(), self.theme);
+    }
+
     pub fn render(&mut self, ui_state: &super::UiState, rc: &mut RenderContext) {
+        if config().layout == Layout::Horizontal {
+            return self.render_horizontal(ui_state, rc)
+        }
+
         let entries = &self.entries;
         let theme = &self.theme;
         let config = config();
@@ -199,14 +370,19 @@ impl Keal {
         let search_bar_height = (config.font_size as f64 * 3.25).ceil();
         let mouse = ui_state.mouse_pos;
 
-        self.hovered_choice = None;
+        self.selection.clear_hover();
 
         let mut offset_y = search_bar_height - self.scroll;
 
         for (index, (entry, wrap_info)) in entries.list.iter().zip(entries.wrap_info.iter()).enumerate() {
+            // section headers aren't entries, so they can't be hovered or selected
+            if let Some(section) = &wrap_info.section {
+                rc.draw_text(section, (10.0, offset_y + 5.0));
+                offset_y += wrap_info.section_height();
+            }
 
             let max_height = wrap_info.max_height();
-            let next_offset_y = offset_y + max_height + 26.0;
+            let next_offset_y = offset_y + max_height + 2.0*config.density.row_padding() as f64;
 
             if next_offset_y < search_bar_height { 
                 offset_y = next_offset_y;
@@ -214,11 +390,13 @@ impl Keal {
             }
             if offset_y > ui_state.screen_height { break }
 
-            let selected = self.selected == index;
+            let selected = self.selection.selected() == index;
 
             let mut rectangle_color = theme.choice_background;
-            if mouse.y >= offset_y && mouse.y < next_offset_y {
-                self.hovered_choice = Some(index);
+            if config.mouse_enabled && mouse.y >= offset_y && mouse.y < next_offset_y {
+                self.selection.hover(index);
+            }
+            if self.selection.hovered() == Some(index) {
                 rectangle_color = theme.hovered_choice_background;
             }
             if selected { rectangle_color = theme.selected_choice_background; } 
@@ -227,60 +405,61 @@ impl Keal {
 
             let mut icon_offset = 10.0;
 
-            if let Some(icon_path) = &entry.icon {
+            if self.jump_mode && index < 10 {
+                let number = rc.text().new_text_layout(((index + 1) % 10).to_string())
+                    .font(self.font.clone(), pixels_to_pts(config.font_size as f64))
+                    .text_color(theme.comment)
+                    .build().unwrap();
+                rc.draw_text(&number, (icon_offset, offset_y + config.density.row_padding() as f64));
+                icon_offset += config.font_size as f64;
+            }
+
+            if let Some(IconPath::Glyph(glyph)) = &entry.icon {
+                let glyph = rc.text().new_text_layout(glyph.clone())
+                    .font(self.font.clone(), pixels_to_pts(config.icon_size as f64))
+                    .text_color(theme.text)
+                    .build().unwrap();
+                rc.draw_text(&glyph, (icon_offset, offset_y + config.density.row_padding() as f64));
+                icon_offset += (config.icon_size + config.icon_padding) as f64;
+            } else if let Some(icon_path) = &entry.icon {
+                // pixmaps are drawn directly on the target, which isn't affected by the scale of the render context
+                let ui_scale = self.scale as f32;
                 let mut draw_rendered = |rendered: &Pixmap| {
-                        let scale = config.font_size / rendered.width() as f32;
+                        let scale = config.icon_size * ui_scale / rendered.width().max(rendered.height()) as f32;
                         let target = rc.target_mut();
                         target.draw_pixmap(
                             0, 0, rendered.as_ref(),
                             &PixmapPaint { quality: FilterQuality::Bilinear, ..Default::default() },
-                            Transform::from_scale(scale, scale).post_concat(Transform::from_translate(icon_offset as f32, offset_y as f32 + 13.0)), None
+                            Transform::from_scale(scale, scale).post_concat(Transform::from_translate(
+                                icon_offset as f32 * ui_scale,
+                                (offset_y as f32 + config.density.row_padding()) * ui_scale
+                            )), None
                         );
-                        icon_offset += config.font_size as f64 + 4.0;
+                        icon_offset += (config.icon_size + config.icon_padding) as f64;
                 };
 
-                match self.rendered_icons.get(icon_path) {
-                    Some(Some(rendered)) => {
-                        draw_rendered(&rendered);
-                    }
-                    Some(None) => (),
-                    None => if let Some(icon) = self.icons.get(icon_path) {
-                        match icon {
-                            Icon::Svg(path) => {
-                                let path = path.clone();
-                                if let Ok(data) = std::fs::read(path) {
-                                        // let _ = message_sender.send(Message::RenderedIcon(RenderedIcon::Failed));
-
-                                    if let Ok(tree) = resvg::usvg::Tree::from_data(
-                                        &data,
-                                        &resvg::usvg::Options { default_size: Size::from_wh(config.font_size, config.font_size).unwrap(), ..Default::default() }
-                                    ) {
-                                        let size = tree.size();
-                                        let mut pixmap = Pixmap::new(size.width() as u32, size.height() as u32).unwrap();
-                                        resvg::render(&tree, Default::default(), &mut pixmap.as_pixmap_mut());
-                                        draw_rendered(&pixmap);
-                                        self.rendered_icons.insert(icon_path.clone(), Some(pixmap));
-                                    } else {
-                                        self.rendered_icons.insert(icon_path.clone(), None);
-                                    };
-                                } else {
-                                    self.rendered_icons.insert(icon_path.clone(), None);
-                                }
-                            } 
-                            Icon::Other(_path) => {
-                                // TODO: Other icons
-                                self.rendered_icons.insert(icon_path.clone(), None);
-                            }
-                        };
+                if !self.rendered_icons.contains_key(icon_path) {
+                    let _ = self.icon_requests.send((icon_path.clone(), ui_scale));
+                    self.rendered_icons.insert(icon_path.clone(), RenderedIcon::Pending);
+                }
+
+                match &self.rendered_icons[icon_path] {
+                    RenderedIcon::Ready(rendered) => draw_rendered(rendered),
+                    RenderedIcon::Failed => (),
+                    RenderedIcon::Pending => {
+                        let size = config.icon_size as f64;
+                        let y = offset_y + config.density.row_padding() as f64;
+                        rc.fill(kurbo::RoundedRect::new(icon_offset, y, icon_offset + size, y + size, size / 4.0), &theme.comment.with_alpha(0.2));
+                        icon_offset += (config.icon_size + config.icon_padding) as f64;
                     }
                 }
             }
 
             let name = if selected { &wrap_info.name_selected } else { &wrap_info.name };
-            rc.draw_text(name, (icon_offset, offset_y + 13.0));
+            rc.draw_text(name, (icon_offset, offset_y + config.density.row_padding() as f64));
 
             if let Some(comment) = &wrap_info.comment {
-                rc.draw_text(comment, (ui_state.screen_width - comment.size().width - 10.0, offset_y + 13.0));
+                rc.draw_text(comment, (ui_state.screen_width - comment.size().width - 10.0, offset_y + config.density.row_padding() as f64));
             }
 
             offset_y = next_offset_y;
@@ -289,95 +468,327 @@ impl Keal {
         self.input.render(rc, config, theme);
     }
 
+    /// Draws the entries after the search bar, on the same line, starting from `first_shown`
+    /// Only their names are shown, like in dmenu
+    fn render_horizontal(&mut self, ui_state: &super::UiState, rc: &mut RenderContext) {
+        let theme = self.theme;
+        let config = config();
+
+        let search_bar_height = (config.font_size as f64 * 3.25).ceil();
+        let input_width = self.input.width.unwrap_or_default();
+        let mouse = ui_state.mouse_pos;
+
+        self.selection.clear_hover();
+        self.input.render(rc, config, theme);
+        rc.fill(kurbo::Rect::new(input_width, 0.0, ui_state.screen_width, search_bar_height), &theme.choice_background);
+
+        let text = |rc: &mut RenderContext, text: String| rc.text().new_text_layout(text)
+            .font(self.font.clone(), pixels_to_pts(config.font_size as f64))
+            .text_color(theme.comment)
+            .build().unwrap();
+        let (left_arrow, right_arrow) = (text(rc, "<".to_owned()), text(rc, ">".to_owned()));
+        let numbers: Vec<_> = (0..10).filter(|_| self.jump_mode).map(|index| text(rc, ((index + 1) % 10).to_string())).collect();
+
+        // room is left on both sides for the arrows showing that there are more entries
+        let arrow_width = config.font_size as f64 * 1.5;
+        let (start, end) = (input_width + arrow_width, ui_state.screen_width - arrow_width);
+
+        let widths: Vec<f64> = self.entries.wrap_info.iter().enumerate()
+            .map(|(index, wrap_info)| {
+                let number_width = numbers.get(index).map(|_| config.font_size as f64).unwrap_or_default();
+                wrap_info.name.size().width + number_width + 20.0
+            })
+            .collect();
+
+        // scroll just enough for the selected entry to be visible
+        let selected = self.selection.selected().min(widths.len().saturating_sub(1));
+        self.first_shown = self.first_shown.min(selected);
+        while self.first_shown < selected && start + widths[self.first_shown..=selected].iter().sum::<f64>() > end {
+            self.first_shown += 1;
+        }
+
+        if self.first_shown > 0 {
+            rc.draw_text(&left_arrow, (input_width + (arrow_width - left_arrow.size().width)/2.0, (search_bar_height - left_arrow.size().height)/2.0));
+        }
+
+        let mut x = start;
+        for (index, wrap_info) in self.entries.wrap_info.iter().enumerate().skip(self.first_shown) {
+            let next_x = x + widths[index];
+            // the first entry is always shown, even if it doesn't fit
+            if next_x > end && index > self.first_shown {
+                rc.draw_text(&right_arrow, (end + (arrow_width - right_arrow.size().width)/2.0, (search_bar_height - right_arrow.size().height)/2.0));
+                break
+            }
+
+            let selected = self.selection.selected() == index;
+
+            let mut rectangle_color = theme.choice_background;
+            if config.mouse_enabled && mouse.y < search_bar_height && mouse.x >= x && mouse.x < next_x {
+                self.selection.hover(index);
+            }
+            if self.selection.hovered() == Some(index) {
+                rectangle_color = theme.hovered_choice_background;
+            }
+            if selected { rectangle_color = theme.selected_choice_background; }
+
+            rc.fill(kurbo::Rect::new(x, 0.0, next_x, search_bar_height), &rectangle_color);
+
+            let mut text_x = x + 10.0;
+            if let Some(number) = numbers.get(index) {
+                rc.draw_text(number, (text_x, (search_bar_height - number.size().height)/2.0));
+                text_x += config.font_size as f64;
+            }
+
+            let name = if selected { &wrap_info.name_selected } else { &wrap_info.name };
+            rc.draw_text(name, (text_x, (search_bar_height - name.size().height)/2.0));
+
+            x = next_x;
+        }
+    }
+
     /// Call this on the event [`WindowEvent::Resized`]
     pub fn on_resize(&mut self, rc: &mut RenderContext) {
+        self.damage = Damage::Full;
+        // like dmenu, the input takes a third of the bar
+        let screen_width = rc.target().width() as f64 / self.scale;
+        self.input.width = (config().layout == Layout::Horizontal).then_some(screen_width / 3.0);
         let data = &mut *self.manager.get_data();
-        self.entries.recalculate(rc, self.theme, &self.font, data);
+        self.entries.recalculate(rc, self.scale, self.theme, &self.font, &mut self.layout_cache, data);
+    }
+
+    /// Call this on the event [`WindowEvent::ScaleFactorChanged`], before resizing
+    pub fn on_scale_changed(&mut self, scale: f64) {
+        self.scale = scale;
+        self.rendered_icons.clear();
     }
 
     /// Call this on the event [`WindowEvent::KeyboardInput`]
     pub fn on_key_press(&mut self, rc: &mut RenderContext, window: &Window, ui_state: &crate::UiState, key: KeyEvent) {
-        window.request_redraw();
+        self.damage(window, Damage::Full);
 
         let config = config();
-        if self.input.on_key_press(&key, ui_state) {
-            self.update_input(rc, config, true);
+
+        let ctrl = ui_state.ctrl;
+
+        // keybinds can name the logical key or the physical one, see `keal::keybind::KeyName`
+        let key_name = match &key.logical_key {
+            Key::Character(c) => Some(Cow::Borrowed(c.as_str())),
+            Key::Named(named) => Some(Cow::Owned(format!("{named:?}"))),
+            _ => None
+        };
+        let code_name = match key.physical_key {
+            PhysicalKey::Code(code) => Some(format!("{code:?}")),
+            PhysicalKey::Unidentified(_) => None
+        };
+        let modifiers = Modifiers { ctrl, shift: ui_state.shift, alt: ui_state.alt };
+        // with the horizontal layout, Left and Right move the selection once the cursor is at the end of the query, like in dmenu
+        let horizontal_action = match key.logical_key {
+            Key::Named(NamedKey::ArrowRight) => Some(KeyAction::SelectNext),
+            Key::Named(NamedKey::ArrowLeft) if self.selection.selected() > 0 => Some(KeyAction::SelectPrevious),
+            _ => None
+        }.filter(|_| config.layout == Layout::Horizontal && self.input.cursor_at_end());
+        let action = config.keybinds.action(modifiers, key_name.as_deref(), code_name.as_deref()).or(horizontal_action).or(match key.logical_key {
+            // logical keys, so that the keypad arrows (without num lock) work like the main ones
+            Key::Named(NamedKey::ArrowDown) => Some(KeyAction::SelectNext),
+            Key::Named(NamedKey::ArrowUp) => Some(KeyAction::SelectPrevious),
+            _ => None
+        });
+
+        // holding a key that moves the selection repeats at the configured rate, see `Keal::update`
+        if key.repeat && matches!(action, Some(KeyAction::SelectNext | KeyAction::SelectPrevious)) { return }
+
+        if action == Some(KeyAction::JumpMode) {
+            self.jump_mode = !self.jump_mode;
+            return
+        }
+        if action == Some(KeyAction::NextPage) {
+            self.manager.send(async_manager::Event::NextPage);
+            return
         }
 
-        // TODO: Refactor
-        let snap_selected_to_edge = |this: &mut Keal| { // returns the
-            let search_bar_height = (config.font_size as f64 * 3.25).ceil();
-            let mut offset_y = 0.0;
-            for (index, wrap_info) in this.entries.wrap_info.iter().enumerate() {
-                let max_height = wrap_info.max_height();
-
-                if index == this.selected {
-                    this.scroll = this.scroll.clamp(
-                        offset_y - ui_state.screen_height + search_bar_height + max_height + 26.0,
-                        offset_y
-                    );
-                    break;
-                }
+        // custom keybinds, Ctrl+1 picks the selection and exits with 10, Ctrl+2 with 11, etc, like rofi's kb-custom-N
+        const DIGITS: [KeyCode; 9] = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9];
+        if let PhysicalKey::Code(code) = key.physical_key {
+            if let Some(n) = DIGITS.iter().position(|&d| d == code).filter(|_| ctrl && arguments().dmenu) {
+                keal::set_exit_code(10 + n as i32);
+                let _ = self.message_sender.send(Message::Launch(self.entries.list.get(self.selection.selected()).map(|e| e.label)));
+                return
+            }
+        }
 
-                offset_y += max_height + 26.0;
+        // digits are taken before the input sees them, other keys go to the input
+        if let Some(digit) = key.text.as_ref().and_then(|t| t.chars().next()).and_then(|c| c.to_digit(10)).filter(|_| self.jump_mode) {
+            let index = (digit as usize + 9) % 10; // 1 is the first entry, 0 the tenth
+            if index < self.entries.list.len() {
+                self.selection.select(index);
+                self.snap_selected_to_edge(ui_state.screen_height);
             }
-        };
+            return
+        }
 
-        let ctrl = ui_state.ctrl;
+        if horizontal_action.is_none() && self.input.on_key_press(&key, ui_state) {
+            self.jump_mode = false; // editing the query leaves jump mode
+            self.update_input(rc, config, true);
+        }
+        self.input.sync_primary();
 
-        let PhysicalKey::Code(keycode) = key.physical_key else { return };
+        if let Some(action @ (KeyAction::SelectNext | KeyAction::SelectPrevious)) = action {
+            self.move_selection(action, ui_state.screen_height);
+            self.key_repeat.press((key.physical_key, action));
+            return
+        }
 
-        match (keycode, ctrl) {
-            (KeyCode::Escape, _) => self.quit = true,
-            (KeyCode::Enter, _) => {
-                let _ = self.message_sender.send(Message::Launch(Some(self.entries.list[self.selected].label)));
+        // logical keys, so that numpad Enter works like the main one
+        let Key::Named(named) = key.logical_key else { return };
+
+        match named {
+            NamedKey::Escape => {
+                // the first escape only clears the query and exits plugin mode
+                if config.escape_action == EscapeAction::ClearThenClose && (!self.input.text.is_empty() || self.input.chip.is_some()) {
+                    self.input.text.clear();
+                    self.input.chip = None;
+                    self.update_input(rc, config, true);
+                } else {
+                    self.quit = true;
+                }
+            }
+            NamedKey::Enter if ui_state.shift && arguments().dmenu => {
+                // print the query as typed, even if an entry matches
+                let _ = self.message_sender.send(Message::Launch(None));
             }
-            (KeyCode::ArrowDown, _) | (KeyCode::KeyJ, true) | (KeyCode::KeyN, true) => {
-                self.selected += 1;
-                self.selected = self.selected.min(self.entries.list.len().saturating_sub(1));
-                snap_selected_to_edge(self);
+            NamedKey::Enter if ui_state.shift => {
+                if let Some(entry) = self.entries.list.get(self.selection.selected()) {
+                    let _ = self.message_sender.send(Message::LaunchSecondary(entry.label));
+                }
             }
-            (KeyCode::ArrowUp, _) | (KeyCode::KeyK, true) | (KeyCode::KeyP, true) => {
-                self.selected = self.selected.saturating_sub(1);
-                snap_selected_to_edge(self);
+            NamedKey::Enter => {
+                let _ = self.message_sender.send(Message::Launch(Some(self.entries.list[self.selection.selected()].label)));
             }
             _ => ()
         }
     }
 
-    pub fn on_cursor_moved(&mut self, window: &Window, pos: PhysicalPosition<f64>) {
+    /// Call this when a key is released, to stop repeating it
+    pub fn on_key_release(&mut self, key: &KeyEvent) {
+        if self.key_repeat.held().is_some_and(|&(held, _)| held == key.physical_key) {
+            self.key_repeat.stop();
+        }
+    }
+
+    /// Call this on the event [`WindowEvent::ModifiersChanged`], or when the window loses focus
+    pub fn stop_key_repeat(&mut self) {
+        self.key_repeat.stop();
+    }
+
+    /// When the held key repeats next, if one is held
+    pub fn next_repeat(&self) -> Option<Instant> {
+        self.key_repeat.next_repeat()
+    }
+
+    fn move_selection(&mut self, action: KeyAction, screen_height: f64) {
+        match action {
+            KeyAction::SelectNext => self.selection.select_next(self.entries.list.len()),
+            KeyAction::SelectPrevious => self.selection.select_previous(),
+            KeyAction::JumpMode | KeyAction::NextPage => return
+        }
+        self.snap_selected_to_edge(screen_height);
+    }
+
+    /// Scrolls just enough for the selected entry to be visible
+    fn snap_selected_to_edge(&mut self, screen_height: f64) {
         let config = config();
-        if let Some(_) = self.hovered_choice {
+        let search_bar_height = (config.font_size as f64 * 3.25).ceil();
+        let mut offset_y = 0.0;
+        for (index, wrap_info) in self.entries.wrap_info.iter().enumerate() {
+            let max_height = wrap_info.max_height();
+            let section_height = wrap_info.section_height();
+
+            if index == self.selection.selected() {
+                // keep the section header visible when scrolling up
+                self.scroll = self.scroll.clamp(
+                    offset_y + section_height - screen_height + search_bar_height + max_height + 2.0*config.density.row_padding() as f64,
+                    offset_y
+                );
+                break;
+            }
+
+            offset_y += section_height + max_height + 2.0*config.density.row_padding() as f64;
+        }
+    }
+
+    pub fn on_cursor_moved(&mut self, window: &Window, pos: LogicalPosition<f64>) {
+        let config = config();
+        if !config.mouse_enabled { return }
+
+        self.selection.mouse_moved();
+        if self.selection.hovered().is_some() {
             window.set_cursor(CursorIcon::Pointer);
         }
         self.input.on_cursor_moved(config, window, pos);
-        window.request_redraw();
+        self.damage(window, Damage::Full);
     }
 
     pub fn on_left_click(&mut self, window: &Window, ui_state: &crate::UiState) {
-        if let Some(hovered_choice) = self.hovered_choice {
+        let config = config();
+        if !config.mouse_enabled { return }
+
+        if let Some(hovered_choice) = self.selection.hovered() {
             self.message_sender.send(Message::Launch(Some(self.entries.list[hovered_choice].label)))
                 .expect("message reciever destroyed");
         } 
 
-        let config = config();
         self.input.on_left_click(config, ui_state);
-        window.request_redraw();
+        self.damage(window, Damage::Full);
+    }
+
+    /// Pastes the primary selection in the input, or launches the hovered entry without closing
+    pub fn on_middle_click(&mut self, rc: &mut RenderContext, window: &Window, ui_state: &crate::UiState) {
+        let config = config();
+        if !config.mouse_enabled { return }
+
+        if self.input.on_middle_click(config, ui_state) {
+            self.update_input(rc, config, true);
+        } else if let Some(hovered_choice) = self.selection.hovered() {
+            self.message_sender.send(Message::LaunchAndStay(self.entries.list[hovered_choice].label))
+                .expect("message reciever destroyed");
+        }
+        self.damage(window, Damage::Full);
     }
 
+    /// Selects the hovered entry without launching it
+    pub fn on_right_click(&mut self, window: &Window) {
+        if !config().mouse_enabled { return }
+
+        if let Some(hovered_choice) = self.selection.hovered() {
+            self.selection.select(hovered_choice);
+        }
+        self.damage(window, Damage::Full);
+    }
+
+    /// Scrolls the list by `amount` logical pixels, scaled by the configured scroll speed
     pub fn on_scroll(&mut self, window: &Window, ui_state: &crate::UiState, amount: f64) {
         let config = config();
         let search_bar_height = config.font_size as f64 * 3.25;
 
-        self.scroll -= amount*20.0;
+        self.selection.mouse_moved();
+        self.scroll -= amount * config.scroll_speed as f64;
         self.scroll = self.scroll.clamp(0.0, (self.entries.total_height - ui_state.screen_height + search_bar_height).max(0.0));
-        window.request_redraw();
+        self.damage(window, Damage::Full);
     }
 
     /// Try to call this pretty regularly
-    pub fn update(&mut self, rc: &mut RenderContext, window: &Window) {
+    pub fn update(&mut self, rc: &mut RenderContext, window: &Window, ui_state: &crate::UiState) {
         let config = config();
 
+        if self.input.cursor_blinked(self.theme) {
+            self.damage(window, Damage::Input);
+        }
+
+        if let Some((_, action)) = self.key_repeat.poll() {
+            self.move_selection(action, ui_state.screen_height);
+            self.damage(window, Damage::Full);
+        }
+
         loop {
             let message = match self.message_rec.try_recv() {
                 Ok(message) => message,
@@ -387,66 +798,147 @@ impl Keal {
 
             match message {
                 Message::Launch(selected) => {
-                    self.manager.send(async_manager::Event::Launch(selected));
+                    self.manager.send(async_manager::Event::Launch(selected, false));
+                }
+                Message::LaunchSecondary(selected) => {
+                    self.manager.send(async_manager::Event::Launch(Some(selected), true));
                 }
-                Message::IconCacheLoaded(icon_cache) => {
-                    self.icons = icon_cache;
-                    window.request_redraw();
+                Message::LaunchAndStay(selected) => {
+                    self.keep_open = true;
+                    self.manager.send(async_manager::Event::Launch(Some(selected), false));
+                }
+                Message::RenderedIcon { path, scale, pixmap } => {
+                    if scale != self.scale as f32 { continue }
+
+                    let rendered = match pixmap {
+                        Some(pixmap) => RenderedIcon::Ready(pixmap),
+                        None => RenderedIcon::Failed
+                    };
+                    self.rendered_icons.insert(path, rendered);
+                    self.damage(window, Damage::Full);
                 }
                 Message::Entries(entries) => { 
-                    let data = &mut *self.manager.get_data();
-                    self.entries = Entries::new(entries, rc, self.theme, &self.font, data);
-                    window.request_redraw();
+                    self.set_entries(rc, entries, true);
+                    self.fit_height(window);
+                    self.damage(window, Damage::Full);
+                },
+                Message::Refreshed(entries) => {
+                    self.set_entries(rc, entries, false);
+                    self.fit_height(window);
+                    self.damage(window, Damage::Full);
                 },
-                Message::Action(action) => return self.handle_action(rc, config, action),
+                Message::Action(action) => return self.handle_action(rc, window, config, action),
             };
         }
     }
 }
 
 impl Keal {
+    /// Replaces the entries, keeping the selected entry selected
+    fn set_entries(&mut self, rc: &mut RenderContext, page: Page, query_changed: bool) {
+        let selected = follow_selection(&self.entries.list, &page.entries, self.selection.selected(), query_changed);
+        self.input.set_indicator(rc, config(), self.theme, page.indicator());
+
+        let data = &mut *self.manager.get_data();
+        self.entries = Entries::new(page.entries, rc, self.scale, self.theme, &self.font, &mut self.layout_cache, data);
+        if selected != self.selection.selected() {
+            self.selection.select(selected);
+        }
+    }
+
+    /// With `auto_shrink`, resizes the window to be just as high as the entries, up to its height when it was opened
+    fn fit_height(&self, window: &Window) {
+        let config = config();
+        if !config.auto_shrink { return }
+
+        let search_bar_height = (config.font_size as f64 * 3.25).ceil();
+        let height = (search_bar_height + self.entries.total_height).min(self.max_height).ceil();
+
+        let size = window.inner_size().to_logical::<f64>(self.scale);
+        if (size.height - height).abs() >= 1.0 {
+            let _ = window.request_inner_size(LogicalSize::new(size.width, height));
+        }
+    }
+
     pub fn update_input(&mut self, rc: &mut RenderContext, config: &Config, from_user: bool) {
+        // a plugin prefix followed by a space becomes a chip, the input only keeps the query
+        if self.input.chip.is_none() {
+            if let Some((prefix, query)) = self.manager.split_prefix(&self.input.text) {
+                self.input.chip = Some(prefix.to_owned());
+                self.input.text = query.to_owned();
+            }
+        }
+
         self.input.update_input(rc, config, &self.theme, from_user);
 
         let mut data = self.manager.get_data();
-        self.entries.recalculate(rc, self.theme, &self.font, &mut *data);
+        self.entries.recalculate(rc, self.scale, self.theme, &self.font, &mut self.layout_cache, &mut data);
         drop(data);
 
-        self.manager.send(async_manager::Event::UpdateInput(self.input.text.clone(), from_user));
+        self.manager.send(async_manager::Event::UpdateInput(self.input.chip.clone(), self.input.text.clone(), from_user));
     }
 
-    fn handle_action(&mut self, rc: &mut RenderContext, config: &Config, action: Action) /* -> Command<Message> */ {
+    fn handle_action(&mut self, rc: &mut RenderContext, window: &Window, config: &Config, action: Action) /* -> Command<Message> */ {
+        // middle-clicked entries stay open just like with `--stay-open`
+        let stay_open = arguments().stay_open || std::mem::take(&mut self.keep_open);
+
         match action {
             Action::None => (),
             Action::ChangeInput(new) => {
                 self.manager.with_manager(|m| m.kill());
+                self.input.chip = None;
                 self.input.text = new;
                 self.update_input(rc, config, false);
                 // return text_input::move_cursor_to_end(text_input::Id::new("query_input"));
             }
             Action::ChangeQuery(new) => {
-                let new = self.manager.use_manager(|m| m.current().map(
-                    |plugin| format!("{} {}", plugin.prefix, new) 
-                )).unwrap_or(new);
+                // the plugin chip is kept
                 self.input.text = new;
                 self.update_input(rc, config, false);
             }
-            Action::Exec(mut command) => {
-                let _ = command.0.exec();
+            Action::Invalid(message) => {
+                self.input.set_invalid(rc, config, self.theme, message);
+                self.damage(window, Damage::Full);
+            }
+            Action::Sensitive => {
+                // the plugin chip is kept
+                self.input.wipe();
+                self.update_input(rc, config, false);
+            }
+            Action::Exec(command) if stay_open => {
+                launch::spawn_detached(command);
+                self.reset(rc, config);
+            }
+            Action::Exec(command) => {
+                launch::exec(command);
                 self.quit = true;
             }
             Action::PrintAndClose(message) => {
                 println!("{message}");
-                self.quit = true;
+                if stay_open { self.reset(rc, config) } else { self.quit = true }
             }
-            Action::Fork => match fork().expect("failed to fork") {
-                Fork::Parent(_) => self.quit = true,
-                Fork::Child => ()
+            Action::Fork if stay_open => {
+                self.manager.with_manager(|m| m.detach());
+                self.reset(rc, config);
+            }
+            Action::Fork => {
+                self.manager.with_manager(launch::fork_plugin);
+                self.quit = true;
             }
             Action::WaitAndClose => {
                 self.manager.with_manager(|m| m.wait());
-                self.quit = true;
+                if stay_open { self.reset(rc, config) } else { self.quit = true }
             }
         }
     }
+
+    /// Goes back to an empty query with the input focused, after launching something with `--stay-open`
+    fn reset(&mut self, rc: &mut RenderContext, config: &Config) {
+        self.input.text.clear();
+        self.input.chip = None;
+        self.selection.select(0);
+        self.scroll = 0.0;
+        // not from the user, so that the cursor is put back in the input
+        self.update_input(rc, config, false);
+    }
 }
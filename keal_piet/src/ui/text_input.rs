@@ -1,10 +1,12 @@
+use std::time::Instant;
+
 use super::{pixels_to_pts, RenderContext, RenderContextTrait, TextLayout, TextLayoutBuilderTrait, TextLayoutTrait, TextTrait};
-use piet_tiny_skia::piet::{kurbo::{self, Point}, Color, FontFamily, FontWeight};
+use piet_tiny_skia::piet::{kurbo::{self, Point}, FontFamily, FontWeight};
 
-use keal::config::Config;
-use winit::{dpi::PhysicalPosition, event::KeyEvent, keyboard::{KeyCode, PhysicalKey}, window::Window};
+use keal::{arguments::arguments, config::Config, cursor::CursorBlink, edit_history::{EditHistory, EditKind}, sensitive::{is_sensitive, zeroize}};
+use winit::{dpi::LogicalPosition, event::KeyEvent, keyboard::{Key, KeyCode, NamedKey, PhysicalKey}, raw_window_handle::{HasDisplayHandle, RawDisplayHandle}, window::Window};
 
-use copypasta::{ClipboardContext, ClipboardProvider};
+use copypasta::{wayland_clipboard, x11_clipboard::{Primary, X11ClipboardContext}, ClipboardContext, ClipboardProvider};
 
 use crate::config::Theme;
 
@@ -82,25 +84,63 @@ fn ceil_word_boundary(s: &str, mut index: usize) -> usize {
 pub struct TextInput {
     /// Modifying `input` should call [`Self::update_input`]
     pub text: String,
+    /// Prefix of the selected plugin, shown as a chip before the text
+    /// Modifying `chip` should call [`Self::update_input`]
+    pub chip: Option<String>,
+    /// width of the input with the horizontal layout, where the entries follow it on the same line
+    /// `None` spans the whole window
+    pub width: Option<f64>,
 
     font: FontFamily,
     /// Layout should be modified to reflect `text`
     layout: TextLayout,
     placeholder_layout: TextLayout,
+    /// label left of the chip and the text, which can't be edited
+    prompt_layout: Option<TextLayout>,
+    chip_layout: Option<TextLayout>,
+    /// how many entries are shown out of those that matched, on the right of the input
+    indicator_layout: Option<TextLayout>,
+    /// message of a plugin saying the query is invalid, shown under the input until it changes
+    invalid_layout: Option<TextLayout>,
     /// byte index of the cursor in the text input, None if the input is not selected
     cursor_index: Option<usize>,
-    cursor_tick: usize,
+    cursor: CursorBlink,
+    /// wether the cursor was visible during the last render
+    cursor_drawn: bool,
     /// byte indices of the start and end ranges of the selection
     select_range: Option<(usize, usize)>,
+    history: EditHistory,
 
     /// wether the mouse is hovering over the input
     hovered: bool,
 
-    clipboard: ClipboardContext
+    clipboard: ClipboardContext,
+    /// holds the last selected text, and is pasted with a middle-click, `None` if it couldn't be opened
+    primary: Option<Box<dyn ClipboardProvider>>,
+    /// what was last put in the primary selection, so that it's only set again when the selection changes
+    primary_text: String
+}
+
+/// Opens the primary selection of the display the window is on
+fn open_primary(window: &Window) -> Option<Box<dyn ClipboardProvider>> {
+    match window.display_handle().ok()?.as_raw() {
+        // SAFETY: the display is winit's connection, which lives as long as the window
+        RawDisplayHandle::Wayland(display) => {
+            let (primary, _) = unsafe { wayland_clipboard::create_clipboards_from_external(display.display.as_ptr()) };
+            Some(Box::new(primary))
+        }
+        _ => match X11ClipboardContext::<Primary>::new() {
+            Ok(primary) => Some(Box::new(primary)),
+            Err(e) => {
+                eprintln!("failed to open primary selection: {e}");
+                None
+            }
+        }
+    }
 }
 
 impl TextInput {
-    pub fn new(rc: &mut RenderContext, config: &Config, theme: &Theme, font: FontFamily) -> Self {
+    pub fn new(rc: &mut RenderContext, window: &Window, config: &Config, theme: &Theme, font: FontFamily) -> Self {
         let text = rc.text();
         let layout = text.new_text_layout("").build().unwrap();
         let placeholder_layout = text.new_text_layout(config.placeholder_text.clone())
@@ -108,17 +148,50 @@ impl TextInput {
             .text_color(theme.text)
             .default_attribute(FontWeight::MEDIUM)
             .build().unwrap();
+        let prompt_layout = arguments().prompt().map(|prompt| text.new_text_layout(prompt.to_owned())
+            .font(font.clone(), pixels_to_pts(config.font_size as f64 * 1.25))
+            .text_color(theme.input_prompt)
+            .default_attribute(FontWeight::MEDIUM)
+            .build().unwrap());
 
         Self {
             text: String::new(),
+            chip: None,
+            width: None,
             font,
             layout,
             placeholder_layout,
+            prompt_layout,
+            chip_layout: None,
+            indicator_layout: None,
+            invalid_layout: None,
             cursor_index: Some(0),
-            cursor_tick: 0,
+            cursor: CursorBlink::default(),
+            cursor_drawn: false,
             select_range: None,
+            history: EditHistory::default(),
             hovered: false,
-            clipboard: ClipboardContext::new().unwrap()
+            clipboard: ClipboardContext::new().unwrap(),
+            primary: open_primary(window),
+            primary_text: String::new()
+        }
+    }
+
+    /// Horizontal position of the chip, which is moved right by the prompt
+    fn chip_offset(&self, config: &Config) -> f64 {
+        let left_padding = config.font_size as f64;
+        match &self.prompt_layout {
+            Some(prompt) => left_padding + prompt.size().width + 8.0, // prompt-chip padding
+            None => left_padding
+        }
+    }
+
+    /// Horizontal position of the text, which is moved right by the prompt and the chip
+    fn text_offset(&self, config: &Config) -> f64 {
+        let left_padding = self.chip_offset(config);
+        match &self.chip_layout {
+            Some(chip) => left_padding + chip.size().width + 16.0 + 8.0, // chip inner padding, and chip-text padding
+            None => left_padding
         }
     }
 
@@ -127,16 +200,50 @@ impl TextInput {
 
         let size = config.font_size as f64 * 1.25;
 
-        let left_padding = config.font_size as f64;
+        let left_padding = self.text_offset(config);
         let baseline = (search_bar_height/2.0 - size/2.0).ceil();
 
-        let screen_width = rc.target().width() as f64;
+        let screen_width = self.width.unwrap_or(rc.target().width() as f64);
 
         rc.fill(kurbo::RoundedRect::new(0.0, 0.0, screen_width, search_bar_height, (5.0, 5.0, 0.0, 0.0)), &theme.input_background);
 
+        if let Some(prompt) = &self.prompt_layout {
+            rc.draw_text(prompt, (config.font_size as f64, (search_bar_height/2.0 - prompt.size().height/2.0).ceil()));
+        }
+
+        if let Some(chip) = &self.chip_layout {
+            let x = self.chip_offset(config);
+            let chip_size = chip.size();
+            let y = (search_bar_height/2.0 - chip_size.height/2.0).ceil();
+
+            rc.fill(kurbo::RoundedRect::new(x, y - 2.0, x + chip_size.width + 16.0, y + chip_size.height + 2.0, 5.0), &theme.selected_choice_background);
+            rc.draw_text(chip, (x + 8.0, y));
+        }
+
+        if let Some(invalid) = &self.invalid_layout {
+            rc.stroke(kurbo::RoundedRect::new(0.5, 0.5, screen_width - 0.5, search_bar_height - 0.5, (5.0, 5.0, 0.0, 0.0)), &theme.input_error, 1.0);
+
+            // with the horizontal layout, there is no room under the input, so the message takes the place of the indicator
+            if self.width.is_none() {
+                let height = invalid.size().height + 10.0;
+                rc.fill(kurbo::Rect::new(0.0, search_bar_height, screen_width, search_bar_height + height), &theme.input_background);
+                rc.draw_text(invalid, (config.font_size as f64, search_bar_height + 5.0));
+            }
+        }
+
+        let right = match &self.invalid_layout {
+            Some(invalid) if self.width.is_some() => Some(invalid),
+            _ => self.indicator_layout.as_ref()
+        };
+        if let Some(right) = right {
+            let right_size = right.size();
+            rc.draw_text(right, (screen_width - right_size.width - config.font_size as f64, (search_bar_height/2.0 - right_size.height/2.0).ceil()));
+        }
+
         let layout = if self.text.is_empty() && self.cursor_index.is_none() { &self.placeholder_layout } else { &self.layout };
         rc.draw_text(&layout, (left_padding, baseline));
 
+        self.cursor_drawn = false;
         if let Some((start, end)) = self.select_range {
             let mut rect = layout.rects_for_range(start..end)[0];
             if end == self.text.len() {
@@ -152,14 +259,33 @@ impl TextInput {
                 layout.rects_for_range(cursor_index..cursor_index+1)[0].x0
             };
 
-            let pos = left_padding + cursor_position;
-            rc.stroke(kurbo::Line::new((pos, baseline), Point::new(pos, baseline + size + 5.0)), &Color::WHITE, 1.0);
+            self.cursor_drawn = self.cursor.visible(theme.cursor_blink_ms);
+            if self.cursor_drawn {
+                let pos = left_padding + cursor_position;
+                rc.stroke(kurbo::Line::new((pos, baseline), Point::new(pos, baseline + size + 5.0)), &theme.cursor_color, theme.cursor_width as f64);
+            }
         }
     }
 
-    pub fn on_cursor_moved(&mut self, config: &Config, window: &Window, PhysicalPosition { x: _, y }: PhysicalPosition<f64>) {
+    /// Wether the cursor is after the last character, with nothing selected
+    pub fn cursor_at_end(&self) -> bool {
+        self.select_range.is_none() && self.cursor_index.is_none_or(|index| index == self.text.len())
+    }
+
+    /// When the cursor will blink next, if it is shown
+    pub fn next_blink(&self, theme: &Theme) -> Option<Instant> {
+        if self.select_range.is_some() || self.cursor_index.is_none() { return None }
+        self.cursor.next_blink(theme.cursor_blink_ms)
+    }
+
+    /// Wether the cursor blinked since the last render, meaning the input should be redrawn
+    pub fn cursor_blinked(&self, theme: &Theme) -> bool {
+        self.select_range.is_none() && self.cursor_index.is_some() && self.cursor_drawn != self.cursor.visible(theme.cursor_blink_ms)
+    }
+
+    pub fn on_cursor_moved(&mut self, config: &Config, window: &Window, LogicalPosition { x, y }: LogicalPosition<f64>) {
         let search_bar_height = (config.font_size as f64*3.25).ceil();
-        self.hovered = y >= 0.0 && y < search_bar_height;
+        self.hovered = y >= 0.0 && y < search_bar_height && self.width.is_none_or(|width| x < width);
 
         if self.hovered {
             window.set_cursor(winit::window::CursorIcon::Text);
@@ -169,10 +295,43 @@ impl TextInput {
     }
 
     pub fn on_left_click(&mut self, config: &Config, ui_state: &crate::UiState) {
-        let left_padding = config.font_size as f64;
+        let left_padding = self.text_offset(config);
         if self.hovered {
             let hit = self.layout.hit_test_point((ui_state.mouse_pos.x - left_padding, 0.0).into());
             self.cursor_index = Some(hit.idx);
+            self.cursor.reset();
+            self.history.split();
+        }
+    }
+
+    /// Pastes the primary selection where the input was clicked, like other text fields on Linux
+    /// Returns whether the input was modified, in which case [`Self::update_input`] should be called
+    pub fn on_middle_click(&mut self, config: &Config, ui_state: &crate::UiState) -> bool {
+        if !self.hovered { return false }
+        let Some(pasted) = self.primary.as_mut().and_then(|primary| primary.get_contents().ok()).filter(|text| !text.is_empty()) else { return false };
+
+        let hit = self.layout.hit_test_point((ui_state.mouse_pos.x - self.text_offset(config), 0.0).into());
+        self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, self.cursor_index.unwrap_or(self.text.len()));
+        self.text.insert_str(hit.idx, &pasted);
+        self.cursor_index = Some(hit.idx + pasted.len());
+        self.select_range = None;
+        self.cursor.reset();
+        true
+    }
+
+    /// Puts the selected text in the primary selection, so that it can be pasted elsewhere with a middle-click
+    /// Nothing is put there in a sensitive session
+    pub fn sync_primary(&mut self) {
+        if is_sensitive() { return }
+        let Some((start, end)) = self.select_range else { return };
+        let selected = &self.text[start..end];
+        if selected.is_empty() || selected == self.primary_text { return }
+
+        self.primary_text = selected.to_owned();
+        if let Some(primary) = &mut self.primary {
+            if let Err(e) = primary.set_contents(self.primary_text.clone()) {
+                eprintln!("failed to set primary selection: {e}");
+            }
         }
     }
 
@@ -188,15 +347,56 @@ impl TextInput {
 
             if ctrl {
                 match key.physical_key {
-                    PhysicalKey::Code(KeyCode::KeyA) => self.select_range = Some((0, self.text.len())),
+                    // readline bindings
+                    PhysicalKey::Code(KeyCode::KeyA) => {
+                        *cursor_index = 0;
+                        self.select_range = None;
+                        self.cursor.reset();
+                        self.history.split();
+                    }
+                    PhysicalKey::Code(KeyCode::KeyE) => {
+                        *cursor_index = self.text.len();
+                        self.select_range = None;
+                        self.cursor.reset();
+                        self.history.split();
+                    }
+                    PhysicalKey::Code(KeyCode::KeyU) | PhysicalKey::Code(KeyCode::KeyW) if *cursor_index > 0 => {
+                        let start = if key.physical_key == PhysicalKey::Code(KeyCode::KeyU) {
+                            0
+                        } else {
+                            floor_word_boundary(&self.text, *cursor_index)
+                        };
+
+                        self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
+                        self.text.drain(start..*cursor_index);
+                        *cursor_index = start;
+                        self.select_range = None;
+                        modified = true;
+                    }
                     PhysicalKey::Code(KeyCode::KeyC) => {
                         if let Some((start, end)) = self.select_range {
                             let text = &self.text[start..end];
                             self.clipboard.set_contents(text.to_owned()).unwrap();
                         }
                     }
+                    PhysicalKey::Code(KeyCode::KeyZ) => {
+                        let snapshot = if shift {
+                            self.history.redo(self.chip.as_deref(), &self.text, *cursor_index)
+                        } else {
+                            self.history.undo(self.chip.as_deref(), &self.text, *cursor_index)
+                        };
+
+                        if let Some(snapshot) = snapshot {
+                            self.chip = snapshot.prefix;
+                            self.text = snapshot.text;
+                            *cursor_index = snapshot.cursor;
+                            self.select_range = None;
+                            modified = true;
+                        }
+                    }
                     PhysicalKey::Code(KeyCode::KeyX) => {
                         if let Some((start, end)) = self.select_range {
+                            self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
                             *cursor_index = start; // in case we expanded the selection to the right
                             self.select_range = None;
 
@@ -206,6 +406,11 @@ impl TextInput {
                         }
                     }
                     PhysicalKey::Code(KeyCode::KeyV) => {
+                        let pasted = self.clipboard.get_contents().ok().filter(|text| !text.is_empty());
+                        if self.select_range.is_some() || pasted.is_some() {
+                            self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
+                        }
+
                         if let Some((start, end)) = self.select_range {
                             *cursor_index = start; // in case we expanded the selection to the right
                             self.text.drain(start..end);
@@ -213,19 +418,17 @@ impl TextInput {
                             modified = true;
                         }
 
-                        match self.clipboard.get_contents() {
-                            Ok(text) if !text.is_empty() => {
-                                self.text.insert_str(*cursor_index, &text);
-                                *cursor_index += text.len();
-                                modified = true;
-                            }
-                            _ => (),
+                        if let Some(text) = pasted {
+                            self.text.insert_str(*cursor_index, &text);
+                            *cursor_index += text.len();
+                            modified = true;
                         }
                     }
                     _ => ()
                 }
-            } else if let (PhysicalKey::Code(KeyCode::ArrowLeft), true) = (key.physical_key, *cursor_index > 0) {
-                self.cursor_tick = 0;
+            } else if let (Key::Named(NamedKey::ArrowLeft), true) = (&key.logical_key, *cursor_index > 0) {
+                self.cursor.reset();
+                self.history.split();
                 let old_index = *cursor_index;
 
                 let mut new_index = if ctrl {
@@ -254,8 +457,9 @@ impl TextInput {
                 }
 
                 *cursor_index = new_index;
-            } else if let (PhysicalKey::Code(KeyCode::ArrowRight), true) = (key.physical_key, *cursor_index < self.text.len()) {
-                self.cursor_tick = 0;
+            } else if let (Key::Named(NamedKey::ArrowRight), true) = (&key.logical_key, *cursor_index < self.text.len()) {
+                self.cursor.reset();
+                self.history.split();
                 let old_index = *cursor_index;
 
                 let mut new_index = if ctrl {
@@ -286,16 +490,24 @@ impl TextInput {
                 *cursor_index = new_index;
             } else if let PhysicalKey::Code(KeyCode::Backspace) = key.physical_key {
                 if let Some((start, end)) = self.select_range { // remove selection
+                    self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
                     *cursor_index = start; // in case we expanded the selection to the right
                     self.text.drain(start..end);
                     self.select_range = None;
                 } else if *cursor_index > 0 {
+                    self.history.record(EditKind::Delete, self.chip.as_deref(), &self.text, *cursor_index);
                     *cursor_index = floor_char_boundary(&self.text, *cursor_index);
                     self.text.remove(*cursor_index);
+                } else if self.text.is_empty() && self.chip.is_some() { // backspace on an empty query removes the plugin chip
+                    self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
+                    self.chip = None;
                 }
                 modified = true;
             } else if let Some(text) = &key.text {
                 if !text.contains(|c: char| c == '\n' || c == '\r' || c.is_control()) {
+                    let kind = if self.select_range.is_some() { EditKind::Other } else { EditKind::Insert };
+                    self.history.record(kind, self.chip.as_deref(), &self.text, *cursor_index);
+
                     if let Some((start, end)) = self.select_range { // remove selected text
                         *cursor_index = start;
                         self.text.drain(start..end);
@@ -305,24 +517,34 @@ impl TextInput {
                     self.text.insert_str(*cursor_index, text.as_str());
                     *cursor_index += text.len();
 
-                    self.cursor_tick = 0;
+                    self.cursor.reset();
                     modified = true;
                 }
             }
 
             modified
         } else {
-            self.cursor_tick = 0;
+            self.cursor.reset();
             false
         }
     }
 
+    /// Empties the input and its undo history, zeroing the text they kept, for sensitive sessions
+    /// [`Self::update_input`] should be called afterwards
+    pub fn wipe(&mut self) {
+        zeroize(&mut self.text);
+        zeroize(&mut self.primary_text);
+        self.history.clear();
+        self.select_range = None;
+    }
+
     pub fn update_input(&mut self, rc: &mut RenderContext, config: &Config, theme: &Theme, from_user: bool) {
         match &mut self.cursor_index {
             Some(cursor_index) if from_user => *cursor_index = (*cursor_index).min(self.text.len()),
             cursor_index => *cursor_index = Some(self.text.len())
         }
         self.select_range = None;
+        self.invalid_layout = None;
 
         let rc_text = rc.text();
         let layout = rc_text.new_text_layout(self.text.clone())
@@ -332,5 +554,34 @@ impl TextInput {
             .build().unwrap();
 
         self.layout = layout;
+
+        self.chip_layout = self.chip.as_ref().map(|chip| rc_text.new_text_layout(chip.clone())
+            .font(self.font.clone(), pixels_to_pts(config.font_size as f64))
+            .text_color(theme.text)
+            .default_attribute(FontWeight::MEDIUM)
+            .build().unwrap());
+    }
+
+    /// Shows the message of a plugin saying the query is invalid, until the input changes
+    pub fn set_invalid(&mut self, rc: &mut RenderContext, config: &Config, theme: &Theme, message: String) {
+        self.invalid_layout = Some(rc.text().new_text_layout(message)
+            .font(self.font.clone(), pixels_to_pts(config.font_size as f64))
+            .text_color(theme.input_error)
+            .build().unwrap());
+    }
+
+    /// Shows how many entries were left out, see [`keal::plugin::entry::Page::indicator`]
+    pub fn set_indicator(&mut self, rc: &mut RenderContext, config: &Config, theme: &Theme, indicator: Option<String>) {
+        self.indicator_layout = indicator.map(|indicator| rc.text().new_text_layout(indicator)
+            .font(self.font.clone(), pixels_to_pts(config.font_size as f64))
+            .text_color(theme.comment)
+            .build().unwrap());
+    }
+}
+
+impl Drop for TextInput {
+    fn drop(&mut self) {
+        zeroize(&mut self.text);
+        zeroize(&mut self.primary_text);
     }
 }
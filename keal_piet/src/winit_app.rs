@@ -9,7 +9,7 @@ use winit::window::{Window, WindowAttributes, WindowId};
 
 /// Run a Winit application.
 #[allow(unused_mut)]
-pub(crate) fn run_app(event_loop: EventLoop<()>, mut app: impl ApplicationHandler<()> + 'static) {
+pub(crate) fn run_app<E: 'static>(event_loop: EventLoop<E>, mut app: impl ApplicationHandler<E> + 'static) {
     #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
     event_loop.run_app(&mut app).unwrap();
 
@@ -75,9 +75,9 @@ where
     }
 
     /// Build a new application.
-    pub(crate) fn with_event_handler<F>(self, handler: F) -> WinitApp<T, S, Init, InitSurface, F>
+    pub(crate) fn with_event_handler<F, E>(self, handler: F) -> WinitApp<T, S, Init, InitSurface, F>
     where
-        F: FnMut(&mut T, Option<&mut S>, Event<()>, &ActiveEventLoop),
+        F: FnMut(&mut T, Option<&mut S>, Event<E>, &ActiveEventLoop),
     {
         WinitApp::new(self.init, self.init_surface, handler)
     }
@@ -87,7 +87,6 @@ impl<T, S, Init, InitSurface, Handler> WinitApp<T, S, Init, InitSurface, Handler
 where
     Init: FnMut(&ActiveEventLoop) -> T,
     InitSurface: FnMut(&ActiveEventLoop, &mut T) -> S,
-    Handler: FnMut(&mut T, Option<&mut S>, Event<()>, &ActiveEventLoop),
 {
     /// Create a new application.
     pub(crate) fn new(init: Init, init_surface: InitSurface, event: Handler) -> Self {
@@ -101,12 +100,12 @@ where
     }
 }
 
-impl<T, S, Init, InitSurface, Handler> ApplicationHandler
+impl<T, S, E: 'static, Init, InitSurface, Handler> ApplicationHandler<E>
     for WinitApp<T, S, Init, InitSurface, Handler>
 where
     Init: FnMut(&ActiveEventLoop) -> T,
     InitSurface: FnMut(&ActiveEventLoop, &mut T) -> S,
-    Handler: FnMut(&mut T, Option<&mut S>, Event<()>, &ActiveEventLoop),
+    Handler: FnMut(&mut T, Option<&mut S>, Event<E>, &ActiveEventLoop),
 {
     fn resumed(&mut self, el: &ActiveEventLoop) {
         debug_assert!(self.state.is_none());
@@ -137,6 +136,17 @@ where
         );
     }
 
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: E) {
+        if let Some(state) = self.state.as_mut() {
+            (self.event)(
+                state,
+                self.surface_state.as_mut(),
+                Event::UserEvent(event),
+                event_loop,
+            );
+        }
+    }
+
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         if let Some(state) = self.state.as_mut() {
             (self.event)(
@@ -0,0 +1,70 @@
+#![allow(non_snake_case)]
+
+use keal::{config::{WindowLevel, WindowPosition}, log_time};
+use ui::Keal;
+use raylib::prelude::*;
+
+mod ui;
+mod config;
+
+/// Runs the raylib frontend
+/// Arguments should have been initialized beforehand
+pub fn run() -> anyhow::Result<()> {
+    log_time("reading config");
+
+    let mut theme = config::Theme::default();
+    let config = keal::config::Config::init_launcher(&mut theme);
+
+    log_time("initilizing window");
+
+    set_trace_log_level(TraceLogLevel::Fatal);
+    // let raylib scale the window and what's drawn in it by the scale of the monitor, and follow it when it changes
+    set_config_flags(ConfigFlags::TRANSPARENT | ConfigFlags::HIGHDPI);
+    let row_height = config.font_size.max(config.icon_size) + 2.0*config.density.row_padding();
+    let (width, height) = keal::arguments::arguments().window_size((1920.0/3.0, 1080.0/2.0), (config.font_size*3.25).ceil(), row_height, None);
+    let mut rl = &mut init_window(width as _, height as _, "Keal", 60);
+    set_window_state(rl, WindowFlags::UNDECORATED | WindowFlags::RESIZABLE);
+    // glfw has no way to bypass the window manager, so it can't stay above fullscreen windows
+    if config.window_level != WindowLevel::Normal {
+        set_window_state(rl, WindowFlags::TOPMOST);
+    }
+    // bars span the monitor the window opened on, whose size isn't known before
+    if config.position.is_bar() {
+        let monitor = get_current_monitor(rl);
+        let (monitor_width, monitor_height) = (get_monitor_width(rl, monitor), get_monitor_height(rl, monitor));
+        let origin = get_monitor_position(rl, monitor);
+        let y = match config.position {
+            WindowPosition::BottomBar => origin.y as i32 + monitor_height - height as i32,
+            _ => origin.y as i32
+        };
+        set_window_size(rl, monitor_width, height as _);
+        set_window_position(rl, origin.x as _, y);
+    }
+    // ask for the focus explicitly, in case the window manager doesn't give it to new windows
+    set_window_focused(rl);
+
+    log_time("initilizing font");
+
+    let iosevka = include_bytes!("../../public/iosevka-regular.ttf");
+    let iosevka = load_font_bytes(rl, &iosevka[..]);
+
+    log_time("initializing keal");
+
+    let mut keal = Keal::new(iosevka, height);
+
+    log_time("entering drawing loop");
+
+    keal.update_input(true);
+
+    while !window_should_close(rl) {
+        keal.handle_messages(&mut rl);
+        begin_drawing(rl, |rl| {
+            clear_background(rl, theme.background);
+
+            keal.render(rl, &theme);
+        });
+        keal.update(&mut rl);
+    }
+
+    Ok(())
+}
@@ -1,17 +1,15 @@
-use std::{os::unix::process::CommandExt, sync::mpsc::{channel, Receiver, Sender, TryRecvError}};
+use std::{path::PathBuf, sync::mpsc::{channel, Receiver, Sender, TryRecvError}};
 
-use fork::{fork, Fork};
 use raylib::prelude::*;
 use nucleo_matcher::Matcher;
 use smallvec::SmallVec;
 
-use keal::{config::config, icon::{Icon, IconCache, IconPath}, log_time, plugin::{entry::{Label, OwnedEntry}, Action}};
+use keal::{animation::Animation, arguments::arguments, config::{config, EscapeAction, Layout, Wrap}, ellipsis::ellipsize, icon::{raster, IconCache, IconPath}, keybind::{KeyAction, KeyName, Keybind, Modifiers}, key_repeat::KeyRepeat, layout_cache::LayoutCache, log_time, launch, match_span::MatchSpan, plugin::{entry::{follow_selection, Label, OwnedEntry, Page}, Action}, selection::Selection};
 use text_input::TextInput;
 use crate::config::Theme;
 
-use self::{match_span::MatchSpan, async_manager::AsyncManager};
+use self::async_manager::AsyncManager;
 
-mod match_span;
 mod async_manager;
 
 mod text_input;
@@ -22,6 +20,34 @@ fn is_key_pressed_repeated(rl: &mut Raylib, key: Key) -> bool {
     is_key_pressed(rl, key) || is_key_pressed_again(rl, key)
 }
 
+/// Raylib only sees physical keys named after the US layout, so `key:` and `code:` keybinds both resolve to them
+fn keybind_key(name: &KeyName) -> Option<Key> {
+    let name = match name {
+        KeyName::Key(name) => name.clone(),
+        KeyName::Code(name) => name.strip_prefix("Key").or(name.strip_prefix("Digit")).unwrap_or(name).to_lowercase()
+    };
+
+    Some(match name.as_str() {
+        "a" => Key::A, "b" => Key::B, "c" => Key::C, "d" => Key::D, "e" => Key::E, "f" => Key::F, "g" => Key::G,
+        "h" => Key::H, "i" => Key::I, "j" => Key::J, "k" => Key::K, "l" => Key::L, "m" => Key::M, "n" => Key::N,
+        "o" => Key::O, "p" => Key::P, "q" => Key::Q, "r" => Key::R, "s" => Key::S, "t" => Key::T, "u" => Key::U,
+        "v" => Key::V, "w" => Key::W, "x" => Key::X, "y" => Key::Y, "z" => Key::Z,
+        "0" => Key::Zero, "1" => Key::One, "2" => Key::Two, "3" => Key::Three, "4" => Key::Four,
+        "5" => Key::Five, "6" => Key::Six, "7" => Key::Seven, "8" => Key::Eight, "9" => Key::Nine,
+        "arrowup" => Key::Up, "arrowdown" => Key::Down, "arrowleft" => Key::Left, "arrowright" => Key::Right,
+        "enter" => Key::Enter, "tab" => Key::Tab, "home" => Key::Home, "end" => Key::End, "pageup" => Key::PageUp, "pagedown" => Key::PageDown,
+        _ => return None
+    })
+}
+
+/// Returns the key of the keybind pressed this frame, if any
+fn keybind_pressed(rl: &mut Raylib, binds: &[Keybind], modifiers: Modifiers) -> Option<Key> {
+    binds.iter()
+        .filter(|bind| bind.modifiers == modifiers)
+        .filter_map(|bind| keybind_key(&bind.key))
+        .find(|&key| is_key_pressed(rl, key))
+}
+
 /// order of border radius is: `[top-left, top-right, bot-left, bot-right]`
 fn draw_rectangle_rounded(rl: &mut DrawHandle, x: f32, y: f32, w: f32, h: f32, mut borders: [f32; 4], color: Color) {
     for radius in &mut borders {
@@ -55,9 +81,20 @@ fn draw_rectangle_rounded(rl: &mut DrawHandle, x: f32, y: f32, w: f32, h: f32, m
 
 
 /// Returns a vector of indices (byte offsets) at which the text should wrap, as well as the total height of the text
+/// With `wrap = ellipsis`, the text is instead shortened to a single line
 fn measure_text_wrap(text: &str, max_width: f32, atlas: &TTFCache, font_size: f32, line_height: f32) -> WrapInfo {
     let max_width = max_width.max(font_size*2.0);
 
+    if config().wrap == Wrap::Ellipsis {
+        let text = ellipsize(text, max_width, config().ellipsize_paths, |s| measure_text(atlas, s, font_size).x).into_owned();
+        return WrapInfo {
+            splits: SmallVec::from_slice(&[text.len()]),
+            width: measure_text(atlas, &text, font_size).x,
+            height: font_size,
+            text: Some(text)
+        };
+    }
+
     let mut splits = SmallVec::new();
     let mut height = font_size;
 
@@ -91,13 +128,36 @@ fn measure_text_wrap(text: &str, max_width: f32, atlas: &TTFCache, font_size: f3
 
     let width = if line_start == 0 { running_width } else { max_width };
 
-    WrapInfo { splits, width, height }
+    WrapInfo { splits, width, height, text: None }
+}
+
+/// Wrapped text along with the width it was wrapped at, to reuse measurements between entry updates
+type MeasureCache = LayoutCache<(String, u32), WrapInfo>;
+
+/// [`measure_text_wrap`] with the font and line height of entries, reusing earlier measurements
+fn measure_entry_text(cache: &mut MeasureCache, text: &str, max_width: f32, atlas: &TTFCache) -> WrapInfo {
+    let font_size = config().font_size;
+    cache.get_or_insert_with((text.to_owned(), max_width.to_bits()), || measure_text_wrap(text, max_width, atlas, font_size, 5.0)).clone()
 }
 
+#[derive(Clone)]
 struct WrapInfo {
     splits: SmallVec<[usize; 8]>,
     width: f32,
-    height: f32
+    height: f32,
+    /// shortened text to draw instead of the original one
+    text: Option<String>
+}
+
+/// Height of the plugin section header shown above the entry, if any
+fn section_height(entry: &OwnedEntry) -> f32 {
+    entry.section.as_ref().map(|_| config().font_size + 10.0).unwrap_or(0.0)
+}
+
+/// Height of the tallest of the name, comment and icon of the entry
+fn max_height(entry: &OwnedEntry, wrap_info: &(WrapInfo, Option<WrapInfo>)) -> f32 {
+    let icon_height = entry.icon.as_ref().map(|_| config().icon_size).unwrap_or(0.0);
+    wrap_info.0.height.max(wrap_info.1.as_ref().map(|x| x.height).unwrap_or(0.0)).max(icon_height)
 }
 
 #[derive(Default)]
@@ -109,35 +169,36 @@ struct Entries {
 }
 
 impl Entries {
-    fn new(list: Vec<OwnedEntry>, rl: &mut Raylib, atlas: &TTFCache) -> Self {
+    fn new(list: Vec<OwnedEntry>, rl: &mut Raylib, atlas: &TTFCache, cache: &mut MeasureCache) -> Self {
         let mut this = Self {
             list,
             wrap_info: Vec::new(),
             total_height: 0.0
         };
 
-        this.recalculate(rl, atlas);
+        this.recalculate(rl, atlas, cache);
         this
     }
 
     /// call this when the screen width changes
-    fn recalculate(&mut self, rl: &mut Raylib, font: &TTFCache) {
+    /// Entries that didn't change reuse their measurements from `cache`
+    fn recalculate(&mut self, rl: &mut Raylib, font: &TTFCache, cache: &mut MeasureCache) {
         let config = config();
 
         self.total_height = 0.0;
         self.wrap_info.clear();
         self.wrap_info.extend(self.list.iter().map(|entry| {
-            let icon_width = entry.icon.as_ref().map(|_| config.font_size + 4.0).unwrap_or_default();
+            let icon_width = entry.icon.as_ref().map(|_| config.icon_size + config.icon_padding).unwrap_or_default();
 
-            let name = measure_text_wrap(&entry.name, get_screen_width(rl)/2.0 - icon_width, font, config.font_size, 5.0);
-            let mut max_height = name.height;
+            let name = measure_entry_text(cache, &entry.name, get_screen_width(rl)/2.0 - icon_width, font);
+            let mut max_height = name.height.max(entry.icon.as_ref().map(|_| config.icon_size).unwrap_or(0.0));
 
             let comment_width = get_screen_width(rl) - name.width - icon_width - 10.0 - 20.0 - 10.0; // this removes: name left padding, name-comment inner padding, comment right padding
             let comment = entry.comment.as_ref()
-                .map(|comment| measure_text_wrap(comment, comment_width, font, config.font_size, 5.0))
+                .map(|comment| measure_entry_text(cache, comment, comment_width, font))
                 .inspect(|comment| max_height = max_height.max(comment.height));
 
-            self.total_height += max_height + 20.0;
+            self.total_height += section_height(entry) + max_height + 2.0*config.density.row_padding();
 
             (name, comment)
         }));
@@ -150,15 +211,31 @@ pub struct Keal {
 
     scroll: f32,
 
-    selected: usize,
-    hovered_choice: Option<usize>,
+    selection: Selection,
+    /// used to notice mouse motion, which shows the hover again after keyboard navigation
+    last_mouse_pos: (f32, f32),
+    /// when enabled, typing a digit selects the corresponding entry instead of being inserted in the query
+    jump_mode: bool,
+    /// the entry being launched was middle-clicked, so keal stays open after the action
+    keep_open: bool,
+    /// the key moving the selection that is held down
+    key_repeat: KeyRepeat<(Key, KeyAction)>,
 
     old_screen_width: f32,
+    /// index of the first entry shown with the horizontal layout, which scrolls by entries instead of pixels
+    first_shown: usize,
+    /// height the window was opened with, which `auto_shrink` never goes above
+    max_height: f32,
 
-    rendered_icons: std::collections::HashMap<IconPath, Option<Texture>>,
+    rendered_icons: std::collections::HashMap<IconPath, RenderedIcon>,
+    /// measurements of recently shown entries, which often come back after the next keystroke
+    measure_cache: MeasureCache,
+
+    animation: Animation,
 
     // -- Data state --
-    icons: IconCache,
+    /// asks the icon worker to rasterize an icon
+    icon_requests: Sender<IconPath>,
     font: TrueTypeFontCache,
 
     entries: Entries,
@@ -168,30 +245,51 @@ pub struct Keal {
     message_rec: Receiver<Message>
 }
 
+/// Icons are rasterized by the icon worker, so that it doesn't hold up drawing
+enum RenderedIcon {
+    /// requested from the icon worker, drawn as a placeholder in the meantime
+    Pending,
+    Failed,
+    Ready(Texture)
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     // UI events
     Launch(Option<Label>),
+    /// launch the secondary action of the entry, from Shift+Enter
+    LaunchSecondary(Label),
+    /// launch without closing, from a middle-click
+    LaunchAndStay(Label),
 
     // Worker events
-    IconCacheLoaded(IconCache),
-    Entries(Vec<OwnedEntry>),
+    /// path of the rasterized icon, which textures are loaded from
+    RenderedIcon(IconPath, Option<PathBuf>),
+    Entries(Page),
+    /// entries regenerated without the query changing, which keep the selected entry selected
+    Refreshed(Page),
     Action(Action)
 }
 
 impl Keal {
-    pub fn new(font: TrueTypeFontCache) -> Self {
+    pub fn new(font: TrueTypeFontCache, max_height: f32) -> Self {
         log_time("initializing app");
 
         let config = config();
 
         let (message_sender, message_rec) = channel();
 
+        // requests sent while the icon cache loads wait in the channel
+        let (icon_requests, requests) = channel::<IconPath>();
         {
             let message_sender = message_sender.clone();
             std::thread::spawn(move || {
-                let icon_cache = IconCache::new(&config.icon_theme);
-                let _ = message_sender.send(Message::IconCacheLoaded(icon_cache));
+                let icon_cache = IconCache::new(&config.icon_theme, config.icon_size);
+                for icon_path in requests {
+                    // rendered at twice their size, so that they stay sharp on scaled screens
+                    let path = icon_cache.get(&icon_path).and_then(|icon| raster::cached_png(icon, (config.icon_size * 2.0).ceil() as u32));
+                    if message_sender.send(Message::RenderedIcon(icon_path, path)).is_err() { break }
+                }
             });
         }
 
@@ -202,11 +300,18 @@ impl Keal {
         Keal {
             input: TextInput::default(),
             scroll: 0.0,
-            selected: 0,
-            hovered_choice: None,
+            selection: Selection::default(),
+            last_mouse_pos: (0.0, 0.0),
+            jump_mode: false,
+            keep_open: false,
+            key_repeat: KeyRepeat::default(),
             old_screen_width: 0.0,
+            first_shown: 0,
+            max_height,
             rendered_icons: Default::default(),
-            icons: Default::default(),
+            measure_cache: LayoutCache::new(1024),
+            animation: Animation::new(config.animation_ms),
+            icon_requests,
             font,
             entries: Default::default(),
             manager,
@@ -216,6 +321,10 @@ impl Keal {
     }
 
     pub fn render(&mut self, rl: &mut DrawHandle, theme: &Theme) {
+        if config().layout == Layout::Horizontal {
+            return self.render_horizontal(rl, theme)
+        }
+
         let entries = &self.entries;
         let config = config();
 
@@ -230,15 +339,27 @@ impl Keal {
         let search_bar_height = (config.font_size*3.25).ceil();
         let mouse = get_mouse_pos(rl);
 
-        self.scroll -= get_mouse_wheel_move(rl)*20.0;
+        let wheel = get_mouse_wheel_move(rl);
+        if (mouse.x, mouse.y) != self.last_mouse_pos || wheel != 0.0 {
+            self.last_mouse_pos = (mouse.x, mouse.y);
+            self.selection.mouse_moved();
+        }
+
+        self.scroll -= wheel*20.0*config.scroll_speed;
         self.scroll = self.scroll.clamp(0.0, (self.entries.total_height - get_screen_height(rl) + search_bar_height).max(0.0));
-        self.hovered_choice = None;
+        self.selection.clear_hover();
 
         let mut offset_y = search_bar_height - self.scroll;
 
         for (index, (entry, wrap_info)) in entries.list.iter().zip(entries.wrap_info.iter()).enumerate() {
-            let max_height = wrap_info.0.height.max(wrap_info.1.as_ref().map(|x| x.height).unwrap_or(0.0));
-            let next_offset_y = offset_y + max_height + 20.0;
+            // section headers aren't entries, so they can't be hovered or selected
+            if let Some(section) = &entry.section {
+                draw_text(rl, font, section, vec2(10.0, offset_y + 5.0), font_size, theme.comment);
+                offset_y += section_height(entry);
+            }
+
+            let max_height = max_height(entry, wrap_info);
+            let next_offset_y = offset_y + max_height + 2.0*config.density.row_padding();
 
             if next_offset_y < search_bar_height { 
                 offset_y = next_offset_y;
@@ -246,11 +367,13 @@ impl Keal {
             }
             if offset_y > get_screen_height(rl) { break }
 
-            let selected = self.selected == index;
+            let selected = self.selection.selected() == index;
 
             let mut rectangle_color = theme.choice_background;
-            if mouse.y >= offset_y && mouse.y < next_offset_y {
-                self.hovered_choice = Some(index);
+            if config.mouse_enabled && mouse.y >= offset_y && mouse.y < next_offset_y {
+                self.selection.hover(index);
+            }
+            if self.selection.hovered() == Some(index) {
                 rectangle_color = theme.hovered_choice_background;
             }
             if selected { rectangle_color = theme.selected_choice_background; } 
@@ -259,31 +382,41 @@ impl Keal {
 
             let mut icon_offset = 10.0;
 
-            if let Some(icon_path) = &entry.icon {
-                if let Some(rendered) = self.rendered_icons.get(icon_path) {
-                    if let Some(rendered) = rendered {
-                        draw_texture_ex(rl, rendered, vec2(icon_offset, offset_y + 10.0), 0.0, config.font_size / rendered.width() as f32, Color::WHITE);
-                        icon_offset += config.font_size + 4.0;
+            if self.jump_mode && index < 10 {
+                let number = ((index + 1) % 10).to_string();
+                draw_text(rl, font, &number, vec2(icon_offset, offset_y + config.density.row_padding()), font_size, theme.comment);
+                icon_offset += config.font_size;
+            }
+
+            if let Some(IconPath::Glyph(glyph)) = &entry.icon {
+                draw_text(rl, font, glyph, vec2(icon_offset, offset_y + config.density.row_padding()), config.icon_size, theme.text);
+                icon_offset += config.icon_size + config.icon_padding;
+            } else if let Some(icon_path) = &entry.icon {
+                if !self.rendered_icons.contains_key(icon_path) {
+                    let _ = self.icon_requests.send(icon_path.clone());
+                    self.rendered_icons.insert(icon_path.clone(), RenderedIcon::Pending);
+                }
+
+                let position = vec2(icon_offset, offset_y + config.density.row_padding());
+                match &self.rendered_icons[icon_path] {
+                    RenderedIcon::Ready(rendered) => {
+                        draw_texture_ex(rl, rendered, position, 0.0, config.icon_size / rendered.width().max(rendered.height()) as f32, Color::WHITE);
+                        icon_offset += config.icon_size + config.icon_padding;
+                    }
+                    RenderedIcon::Failed => (),
+                    RenderedIcon::Pending => {
+                        draw_rectangle_rounded(rl, position.x, position.y, config.icon_size, config.icon_size, [config.icon_size / 4.0; 4], Color { a: theme.comment.a / 5, ..theme.comment });
+                        icon_offset += config.icon_size + config.icon_padding;
                     }
-                } else if let Some(icon) = self.icons.get(icon_path) {
-                    match icon {
-                        Icon::Svg(path) | Icon::Other(path) => {
-                            let img = Texture::load(rl, path).unwrap_or_else(|e| {
-                                eprintln!("failed to open icon: {e}");
-                                None
-                            });
-                            let img = img.map(|mut i| { i.set_texture_filter(TextureFilter::Bilinear); i });
-                            self.rendered_icons.insert(icon_path.clone(), img);
-                        }
-                    };
                 }
             }
 
             let mut line_start = 0;
-            let mut name_offset_y = offset_y + 10.0;
+            let mut name_offset_y = offset_y + config.density.row_padding();
 
+            let name = wrap_info.0.text.as_deref().unwrap_or(&entry.name);
             for &line_end in &wrap_info.0.splits {
-                let text = &entry.name[line_start..line_end];
+                let text = &name[line_start..line_end];
 
                 let mut offset = icon_offset;
                 for (span, highlighted) in MatchSpan::new(text, &mut data.matcher, &data.pattern, &mut buf) {
@@ -295,7 +428,7 @@ impl Keal {
                         }
                     };
 
-                    let new_pos = draw_text(rl, font, span, vec2(offset, name_offset_y.ceil()), font_size, color);
+                    let new_pos = draw_text(rl, font, &text[span], vec2(offset, name_offset_y.ceil()), font_size, color);
                     offset = new_pos.x;
                 }
 
@@ -304,10 +437,11 @@ impl Keal {
             }
 
 
-            let mut comment_offset_y = offset_y + 10.0;
+            let mut comment_offset_y = offset_y + config.density.row_padding();
             // fill the whole line up
             if let Some(comment) = &entry.comment {
                 let wrap_info = wrap_info.1.as_ref().unwrap();
+                let comment = wrap_info.text.as_deref().unwrap_or(comment);
 
                 let mut line_start = 0;
                 for &line_end in &wrap_info.splits {
@@ -323,63 +457,268 @@ impl Keal {
         }
 
         self.input.render(rl, font, config, theme);
+
+        // fade the content from the background color
+        let visibility = self.animation.visibility();
+        if visibility < 1.0 {
+            let overlay = Color { a: (theme.background.a as f32 * (1.0 - visibility)) as u8, ..theme.background };
+            draw_rectangle(rl, 0.0, 0.0, get_screen_width(rl), get_screen_height(rl), overlay);
+        }
+    }
+
+    /// Draws the entries after the search bar, on the same line, starting from `first_shown`
+    /// Only their names are shown, like in dmenu
+    fn render_horizontal(&mut self, rl: &mut DrawHandle, theme: &Theme) {
+        let config = config();
+
+        let font = &self.font;
+        let font_size = config.font_size;
+
+        let data = &mut *self.manager.get_data();
+        let mut buf = vec![];
+
+        let search_bar_height = (config.font_size*3.25).ceil();
+        let mouse = get_mouse_pos(rl);
+        if (mouse.x, mouse.y) != self.last_mouse_pos {
+            self.last_mouse_pos = (mouse.x, mouse.y);
+            self.selection.mouse_moved();
+        }
+        self.selection.clear_hover();
+
+        // like dmenu, the input takes a third of the bar
+        let input_width = get_screen_width(rl) / 3.0;
+        self.input.width = Some(input_width);
+        self.input.render(rl, font, config, theme);
+        draw_rectangle(rl, input_width, 0.0, get_screen_width(rl) - input_width, search_bar_height, theme.choice_background);
+
+        // only the first line of wrapped names is shown
+        let names: Vec<&str> = self.entries.list.iter().zip(self.entries.wrap_info.iter())
+            .map(|(entry, wrap_info)| {
+                let name = wrap_info.0.text.as_deref().unwrap_or(&entry.name);
+                &name[..wrap_info.0.splits.first().copied().unwrap_or(name.len())]
+            })
+            .collect();
+        let widths: Vec<f32> = names.iter().enumerate()
+            .map(|(index, name)| {
+                let number_width = if self.jump_mode && index < 10 { font_size } else { 0.0 };
+                measure_text(font, name, font_size).x + number_width + 20.0
+            })
+            .collect();
+
+        // room is left on both sides for the arrows showing that there are more entries
+        let arrow_width = font_size * 1.5;
+        let (start, end) = (input_width + arrow_width, get_screen_width(rl) - arrow_width);
+        let text_y = (search_bar_height/2.0 - font_size/2.0).ceil();
+
+        // scroll just enough for the selected entry to be visible
+        let selected = self.selection.selected().min(widths.len().saturating_sub(1));
+        self.first_shown = self.first_shown.min(selected);
+        while self.first_shown < selected && start + widths[self.first_shown..=selected].iter().sum::<f32>() > end {
+            self.first_shown += 1;
+        }
+
+        if self.first_shown > 0 {
+            draw_text(rl, font, "<", vec2(input_width + (arrow_width - measure_text(font, "<", font_size).x)/2.0, text_y), font_size, theme.comment);
+        }
+
+        let mut x = start;
+        for (index, name) in names.iter().enumerate().skip(self.first_shown) {
+            let next_x = x + widths[index];
+            // the first entry is always shown, even if it doesn't fit
+            if next_x > end && index > self.first_shown {
+                draw_text(rl, font, ">", vec2(end + (arrow_width - measure_text(font, ">", font_size).x)/2.0, text_y), font_size, theme.comment);
+                break
+            }
+
+            let selected = self.selection.selected() == index;
+
+            let mut rectangle_color = theme.choice_background;
+            if config.mouse_enabled && mouse.y < search_bar_height && mouse.x >= x && mouse.x < next_x {
+                self.selection.hover(index);
+            }
+            if self.selection.hovered() == Some(index) {
+                rectangle_color = theme.hovered_choice_background;
+            }
+            if selected { rectangle_color = theme.selected_choice_background; }
+
+            draw_rectangle(rl, x, 0.0, next_x - x, search_bar_height, rectangle_color);
+
+            let mut offset = x + 10.0;
+            if self.jump_mode && index < 10 {
+                draw_text(rl, font, &((index + 1) % 10).to_string(), vec2(offset, text_y), font_size, theme.comment);
+                offset += font_size;
+            }
+
+            for (span, highlighted) in MatchSpan::new(name, &mut data.matcher, &data.pattern, &mut buf) {
+                let color = match highlighted {
+                    false => theme.text,
+                    true => match selected {
+                        false => theme.matched_text,
+                        true => theme.selected_matched_text
+                    }
+                };
+
+                offset = draw_text(rl, font, &name[span], vec2(offset, text_y), font_size, color).x;
+            }
+
+            x = next_x;
+        }
+
+        // fade the content from the background color
+        let visibility = self.animation.visibility();
+        if visibility < 1.0 {
+            let overlay = Color { a: (theme.background.a as f32 * (1.0 - visibility)) as u8, ..theme.background };
+            draw_rectangle(rl, 0.0, 0.0, get_screen_width(rl), get_screen_height(rl), overlay);
+        }
     }
 
     pub fn update(&mut self, rl: &mut Raylib) {
+        if self.animation.closed() {
+            quit(rl);
+            return
+        }
+
         if self.old_screen_width != get_screen_width(rl) {
-            self.entries.recalculate(rl, &self.font);
+            self.entries.recalculate(rl, &self.font, &mut self.measure_cache);
             self.old_screen_width = get_screen_width(rl);
         }
 
-        if let Some(hovered_choice) = self.hovered_choice {
+        if let Some(hovered_choice) = self.selection.hovered() {
             set_mouse_cursor(rl, MouseCursor::PointingHand);
 
             if is_mouse_button_pressed(rl, MouseButton::Left) {
                 self.message_sender.send(Message::Launch(Some(self.entries.list[hovered_choice].label))).expect("message reciever destroyed");
             }
+            // middle-click launches without closing, right-click only selects
+            if is_mouse_button_pressed(rl, MouseButton::Middle) {
+                self.message_sender.send(Message::LaunchAndStay(self.entries.list[hovered_choice].label)).expect("message reciever destroyed");
+            }
+            if is_mouse_button_pressed(rl, MouseButton::Right) {
+                self.selection.select(hovered_choice);
+            }
         } 
 
-        if self.input.update(rl) {
-            self.update_input(true);
+        if is_key_pressed(rl, Key::Enter) || is_key_pressed(rl, Key::KpEnter) {
+            let shift = is_key_down(rl, Key::LeftShift) || is_key_down(rl, Key::RightShift);
+            let message = match (shift, arguments().dmenu) {
+                (true, true) => Message::Launch(None), // print the query as typed, even if an entry matches
+                (true, false) => Message::LaunchSecondary(self.entries.list[self.selection.selected()].label),
+                (false, _) => Message::Launch(Some(self.entries.list[self.selection.selected()].label))
+            };
+            let _ = self.message_sender.send(message);
         }
 
-        if is_key_pressed(rl, Key::Enter) {
-            let _ = self.message_sender.send(Message::Launch(Some(self.entries.list[self.selected].label)));
+        if is_key_pressed(rl, Key::Escape) {
+            // the first escape only clears the query and exits plugin mode
+            if config().escape_action == EscapeAction::ClearThenClose && (!self.input.text.is_empty() || self.input.chip.is_some()) {
+                self.input.text.clear();
+                self.input.chip = None;
+                self.update_input(true);
+            } else {
+                self.animation.close();
+            }
         }
 
-        if is_key_pressed(rl, Key::Escape) { quit(rl); }
-
         // TODO: Refactor
         let snap_selected_to_edge = |rl: &mut Raylib, this: &mut Keal| { // returns the
             let search_bar_height = (config().font_size*3.25).ceil();
             let mut offset_y = 0.0;
-            for (index, wrap_info) in this.entries.wrap_info.iter().enumerate() {
-                let max_height = wrap_info.0.height.max(wrap_info.1.as_ref().map(|x| x.height).unwrap_or(0.0));
+            for (index, (entry, wrap_info)) in this.entries.list.iter().zip(this.entries.wrap_info.iter()).enumerate() {
+                let max_height = max_height(entry, wrap_info);
+                let section_height = section_height(entry);
 
-                if index == this.selected {
+                if index == this.selection.selected() {
+                    // keep the section header visible when scrolling up
                     this.scroll = this.scroll.clamp(
-                        offset_y - get_render_height(rl) + search_bar_height + max_height + 20.0,
+                        offset_y + section_height - get_render_height(rl) + search_bar_height + max_height + 2.0*config().density.row_padding(),
                         offset_y
                     );
                     break;
                 }
 
-                offset_y += max_height + 20.0;
+                offset_y += section_height + max_height + 2.0*config().density.row_padding();
             }
         };
 
         let ctrl = is_key_down(rl, Key::LeftControl) || is_key_down(rl, Key::RightControl);
+        let modifiers = Modifiers {
+            ctrl,
+            shift: is_key_down(rl, Key::LeftShift) || is_key_down(rl, Key::RightShift),
+            alt: is_key_down(rl, Key::LeftAlt) || is_key_down(rl, Key::RightAlt)
+        };
+        let keybinds = &config().keybinds;
+
+        if keybind_pressed(rl, &keybinds.jump_mode, modifiers).is_some() {
+            self.jump_mode = !self.jump_mode;
+        }
+        if keybind_pressed(rl, &keybinds.next_page, modifiers).is_some() {
+            self.manager.send(async_manager::Event::NextPage);
+        }
+
+        // custom keybinds, Ctrl+1 picks the selection and exits with 10, Ctrl+2 with 11, etc, like rofi's kb-custom-N
+        const DIGITS: [Key; 9] = [Key::One, Key::Two, Key::Three, Key::Four, Key::Five, Key::Six, Key::Seven, Key::Eight, Key::Nine];
+        if let Some(n) = DIGITS.iter().position(|&d| is_key_pressed(rl, d)).filter(|_| ctrl && arguments().dmenu) {
+            keal::set_exit_code(10 + n as i32);
+            let _ = self.message_sender.send(Message::Launch(self.entries.list.get(self.selection.selected()).map(|e| e.label)));
+        }
+
+        if self.jump_mode {
+            // digits are taken before the input sees them, the first other character is typed in the query and leaves jump mode
+            while let Some(ch) = get_char_pressed(rl) {
+                let Some(digit) = ch.to_digit(10) else {
+                    self.input.give_back(ch);
+                    break
+                };
+                let index = (digit as usize + 9) % 10; // 1 is the first entry, 0 the tenth
+
+                if index < self.entries.list.len() {
+                    self.selection.select(index);
+                    snap_selected_to_edge(rl, self);
+                }
+            }
+        }
+
+        // with the horizontal layout, Left and Right move the selection once the cursor is at the end of the query, like in dmenu
+        let horizontal_pressed = [(KeyAction::SelectNext, Key::Right), (KeyAction::SelectPrevious, Key::Left)].into_iter()
+            .filter(|_| config().layout == Layout::Horizontal && self.input.cursor_at_end())
+            .find(|&(action, key)| is_key_pressed(rl, key) && (action == KeyAction::SelectNext || self.selection.selected() > 0))
+            .map(|(action, key)| (key, action));
+        // the input would move the cursor while the arrow repeats
+        let arrow_held = self.key_repeat.held().is_some_and(|&(key, _)| matches!(key, Key::Left | Key::Right));
+
+        if horizontal_pressed.is_none() && !arrow_held && self.input.update(rl) {
+            self.jump_mode = false; // editing the query leaves jump mode
+            self.update_input(true);
+        }
 
-        if is_key_pressed_repeated(rl, Key::Down) || (ctrl && is_key_pressed_repeated(rl, Key::J)) || (ctrl && is_key_pressed_repeated(rl, Key::N)) {
-            self.selected += 1;
-            self.selected = self.selected.min(self.entries.list.len().saturating_sub(1));
-            snap_selected_to_edge(rl, self);
+        // the key moving the selection repeats at the configured rate while it is held
+        let pressed = horizontal_pressed.or_else(|| [(KeyAction::SelectNext, Key::Down, &keybinds.select_next), (KeyAction::SelectPrevious, Key::Up, &keybinds.select_previous)].into_iter()
+            .find_map(|(action, arrow, binds)| {
+                let key = is_key_pressed(rl, arrow).then_some(arrow).or_else(|| keybind_pressed(rl, binds, modifiers))?;
+                Some((key, action))
+            }));
+        if let Some(pressed) = pressed {
+            self.key_repeat.press(pressed);
+        } else if self.key_repeat.held().is_some_and(|&(key, _)| !is_key_down(rl, key)) {
+            self.key_repeat.stop();
         }
-        if is_key_pressed_repeated(rl, Key::Up) || (ctrl && is_key_pressed_repeated(rl, Key::K)) || (ctrl && is_key_pressed_repeated(rl, Key::P)) {
-            self.selected = self.selected.saturating_sub(1);
-            snap_selected_to_edge(rl, self);
+
+        match pressed.or_else(|| self.key_repeat.poll()) {
+            Some((_, KeyAction::SelectNext)) => {
+                self.selection.select_next(self.entries.list.len());
+                snap_selected_to_edge(rl, self);
+            }
+            Some((_, KeyAction::SelectPrevious)) => {
+                self.selection.select_previous();
+                snap_selected_to_edge(rl, self);
+            }
+            _ => ()
         }
+    }
 
+    /// Handles what the plugin manager sent since the last frame
+    /// Call this right before drawing, so that new entries are shown without waiting for another frame
+    pub fn handle_messages(&mut self, rl: &mut Raylib) {
         loop {
             let message = match self.message_rec.try_recv() {
                 Ok(message) => message,
@@ -389,55 +728,140 @@ impl Keal {
 
             match message {
                 Message::Launch(selected) => {
-                    self.manager.send(async_manager::Event::Launch(selected));
+                    self.manager.send(async_manager::Event::Launch(selected, false));
+                }
+                Message::LaunchSecondary(selected) => {
+                    self.manager.send(async_manager::Event::Launch(Some(selected), true));
+                }
+                Message::LaunchAndStay(selected) => {
+                    self.keep_open = true;
+                    self.manager.send(async_manager::Event::Launch(Some(selected), false));
+                }
+                Message::RenderedIcon(icon_path, path) => {
+                    let texture = path.and_then(|path| Texture::load(rl, &path).unwrap_or_else(|e| {
+                        eprintln!("failed to open icon: {e}");
+                        None
+                    }));
+                    let rendered = match texture {
+                        Some(mut texture) => {
+                            texture.set_texture_filter(TextureFilter::Bilinear);
+                            RenderedIcon::Ready(texture)
+                        }
+                        None => RenderedIcon::Failed
+                    };
+                    self.rendered_icons.insert(icon_path, rendered);
+                }
+                Message::Entries(entries) => {
+                    self.set_entries(rl, entries, true);
+                    self.fit_height(rl);
                 }
-                Message::IconCacheLoaded(icon_cache) => self.icons = icon_cache,
-                Message::Entries(entries) => self.entries = Entries::new(entries, rl, &self.font),
-                Message::Action(action) => return self.handle_action(rl, action),
+                Message::Refreshed(entries) => {
+                    self.set_entries(rl, entries, false);
+                    self.fit_height(rl);
+                }
+                Message::Action(action) => return self.handle_action(action),
             };
         }
     }
 }
 
 impl Keal {
+    /// Replaces the entries, keeping the selected entry selected
+    fn set_entries(&mut self, rl: &mut Raylib, page: Page, query_changed: bool) {
+        let selected = follow_selection(&self.entries.list, &page.entries, self.selection.selected(), query_changed);
+        self.input.indicator = page.indicator();
+
+        self.entries = Entries::new(page.entries, rl, &self.font, &mut self.measure_cache);
+        if selected != self.selection.selected() {
+            self.selection.select(selected);
+        }
+    }
+
+    /// With `auto_shrink`, resizes the window to be just as high as the entries, up to its height when it was opened
+    fn fit_height(&self, rl: &mut Raylib) {
+        let config = config();
+        if !config.auto_shrink { return }
+
+        let search_bar_height = (config.font_size*3.25).ceil();
+        let height = (search_bar_height + self.entries.total_height).min(self.max_height).ceil();
+
+        if (get_screen_height(rl) - height).abs() >= 1.0 {
+            set_window_size(rl, get_screen_width(rl) as _, height as _);
+        }
+    }
+
     pub fn update_input(&mut self, from_user: bool) {
+        // a plugin prefix followed by a space becomes a chip, the input only keeps the query
+        if self.input.chip.is_none() {
+            if let Some((prefix, query)) = self.manager.split_prefix(&self.input.text) {
+                self.input.chip = Some(prefix.to_owned());
+                self.input.text = query.to_owned();
+            }
+        }
+
         self.input.update_input(from_user);
 
-        self.manager.send(async_manager::Event::UpdateInput(self.input.text.clone(), from_user));
+        self.manager.send(async_manager::Event::UpdateInput(self.input.chip.clone(), self.input.text.clone(), from_user));
     }
 
-    fn handle_action(&mut self, rl: &mut Raylib, action: Action) /* -> Command<Message> */ {
+    fn handle_action(&mut self, action: Action) /* -> Command<Message> */ {
+        // middle-clicked entries stay open just like with `--stay-open`
+        let stay_open = arguments().stay_open || std::mem::take(&mut self.keep_open);
+
         match action {
             Action::None => (),
             Action::ChangeInput(new) => {
                 self.manager.with_manager(|m| m.kill());
+                self.input.chip = None;
                 self.input.text = new;
                 self.update_input(false);
                 // return text_input::move_cursor_to_end(text_input::Id::new("query_input"));
             }
             Action::ChangeQuery(new) => {
-                let new = self.manager.use_manager(|m| m.current().map(
-                    |plugin| format!("{} {}", plugin.prefix, new) 
-                )).unwrap_or(new);
+                // the plugin chip is kept
                 self.input.text = new;
                 self.update_input(false);
             }
-            Action::Exec(mut command) => {
-                let _ = command.0.exec();
-                quit(rl);
+            Action::Invalid(message) => self.input.invalid = Some(message),
+            Action::Sensitive => {
+                // the plugin chip is kept
+                self.input.wipe();
+                self.update_input(false);
+            }
+            Action::Exec(command) if stay_open => {
+                launch::spawn_detached(command);
+                self.reset();
+            }
+            Action::Exec(command) => {
+                launch::exec(command);
+                self.animation.close();
             }
             Action::PrintAndClose(message) => {
                 println!("{message}");
-                quit(rl);
+                if stay_open { self.reset() } else { self.animation.close() }
             }
-            Action::Fork => match fork().expect("failed to fork") {
-                Fork::Parent(_) => quit(rl),
-                Fork::Child => ()
+            Action::Fork if stay_open => {
+                self.manager.with_manager(|m| m.detach());
+                self.reset();
+            }
+            Action::Fork => {
+                self.manager.with_manager(launch::fork_plugin);
+                self.animation.close();
             }
             Action::WaitAndClose => {
                 self.manager.with_manager(|m| m.wait());
-                quit(rl);
+                if stay_open { self.reset() } else { self.animation.close() }
             }
         }
     }
+
+    /// Goes back to an empty query with the input focused, after launching something with `--stay-open`
+    fn reset(&mut self) {
+        self.input.text.clear();
+        self.input.chip = None;
+        self.selection.select(0);
+        self.scroll = 0.0;
+        // not from the user, so that the cursor is put back in the input
+        self.update_input(false);
+    }
 }
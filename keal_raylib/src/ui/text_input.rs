@@ -2,7 +2,8 @@ use std::ffi::{CStr, CString};
 
 use raylib::prelude::*;
 
-use keal::config::Config;
+use keal::{arguments::arguments, config::Config, cursor::CursorBlink, edit_history::{EditHistory, EditKind}, sensitive::{is_sensitive, zeroize}};
+use copypasta::{x11_clipboard::{Primary, X11ClipboardContext}, ClipboardProvider};
 
 use crate::config::Theme;
 
@@ -82,24 +83,50 @@ fn ceil_word_boundary(s: &str, mut index: usize) -> usize {
 pub struct TextInput {
     /// Modifying `input` should call [`Self::update_input`]
     pub text: String,
+    /// Prefix of the selected plugin, shown as a chip before the text
+    pub chip: Option<String>,
+    /// how many entries are shown out of those that matched, on the right of the input
+    pub indicator: Option<String>,
+    /// message of a plugin saying the query is invalid, shown under the input until it changes
+    pub invalid: Option<String>,
+    /// width of the input with the horizontal layout, where the entries follow it on the same line
+    /// `None` spans the whole window
+    pub width: Option<f32>,
     /// byte index of the cursor in the text input, None if the input is not selected
     cursor_index: Option<usize>,
-    cursor_tick: usize,
+    cursor: CursorBlink,
     /// byte indices of the start and end ranges of the selection
     select_range: Option<(usize, usize)>,
+    history: EditHistory,
+    /// character read from raylib's queue by someone else, typed before the ones still queued
+    given_back: Option<char>,
 
     /// wether the mouse is hovering over the input
-    hovered: bool
+    hovered: bool,
+
+    /// holds the last selected text, and is pasted with a middle-click, `None` if it couldn't be opened
+    /// raylib doesn't expose it, so it is read through X11 (or XWayland)
+    primary: Option<X11ClipboardContext<Primary>>,
+    /// what was last put in the primary selection, so that it's only set again when the selection changes
+    primary_text: String
 }
 
 impl Default for TextInput {
     fn default() -> Self {
         Self {
             text: String::new(),
+            chip: None,
+            indicator: None,
+            invalid: None,
+            width: None,
             cursor_index: Some(0),
-            cursor_tick: 0,
+            cursor: CursorBlink::default(),
             select_range: None,
-            hovered: false
+            history: EditHistory::default(),
+            given_back: None,
+            hovered: false,
+            primary: X11ClipboardContext::new().inspect_err(|e| eprintln!("failed to open primary selection: {e}")).ok(),
+            primary_text: String::new()
         }
     }
 }
@@ -112,10 +139,46 @@ impl TextInput {
 
         let size = config.font_size*1.25;
 
-        let left_padding = config.font_size;
+        let mut left_padding = config.font_size;
         let baseline = (search_bar_height/2.0 - size/2.0).ceil();
 
-        draw_rectangle_rounded(rl, 0.0, 0.0, get_screen_width(rl), search_bar_height, [5.0, 5.0, 0.0, 0.0], theme.input_background);
+        let screen_width = self.width.unwrap_or(get_screen_width(rl));
+
+        draw_rectangle_rounded(rl, 0.0, 0.0, screen_width, search_bar_height, [5.0, 5.0, 0.0, 0.0], theme.input_background);
+
+        if let Some(prompt) = arguments().prompt() {
+            draw_text(rl, font, prompt, vec2(left_padding, baseline), size, theme.input_prompt);
+            left_padding += measure_text(font, prompt, size).x + 8.0; // prompt-chip padding
+        }
+        if let Some(chip) = &self.chip {
+            let chip_width = measure_text(font, chip, config.font_size).x;
+            let y = (search_bar_height/2.0 - config.font_size/2.0).ceil();
+
+            draw_rectangle_rounded(rl, left_padding, y - 4.0, chip_width + 16.0, config.font_size + 8.0, [5.0; 4], theme.selected_choice_background);
+            draw_text(rl, font, chip, vec2(left_padding + 8.0, y), config.font_size, theme.text);
+            left_padding += chip_width + 16.0 + 8.0; // chip inner padding, and chip-text padding
+        }
+        if let Some(invalid) = &self.invalid {
+            for (x, y, width, height) in [(0.0, 0.0, screen_width, 1.0), (0.0, search_bar_height - 1.0, screen_width, 1.0), (0.0, 0.0, 1.0, search_bar_height), (screen_width - 1.0, 0.0, 1.0, search_bar_height)] {
+                draw_rectangle(rl, x, y, width, height, theme.input_error);
+            }
+
+            // with the horizontal layout, there is no room under the input, so the message takes the place of the indicator
+            if self.width.is_none() {
+                draw_rectangle(rl, 0.0, search_bar_height, screen_width, config.font_size + 10.0, theme.input_background);
+                draw_text(rl, font, invalid, vec2(config.font_size, search_bar_height + 5.0), config.font_size, theme.input_error);
+            }
+        }
+
+        let right = match &self.invalid {
+            Some(invalid) if self.width.is_some() => Some((invalid, theme.input_error)),
+            _ => self.indicator.as_ref().map(|indicator| (indicator, theme.comment))
+        };
+        if let Some((right, color)) = right {
+            let right_width = measure_text(font, right, config.font_size).x;
+            let y = (search_bar_height/2.0 - config.font_size/2.0).ceil();
+            draw_text(rl, font, right, vec2(screen_width - right_width - config.font_size, y), config.font_size, color);
+        }
         draw_text(rl, font, &text, vec2(left_padding, baseline), size, theme.text);
 
         if let Some((start, end)) = self.select_range {
@@ -125,13 +188,23 @@ impl TextInput {
         } else if let Some(cursor_index) = self.cursor_index {
             let cursor_position = if self.text.is_empty() { 0.0 } else { measure_text(font, &text[0..cursor_index], size).x };
 
-            if self.cursor_tick % 60 < 30 {
-                draw_rectangle(rl, left_padding + cursor_position - 1.0, baseline, 1.0, size + 5.0, Color::WHITE);
+            if self.cursor.visible(theme.cursor_blink_ms) {
+                draw_rectangle(rl, left_padding + cursor_position - theme.cursor_width/2.0, baseline, theme.cursor_width, size + 5.0, theme.cursor_color);
             }
         }
 
         let mouse = get_mouse_pos(rl);
-        self.hovered = mouse.y >= 0.0 && mouse.y < search_bar_height;
+        self.hovered = config.mouse_enabled && mouse.y >= 0.0 && mouse.y < search_bar_height && mouse.x < screen_width;
+    }
+
+    /// Wether the cursor is after the last character, with nothing selected
+    pub fn cursor_at_end(&self) -> bool {
+        self.select_range.is_none() && self.cursor_index.is_none_or(|index| index == self.text.len())
+    }
+
+    /// Types a character taken out of raylib's queue before the input could read it, on the next [`Self::update`]
+    pub fn give_back(&mut self, ch: char) {
+        self.given_back = Some(ch);
     }
 
     /// Returns whether the input was modified
@@ -143,6 +216,7 @@ impl TextInput {
 
             if is_mouse_button_pressed(rl, MouseButton::Left) {
                 self.cursor_index = Some(0);
+                self.history.split();
             }
         } else {
             set_mouse_cursor(rl, MouseCursor::Default);
@@ -152,10 +226,11 @@ impl TextInput {
         let shift = is_key_down(rl, Key::LeftShift) || is_key_down(rl, Key::RightShift);
 
         if let Some(cursor_index) = &mut self.cursor_index {
-            self.cursor_tick += 1;
-
             let mut modified = false;
-            while let Some(ch) = get_char_pressed(rl) {
+            while let Some(ch) = self.given_back.take().or_else(|| get_char_pressed(rl)) {
+                let kind = if self.select_range.is_some() { EditKind::Other } else { EditKind::Insert };
+                self.history.record(kind, self.chip.as_deref(), &self.text, *cursor_index);
+
                 if let Some((start, end)) = self.select_range { // remove selected text
                     *cursor_index = start;
                     self.text.drain(start..end);
@@ -165,13 +240,39 @@ impl TextInput {
                 self.text.insert(*cursor_index, ch);
                 *cursor_index += ch.len_utf8();
 
-                self.cursor_tick = 0;
+                self.cursor.reset();
                 modified = true;
             }
 
+            // pastes the primary selection, like other text fields on Linux
+            if self.hovered && is_mouse_button_pressed(rl, MouseButton::Middle) {
+                let pasted = self.primary.as_mut().and_then(|primary| primary.get_contents().ok()).filter(|text| !text.is_empty());
+                if let Some(text) = pasted {
+                    self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
+                    self.text.insert_str(*cursor_index, &text);
+                    *cursor_index += text.len();
+                    self.select_range = None;
+                    self.cursor.reset();
+                    modified = true;
+                }
+            }
+
             if ctrl {
-                if is_key_pressed(rl, Key::A) {
-                    self.select_range = Some((0, self.text.len()));
+                // readline bindings
+                if is_key_pressed(rl, Key::A) || is_key_pressed(rl, Key::E) {
+                    *cursor_index = if is_key_pressed(rl, Key::A) { 0 } else { self.text.len() };
+                    self.select_range = None;
+                    self.cursor.reset();
+                    self.history.split();
+                }
+                if (is_key_pressed_repeated(rl, Key::U) || is_key_pressed_repeated(rl, Key::W)) && *cursor_index > 0 {
+                    let start = if is_key_down(rl, Key::U) { 0 } else { floor_word_boundary(&self.text, *cursor_index) };
+
+                    self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
+                    self.text.drain(start..*cursor_index);
+                    *cursor_index = start;
+                    self.select_range = None;
+                    modified = true;
                 }
                 if is_key_pressed(rl, Key::C) {
                     if let Some((start, end)) = self.select_range {
@@ -179,8 +280,24 @@ impl TextInput {
                         set_clipboard_text(rl, &CString::new(text).unwrap());
                     }
                 }
+                if is_key_pressed_repeated(rl, Key::Z) {
+                    let snapshot = if shift {
+                        self.history.redo(self.chip.as_deref(), &self.text, *cursor_index)
+                    } else {
+                        self.history.undo(self.chip.as_deref(), &self.text, *cursor_index)
+                    };
+
+                    if let Some(snapshot) = snapshot {
+                        self.chip = snapshot.prefix;
+                        self.text = snapshot.text;
+                        *cursor_index = snapshot.cursor;
+                        self.select_range = None;
+                        modified = true;
+                    }
+                }
                 if is_key_pressed(rl, Key::X) {
                     if let Some((start, end)) = self.select_range {
+                        self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
                         *cursor_index = start; // in case we expanded the selection to the right
                         self.select_range = None;
 
@@ -191,6 +308,11 @@ impl TextInput {
                     }
                 }
                 if is_key_pressed(rl, Key::V) {
+                    let pasted = get_clipboard_text(rl).to_str().ok().filter(|text| !text.is_empty()).map(str::to_owned);
+                    if self.select_range.is_some() || pasted.is_some() {
+                        self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
+                    }
+
                     if let Some((start, end)) = self.select_range {
                         *cursor_index = start; // in case we expanded the selection to the right
                         self.text.drain(start..end);
@@ -198,19 +320,17 @@ impl TextInput {
                         modified = true;
                     }
 
-                    match get_clipboard_text(rl).to_str() {
-                        Ok(text) if !text.is_empty() => {
-                            self.text.insert_str(*cursor_index, text);
-                            *cursor_index += text.len();
-                            modified = true;
-                        }
-                        _ => (),
+                    if let Some(text) = pasted {
+                        self.text.insert_str(*cursor_index, &text);
+                        *cursor_index += text.len();
+                        modified = true;
                     }
                 }
             }
 
             if is_key_pressed_repeated(rl, Key::Left) && *cursor_index > 0 {
-                self.cursor_tick = 0;
+                self.cursor.reset();
+                self.history.split();
                 let old_index = *cursor_index;
 
                 let mut new_index = if ctrl {
@@ -241,7 +361,8 @@ impl TextInput {
                 *cursor_index = new_index;
             }
             if is_key_pressed_repeated(rl, Key::Right) && *cursor_index < self.text.len() {
-                self.cursor_tick = 0;
+                self.cursor.reset();
+                self.history.split();
                 let old_index = *cursor_index;
 
                 let mut new_index = if ctrl {
@@ -273,28 +394,67 @@ impl TextInput {
             }
             if is_key_pressed_repeated(rl, Key::Backspace) {
                 if let Some((start, end)) = self.select_range { // remove selection
+                    self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
                     *cursor_index = start; // in case we expanded the selection to the right
                     self.text.drain(start..end);
                     self.select_range = None;
                 } else if *cursor_index > 0 {
+                    self.history.record(EditKind::Delete, self.chip.as_deref(), &self.text, *cursor_index);
                     *cursor_index = floor_char_boundary(&self.text, *cursor_index);
                     self.text.remove(*cursor_index);
+                } else if self.text.is_empty() && self.chip.is_some() { // backspace on an empty query removes the plugin chip
+                    self.history.record(EditKind::Other, self.chip.as_deref(), &self.text, *cursor_index);
+                    self.chip = None;
                 }
                 modified = true;
             }
 
+            self.sync_primary();
             modified
         } else {
-            self.cursor_tick = 0;
+            self.cursor.reset();
             false
         }
     }
 
+    /// Puts the selected text in the primary selection, so that it can be pasted elsewhere with a middle-click
+    /// Nothing is put there in a sensitive session
+    fn sync_primary(&mut self) {
+        if is_sensitive() { return }
+        let Some((start, end)) = self.select_range else { return };
+        let selected = &self.text[start..end];
+        if selected.is_empty() || selected == self.primary_text { return }
+
+        self.primary_text = selected.to_owned();
+        if let Some(primary) = &mut self.primary {
+            if let Err(e) = primary.set_contents(self.primary_text.clone()) {
+                eprintln!("failed to set primary selection: {e}");
+            }
+        }
+    }
+
+    /// Empties the input and its undo history, zeroing the text they kept, for sensitive sessions
+    /// [`Self::update_input`] should be called afterwards
+    pub fn wipe(&mut self) {
+        zeroize(&mut self.text);
+        zeroize(&mut self.primary_text);
+        self.history.clear();
+        self.select_range = None;
+    }
+
     pub fn update_input(&mut self, from_user: bool) {
         match &mut self.cursor_index {
             Some(cursor_index) if from_user => *cursor_index = (*cursor_index).min(self.text.len()),
             cursor_index => *cursor_index = Some(self.text.len())
         }
         self.select_range = None;
+        self.invalid = None;
+    }
+}
+
+impl Drop for TextInput {
+    fn drop(&mut self) {
+        zeroize(&mut self.text);
+        zeroize(&mut self.primary_text);
     }
 }
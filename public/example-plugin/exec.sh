@@ -0,0 +1,17 @@
+#!/bin/sh
+# see https://github.com/davawen/keal#plugins for how plugins talk with keal
+
+echo 'events:enter'
+echo 'name:Hello'
+echo 'comment:Replace the query with a greeting'
+echo 'name:Read about plugins'
+echo 'comment:Open the documentation in a browser'
+echo 'end'
+
+while read -r event; do
+    read -r index
+    case "$index" in
+        0) echo 'action:change_query:Hello from the example plugin!'; exit ;;
+        1) echo 'action:fork'; exec xdg-open https://github.com/davawen/keal#plugins ;;
+    esac
+done